@@ -0,0 +1,94 @@
+//! Measures how many instructions per second the interpreter runs for every
+//! ROM in roms/, uncapped and without a window, with and without the decode
+//! cache. Like criterion, each measurement is sampled several times after a
+//! warm-up and the median is reported. Run with `cargo bench`.
+
+extern crate chip8;
+
+use chip8::cpu::Cpu;
+use chip8::rom;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const ROM_DIRECTORY: &str = "roms";
+const INSTRUCTIONS_PER_SAMPLE: u32 = 200_000;
+const SAMPLES: usize = 9;
+const CLOCK_SPEED: u32 = 360;
+
+fn main() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(ROM_DIRECTORY)
+        .expect("roms/ should be readable")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| rom::has_rom_extension(&path.to_string_lossy()))
+        .collect();
+    paths.sort();
+
+    println!(
+        "{:<18} {:>16} {:>16} {:>8}",
+        "ROM", "cached IPS", "uncached IPS", "speedup"
+    );
+    let mut totals = (0.0, 0.0);
+    for path in paths.iter() {
+        let data = fs::read(path).expect("ROM should be readable");
+        let cached = instructions_per_second(&data, true);
+        let uncached = instructions_per_second(&data, false);
+        totals.0 += cached;
+        totals.1 += uncached;
+        println!(
+            "{:<18} {:>16.0} {:>16.0} {:>7.2}x",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            cached,
+            uncached,
+            cached / uncached
+        );
+    }
+
+    let count = paths.len().max(1) as f64;
+    println!(
+        "{:<18} {:>16.0} {:>16.0} {:>7.2}x",
+        "mean",
+        totals.0 / count,
+        totals.1 / count,
+        totals.0 / totals.1
+    );
+}
+
+/// The median rate over all samples
+fn instructions_per_second(data: &[u8], cached: bool) -> f64 {
+    // Warm up the caches of both the CPU and the emulator
+    sample(data, cached);
+
+    let mut rates: Vec<f64> = (0..SAMPLES)
+        .map(|_| {
+            let elapsed = sample(data, cached);
+            INSTRUCTIONS_PER_SAMPLE as f64 / seconds(elapsed)
+        })
+        .collect();
+    rates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    rates[rates.len() / 2]
+}
+
+/// Runs a fixed number of instructions, starting the ROM over whenever it
+/// stops on an instruction the emulator does not support
+fn sample(data: &[u8], cached: bool) -> Duration {
+    let mut cpu = Cpu::new(data.to_vec(), CLOCK_SPEED, false);
+    cpu.set_decode_cache(cached);
+
+    let start = Instant::now();
+    let mut executed = 0;
+    while executed < INSTRUCTIONS_PER_SAMPLE {
+        let before = cpu.state().tick;
+        cpu.cycle();
+        executed += 1;
+        if cpu.faulted || cpu.state().tick == before {
+            cpu.reset(data.to_vec());
+        }
+    }
+    start.elapsed()
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
@@ -0,0 +1,122 @@
+use instruction::Value;
+
+/// The register to register operations of the 8XYN instructions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    Load,
+    Or,
+    And,
+    Xor,
+    Add,
+    Sub,
+    ShiftRight,
+    SubReverse,
+    ShiftLeft,
+}
+
+/// What an operation stores in VX and, if it sets one, in VF. The flag is
+/// written after the result, so it wins when VX is VF.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Output {
+    pub result: Value,
+    pub flag: Option<Value>,
+}
+
+impl Operation {
+    /// Whether the operation is a bitwise one, which some interpreters
+    /// follow by resetting VF
+    pub fn is_logic(&self) -> bool {
+        matches!(*self, Operation::Or | Operation::And | Operation::Xor)
+    }
+
+    pub fn is_shift(&self) -> bool {
+        matches!(*self, Operation::ShiftRight | Operation::ShiftLeft)
+    }
+}
+
+/// Applies an operation to the values of VX and VY. All arithmetic wraps,
+/// and shifts only use `x`, which the caller picks from VX or VY.
+pub fn apply(operation: Operation, x: Value, y: Value) -> Output {
+    let (result, flag) = match operation {
+        Operation::Load => (y, None),
+        Operation::Or => (x | y, None),
+        Operation::And => (x & y, None),
+        Operation::Xor => (x ^ y, None),
+        Operation::Add => {
+            let (result, carry) = x.overflowing_add(y);
+            (result, Some(carry as Value))
+        }
+        Operation::Sub => {
+            let (result, borrow) = x.overflowing_sub(y);
+            (result, Some(!borrow as Value))
+        }
+        Operation::ShiftRight => (x >> 1, Some(x & 0x01)),
+        Operation::SubReverse => {
+            let (result, borrow) = y.overflowing_sub(x);
+            (result, Some(!borrow as Value))
+        }
+        Operation::ShiftLeft => (x << 1, Some(x >> 7)),
+    };
+    Output { result, flag }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks an operation against wider arithmetic for every pair of values
+    fn check_all_pairs<F>(operation: Operation, expected: F)
+    where
+        F: Fn(u16, u16) -> (u16, Option<u16>),
+    {
+        for x in 0..=0xFFu16 {
+            for y in 0..=0xFFu16 {
+                let (result, flag) = expected(x, y);
+                assert_eq!(
+                    Output {
+                        result: result as Value,
+                        flag: flag.map(|flag| flag as Value),
+                    },
+                    apply(operation, x as Value, y as Value),
+                    "{:?} with 0x{:02X}, 0x{:02X}",
+                    operation,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bitwise_operations_leave_the_flag() {
+        check_all_pairs(Operation::Load, |_, y| (y, None));
+        check_all_pairs(Operation::Or, |x, y| (x | y, None));
+        check_all_pairs(Operation::And, |x, y| (x & y, None));
+        check_all_pairs(Operation::Xor, |x, y| (x ^ y, None));
+    }
+
+    #[test]
+    fn add_sets_the_carry() {
+        check_all_pairs(Operation::Add, |x, y| {
+            ((x + y) & 0xFF, Some((x + y > 0xFF) as u16))
+        });
+    }
+
+    #[test]
+    fn subtractions_set_not_borrow() {
+        check_all_pairs(Operation::Sub, |x, y| {
+            ((0x100 + x - y) & 0xFF, Some((x >= y) as u16))
+        });
+        check_all_pairs(Operation::SubReverse, |x, y| {
+            ((0x100 + y - x) & 0xFF, Some((y >= x) as u16))
+        });
+    }
+
+    #[test]
+    fn shifts_set_the_bit_shifted_out() {
+        check_all_pairs(Operation::ShiftRight, |x, _| (x / 2, Some(x % 2)));
+        check_all_pairs(Operation::ShiftLeft, |x, _| {
+            ((x * 2) & 0xFF, Some((x >= 0x80) as u16))
+        });
+    }
+}
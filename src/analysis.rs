@@ -0,0 +1,269 @@
+use display::SPRITES;
+use instruction::{Address, Instruction};
+use platform::Platform;
+use std::collections::HashMap;
+use std::fmt;
+
+const EXECUTED: u8 = 0b0001;
+const READ: u8 = 0b0010;
+const WRITTEN: u8 = 0b0100;
+/// Loaded with the font or the ROM, or written by the program
+const INITIALIZED: u8 = 0b1000;
+
+/// Something a ROM did that is legal but usually a bug, or at least worth knowing about
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Suspicion {
+    /// Memory that was executed as an instruction was overwritten
+    WriteToCode(Address),
+    /// Memory below the program, where the interpreter and font live, was written
+    WriteToInterpreter(Address),
+    /// An instruction was executed from memory that was read or written as data
+    DataExecuted(Address),
+    /// Memory that was never loaded or written was read or executed
+    UninitializedRead(Address),
+    /// A jump or call targets an odd address
+    OddJump(Address),
+}
+
+impl fmt::Display for Suspicion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Suspicion::WriteToCode(address) => {
+                write!(f, "wrote to 0x{:03X}, which was executed as code", address)
+            }
+            Suspicion::WriteToInterpreter(address) => write!(
+                f,
+                "wrote to 0x{:03X}, in the interpreter and font area",
+                address
+            ),
+            Suspicion::DataExecuted(address) => {
+                write!(f, "executed 0x{:03X}, which was used as data", address)
+            }
+            Suspicion::UninitializedRead(address) => {
+                write!(f, "read 0x{:03X}, which was never initialized", address)
+            }
+            Suspicion::OddJump(address) => write!(f, "jumped to odd address 0x{:03X}", address),
+        }
+    }
+}
+
+/// The first time a suspicious thing happened and how often it happened
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Finding {
+    pub suspicion: Suspicion,
+    pub pc: Address,
+    pub cycle: u32,
+    pub count: u64,
+}
+
+/// Watches memory accesses and control flow for suspicious behavior
+pub struct Analyzer {
+    flags: Vec<u8>,
+    program_start: usize,
+    pc: Address,
+    cycle: u32,
+    findings: Vec<Finding>,
+    /// Position of each suspicion in `findings`
+    seen: HashMap<Suspicion, usize>,
+}
+
+impl Analyzer {
+    /// An analyzer for a ROM loaded as on `platform`
+    pub fn new(rom_size: usize, platform: Platform) -> Analyzer {
+        let program_start = platform.program_start();
+        let memory_size = platform.memory_size();
+        let mut flags = vec![0; memory_size];
+        let rom_end = (program_start + rom_size).min(memory_size);
+        for flag in flags[..SPRITES.len()].iter_mut() {
+            *flag = INITIALIZED;
+        }
+        for flag in flags[program_start..rom_end].iter_mut() {
+            *flag = INITIALIZED;
+        }
+
+        Analyzer {
+            flags,
+            program_start,
+            pc: program_start as Address,
+            cycle: 0,
+            findings: Vec::new(),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Everything found so far in the order it first happened
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Called before an instruction at `pc` is executed
+    pub fn executing(&mut self, pc: Address, cycle: u32, instruction: &Instruction) {
+        self.pc = pc;
+        self.cycle = cycle;
+
+        let addresses = [pc as usize, pc as usize + 1];
+        let uninitialized = addresses
+            .iter()
+            .find(|&&address| self.flags(address) & INITIALIZED == 0);
+        if let Some(&address) = uninitialized {
+            self.report(Suspicion::UninitializedRead(address as Address));
+        } else if addresses
+            .iter()
+            .any(|&address| self.flags(address) & (READ | WRITTEN) != 0)
+        {
+            self.report(Suspicion::DataExecuted(pc));
+        }
+        for &address in addresses.iter() {
+            self.mark(address, EXECUTED);
+        }
+
+        match *instruction {
+            Instruction::Jump(target) | Instruction::Call(target) if target % 2 == 1 => {
+                self.report(Suspicion::OddJump(target))
+            }
+            _ => {}
+        }
+    }
+
+    pub fn read(&mut self, address: usize) {
+        if self.flags(address) & INITIALIZED == 0 {
+            self.report(Suspicion::UninitializedRead(address as Address));
+        }
+        self.mark(address, READ);
+    }
+
+    pub fn written(&mut self, address: usize) {
+        if address < self.program_start {
+            self.report(Suspicion::WriteToInterpreter(address as Address));
+        } else if self.flags(address) & EXECUTED != 0 {
+            self.report(Suspicion::WriteToCode(address as Address));
+        }
+        self.mark(address, WRITTEN | INITIALIZED);
+    }
+
+    fn flags(&self, address: usize) -> u8 {
+        self.flags.get(address).cloned().unwrap_or(0)
+    }
+
+    fn mark(&mut self, address: usize, flag: u8) {
+        if let Some(flags) = self.flags.get_mut(address) {
+            *flags |= flag;
+        }
+    }
+
+    fn report(&mut self, suspicion: Suspicion) {
+        if let Some(&index) = self.seen.get(&suspicion) {
+            self.findings[index].count += 1;
+            return;
+        }
+        self.seen.insert(suspicion, self.findings.len());
+        self.findings.push(Finding {
+            suspicion,
+            pc: self.pc,
+            cycle: self.cycle,
+            count: 1,
+        });
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}, PC 0x{:03X}: {}",
+            self.cycle, self.pc, self.suspicion
+        )?;
+        if self.count > 1 {
+            write!(f, " ({} times)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::Cpu;
+
+    fn run(rom: Vec<u8>, cycles: usize) -> Vec<Suspicion> {
+        run_on(Platform::Chip8, rom, cycles)
+    }
+
+    fn run_on(platform: Platform, rom: Vec<u8>, cycles: usize) -> Vec<Suspicion> {
+        let mut cpu = Cpu::with_platform(rom.clone(), 360, false, platform);
+        cpu.set_analyzer(Analyzer::new(rom.len(), platform));
+        for _ in 0..cycles {
+            cpu.cycle();
+        }
+        let analyzer = cpu.take_analyzer().unwrap();
+        analyzer
+            .findings()
+            .iter()
+            .map(|finding| finding.suspicion)
+            .collect()
+    }
+
+    #[test]
+    fn self_modifying_code_is_found() {
+        // LD I, 0x200; LD [I], V0
+        let findings = run(vec![0xA2, 0x00, 0xF0, 0x55], 2);
+        assert_eq!(vec![Suspicion::WriteToCode(0x200)], findings);
+    }
+
+    #[test]
+    fn writes_to_font_are_found() {
+        // LD I, 0x000; LD B, V0
+        let findings = run(vec![0xA0, 0x00, 0xF0, 0x33], 2);
+        assert_eq!(
+            vec![
+                Suspicion::WriteToInterpreter(0x000),
+                Suspicion::WriteToInterpreter(0x001),
+                Suspicion::WriteToInterpreter(0x002),
+            ],
+            findings
+        );
+    }
+
+    #[test]
+    fn chip8x_interpreter_extends_to_its_program() {
+        // LD I, 0x2FF; LD [I], V0; LD I, 0x308; LD V0, [I] from the ROM
+        let rom = vec![0xA2, 0xFF, 0xF0, 0x55, 0xA3, 0x08, 0xF0, 0x65, 0x00, 0x00];
+        assert_eq!(
+            vec![Suspicion::WriteToInterpreter(0x2FF)],
+            run_on(Platform::Chip8X, rom, 4)
+        );
+    }
+
+    #[test]
+    fn odd_jumps_and_uninitialized_memory_are_found() {
+        // JP 0x203 into the middle of the next instruction, then off the end of the ROM
+        let findings = run(vec![0x12, 0x03, 0x60, 0x00], 2);
+        assert_eq!(
+            vec![
+                Suspicion::OddJump(0x203),
+                Suspicion::UninitializedRead(0x204),
+            ],
+            findings
+        );
+    }
+
+    #[test]
+    fn executed_data_is_found_once_with_a_count() {
+        // LD I, 0x206; DRW V0, V0, 2; JP 0x206 over the sprite it drew
+        let mut cpu = Cpu::new(
+            vec![0xA2, 0x06, 0xD0, 0x02, 0x12, 0x06, 0x12, 0x06],
+            360,
+            false,
+        );
+        cpu.set_analyzer(Analyzer::new(8, Platform::Chip8));
+        for _ in 0..5 {
+            cpu.cycle();
+        }
+        let analyzer = cpu.take_analyzer().unwrap();
+        let finding = analyzer.findings()[0];
+        assert_eq!(Suspicion::DataExecuted(0x206), finding.suspicion);
+        assert_eq!(0x206, finding.pc);
+        assert_eq!(3, finding.cycle);
+        assert_eq!(2, finding.count);
+    }
+}
@@ -0,0 +1,63 @@
+extern crate chip8;
+extern crate image;
+extern crate sha1;
+extern crate yaml_rust;
+
+#[macro_use]
+extern crate clap;
+
+mod manifest;
+mod report;
+mod runner;
+#[cfg(test)]
+#[path = "../../testing.rs"]
+mod testing;
+
+use clap::App;
+use report::Totals;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::thread;
+
+fn main() {
+    let yaml = load_yaml!("cli.yml");
+    let matches = App::from_yaml(yaml).get_matches();
+
+    let manifest_path = Path::new(matches.value_of("MANIFEST").unwrap_or_default());
+    let tests = match manifest::load(manifest_path) {
+        Ok(tests) => tests,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(2);
+        }
+    };
+    let jobs = if matches.is_present("jobs") {
+        value_t!(matches, "jobs", usize).unwrap_or_else(|e| e.exit())
+    } else {
+        thread::available_parallelism().map_or(1, |jobs| jobs.get())
+    };
+
+    let results = runner::run_all(tests, jobs);
+
+    let stdout = io::stdout();
+    if let Err(e) = report::write_text(&results, &mut stdout.lock()) {
+        println!("Unable to write the report: {}", e);
+    }
+    if let Some(path) = matches.value_of("junit") {
+        let written = File::create(path).map(BufWriter::new).and_then(|mut out| {
+            report::write_junit(&results, &mut out)?;
+            out.flush()
+        });
+        if let Err(e) = written {
+            println!("Unable to write {}: {}", path, e);
+            process::exit(2);
+        }
+    }
+
+    if !Totals::of(&results).all_passed() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,250 @@
+use chip8::metadata;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+use chip8::timing::Timing;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use yaml_rust::{Yaml, YamlLoader};
+
+const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 6;
+const DEFAULT_TIMEOUT_SECONDS: f64 = 10.0;
+const FRAMES_PER_SECOND: u32 = 60;
+
+/// One ROM to run and what its screen should look like afterwards
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub rom: PathBuf,
+    /// None to guess it from the ROM
+    pub platform: Option<Platform>,
+    /// Instructions to run before comparing the screen
+    pub cycles: u64,
+    pub clock_speed: u32,
+    pub timing: Timing,
+    pub quirks: Quirks,
+    /// Key presses and releases, ordered by cycle
+    pub input: Vec<InputEvent>,
+    pub expected: Expected,
+    /// Wall clock time the test may take
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputEvent {
+    /// The event happens before this instruction is run
+    pub cycle: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// How the final screen is checked. With neither, a test passes as long as
+/// the ROM runs for all of its cycles.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Expected {
+    /// SHA-1 of the screen, as printed by a failing test
+    pub hash: Option<String>,
+    /// A screenshot to compare against, e.g. one saved with --screenshot
+    pub png: Option<PathBuf>,
+}
+
+/// Reads a manifest, resolving the paths in it relative to its directory
+pub fn load(path: &Path) -> Result<Vec<TestCase>, String> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    parse(&source, directory).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse(source: &str, directory: &Path) -> Result<Vec<TestCase>, String> {
+    let documents = YamlLoader::load_from_str(source).map_err(|e| e.to_string())?;
+    let root = documents.into_iter().next().unwrap_or(Yaml::Null);
+    let tests = root["tests"]
+        .as_vec()
+        .ok_or_else(|| String::from("Expected a list of tests"))?;
+
+    tests
+        .iter()
+        .enumerate()
+        .map(|(index, fields)| {
+            let name = fields["name"]
+                .as_str()
+                .map(String::from)
+                .unwrap_or_else(|| format!("test {}", index + 1));
+            parse_test(name.clone(), fields, directory).map_err(|e| format!("{}: {}", name, e))
+        })
+        .collect()
+}
+
+fn parse_test(name: String, fields: &Yaml, directory: &Path) -> Result<TestCase, String> {
+    let rom = fields["rom"]
+        .as_str()
+        .map(|rom| directory.join(rom))
+        .ok_or_else(|| String::from("Missing rom"))?;
+
+    let platform = match fields["platform"] {
+        Yaml::BadValue => None,
+        ref other => Some(
+            other
+                .as_str()
+                .and_then(Platform::from_name)
+                .ok_or_else(|| format!("Invalid platform {:?}", other))?,
+        ),
+    };
+
+    let cycles = match fields["cycles"] {
+        Yaml::Integer(cycles) if cycles > 0 => cycles as u64,
+        ref other => return Err(format!("Invalid cycles {:?}", other)),
+    };
+
+    let instructions_per_frame = match fields["ipf"] {
+        Yaml::BadValue => DEFAULT_INSTRUCTIONS_PER_FRAME,
+        Yaml::Integer(ipf) if ipf > 0 => ipf as u32,
+        ref other => return Err(format!("Invalid ipf {:?}", other)),
+    };
+
+    let timing = match fields["timing"] {
+        Yaml::BadValue => Timing::Simple,
+        ref other => other
+            .as_str()
+            .and_then(Timing::from_name)
+            .ok_or_else(|| format!("Invalid timing {:?}", other))?,
+    };
+
+    let mut quirks = Quirks::default();
+    if let Some(names) = fields["quirks"].as_hash() {
+        for (name, enabled) in names {
+            let name = name.as_str().unwrap_or("");
+            let enabled = enabled
+                .as_bool()
+                .ok_or_else(|| format!("Quirk '{}' must be true or false", name))?;
+            quirks.set(name, enabled)?;
+        }
+    }
+
+    let mut input = Vec::new();
+    for event in fields["input"].as_vec().unwrap_or(&Vec::new()) {
+        input.push(parse_input_event(event)?);
+    }
+    input.sort_by_key(|event| event.cycle);
+
+    let expected = Expected {
+        hash: fields["expect"]["hash"].as_str().map(str::to_lowercase),
+        png: fields["expect"]["png"]
+            .as_str()
+            .map(|png| directory.join(png)),
+    };
+
+    let timeout = match fields["timeout"] {
+        Yaml::BadValue => DEFAULT_TIMEOUT_SECONDS,
+        Yaml::Integer(seconds) => seconds as f64,
+        ref other => other.as_f64().unwrap_or(0.0),
+    };
+    if timeout <= 0.0 {
+        return Err(format!("Invalid timeout {:?}", fields["timeout"]));
+    }
+
+    Ok(TestCase {
+        name,
+        rom,
+        platform,
+        cycles,
+        clock_speed: instructions_per_frame * FRAMES_PER_SECOND,
+        timing,
+        quirks,
+        input,
+        expected,
+        timeout: Duration::from_millis((timeout * 1000.0) as u64),
+    })
+}
+
+/// An event such as `{cycle: 300, press: 5}` or `{cycle: 320, release: A}`
+fn parse_input_event(fields: &Yaml) -> Result<InputEvent, String> {
+    let cycle = match fields["cycle"] {
+        Yaml::Integer(cycle) if cycle >= 0 => cycle as u64,
+        ref other => return Err(format!("Invalid input cycle {:?}", other)),
+    };
+    let (key, pressed) = match (&fields["press"], &fields["release"]) {
+        (key, &Yaml::BadValue) => (key, true),
+        (&Yaml::BadValue, key) => (key, false),
+        _ => return Err(String::from("An input event either presses or releases")),
+    };
+    let key = metadata::parse_key(key).ok_or_else(|| format!("Invalid key {:?}", key))?;
+    Ok(InputEvent {
+        cycle,
+        key,
+        pressed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "
+tests:
+  - name: pong
+    rom: roms/pong.rom
+    platform: hires
+    cycles: 1000
+    ipf: 10
+    timing: vip
+    timeout: 2.5
+    quirks:
+      vf-reset: true
+    input:
+      - {cycle: 300, release: A}
+      - {cycle: 200, press: A}
+    expect:
+      hash: 0123ABCD
+  - rom: roms/tictac.rom
+    cycles: 50
+";
+
+    #[test]
+    fn tests_are_parsed_with_defaults() {
+        let tests = parse(MANIFEST, Path::new("ci")).unwrap();
+        assert_eq!(2, tests.len());
+
+        let pong = &tests[0];
+        assert_eq!("pong", pong.name);
+        assert_eq!(Path::new("ci/roms/pong.rom"), pong.rom);
+        assert_eq!(Some(Platform::HiresChip8), pong.platform);
+        assert_eq!(600, pong.clock_speed);
+        assert_eq!(Timing::Vip, pong.timing);
+        assert!(pong.quirks.logic_resets_vf);
+        assert_eq!(Duration::from_millis(2500), pong.timeout);
+        assert_eq!(
+            vec![
+                InputEvent {
+                    cycle: 200,
+                    key: 0xA,
+                    pressed: true
+                },
+                InputEvent {
+                    cycle: 300,
+                    key: 0xA,
+                    pressed: false
+                },
+            ],
+            pong.input
+        );
+        assert_eq!(Some(String::from("0123abcd")), pong.expected.hash);
+
+        let tictac = &tests[1];
+        assert_eq!("test 2", tictac.name);
+        assert_eq!(None, tictac.platform);
+        assert_eq!(360, tictac.clock_speed);
+        assert_eq!(Timing::Simple, tictac.timing);
+        assert_eq!(Expected::default(), tictac.expected);
+    }
+
+    #[test]
+    fn invalid_tests_are_named_in_errors() {
+        let error = parse("tests:\n  - name: broken\n    rom: x.rom\n", Path::new("")).unwrap_err();
+        assert_eq!("broken: Invalid cycles BadValue", error);
+    }
+}
@@ -0,0 +1,186 @@
+use runner::{Outcome, TestResult};
+use std::io;
+use std::io::Write;
+use std::time::Duration;
+
+/// Number of results with each outcome
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Totals {
+    pub passed: usize,
+    pub failed: usize,
+    pub faulted: usize,
+    pub timed_out: usize,
+    pub errors: usize,
+}
+
+impl Totals {
+    pub fn of(results: &[TestResult]) -> Totals {
+        let mut totals = Totals::default();
+        for result in results {
+            match result.outcome {
+                Outcome::Pass => totals.passed += 1,
+                Outcome::Fail(_) => totals.failed += 1,
+                Outcome::Fault(_) => totals.faulted += 1,
+                Outcome::Timeout => totals.timed_out += 1,
+                Outcome::Error(_) => totals.errors += 1,
+            }
+        }
+        totals
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.failed + self.faulted + self.timed_out + self.errors == 0
+    }
+}
+
+/// One line per test followed by the totals
+pub fn write_text<W: Write>(results: &[TestResult], out: &mut W) -> io::Result<()> {
+    for result in results {
+        let (status, detail) = match result.outcome {
+            Outcome::Pass => ("PASS", String::new()),
+            Outcome::Fail(ref message) => ("FAIL", message.clone()),
+            Outcome::Fault(ref message) => ("FAULT", message.clone()),
+            Outcome::Timeout => ("TIMEOUT", String::from("Ran out of time")),
+            Outcome::Error(ref message) => ("ERROR", message.clone()),
+        };
+        writeln!(
+            out,
+            "{:<8} {} ({} cycles, {} ms)",
+            status,
+            result.name,
+            result.cycles,
+            milliseconds(result.duration)
+        )?;
+        if !detail.is_empty() {
+            writeln!(out, "         {}", detail)?;
+        }
+    }
+
+    let totals = Totals::of(results);
+    writeln!(
+        out,
+        "\n{} tests: {} passed, {} failed, {} faulted, {} timed out, {} errors",
+        results.len(),
+        totals.passed,
+        totals.failed,
+        totals.faulted,
+        totals.timed_out,
+        totals.errors
+    )
+}
+
+/// A JUnit XML report as read by CI servers. Failed tests are failures, and
+/// faults, timeouts and errors are errors.
+pub fn write_junit<W: Write>(results: &[TestResult], out: &mut W) -> io::Result<()> {
+    let totals = Totals::of(results);
+    let total_time = results
+        .iter()
+        .fold(Duration::from_secs(0), |total, result| {
+            total + result.duration
+        });
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<testsuite name=\"chip8\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+        results.len(),
+        totals.failed,
+        totals.faulted + totals.timed_out + totals.errors,
+        seconds(total_time)
+    )?;
+    for result in results {
+        let open = format!(
+            "  <testcase classname=\"chip8\" name=\"{}\" time=\"{:.3}\"",
+            escape(&result.name),
+            seconds(result.duration)
+        );
+        let child = match result.outcome {
+            Outcome::Pass => None,
+            Outcome::Fail(ref message) => Some(("failure", "mismatch", message.clone())),
+            Outcome::Fault(ref message) => Some(("error", "fault", message.clone())),
+            Outcome::Timeout => Some((
+                "error",
+                "timeout",
+                format!("Ran out of time after {} cycles", result.cycles),
+            )),
+            Outcome::Error(ref message) => Some(("error", "error", message.clone())),
+        };
+        match child {
+            None => writeln!(out, "{}/>", open)?,
+            Some((element, kind, message)) => {
+                writeln!(out, "{}>", open)?;
+                writeln!(
+                    out,
+                    "    <{} type=\"{}\" message=\"{}\"/>",
+                    element,
+                    kind,
+                    escape(&message)
+                )?;
+                writeln!(out, "  </testcase>")?;
+            }
+        }
+    }
+    writeln!(out, "</testsuite>")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn milliseconds(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Vec<TestResult> {
+        let result = |name: &str, outcome| TestResult {
+            name: String::from(name),
+            outcome,
+            cycles: 100,
+            duration: Duration::from_millis(1500),
+        };
+        vec![
+            result("pong", Outcome::Pass),
+            result("tetris <title>", Outcome::Fail(String::from("hash \"x\""))),
+            result("ufo", Outcome::Timeout),
+        ]
+    }
+
+    #[test]
+    fn text_report_lists_every_test() {
+        let mut out = Vec::new();
+        write_text(&results(), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("PASS     pong (100 cycles, 1500 ms)\n"));
+        assert!(
+            text.contains("FAIL     tetris <title> (100 cycles, 1500 ms)\n         hash \"x\"\n")
+        );
+        assert!(text.ends_with("3 tests: 1 passed, 1 failed, 0 faulted, 1 timed out, 0 errors\n"));
+    }
+
+    #[test]
+    fn junit_report_is_escaped() {
+        let mut out = Vec::new();
+        write_junit(&results(), &mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains(
+            "<testsuite name=\"chip8\" tests=\"3\" failures=\"1\" errors=\"1\" time=\"4.500\">"
+        ));
+        assert!(xml.contains("  <testcase classname=\"chip8\" name=\"pong\" time=\"1.500\"/>\n"));
+        assert!(xml.contains(
+            "name=\"tetris &lt;title&gt;\" time=\"1.500\">\n    <failure type=\"mismatch\" message=\"hash &quot;x&quot;\"/>\n  </testcase>"
+        ));
+        assert!(
+            xml.contains("<error type=\"timeout\" message=\"Ran out of time after 100 cycles\"/>")
+        );
+    }
+}
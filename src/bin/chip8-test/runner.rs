@@ -0,0 +1,355 @@
+use chip8::cpu::Cpu;
+use chip8::display::Frame;
+use chip8::platform::Platform;
+use chip8::rom;
+use image;
+use manifest::TestCase;
+use sha1::Sha1;
+use std::any::Any;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Instructions run between checks of the wall clock
+const TIMEOUT_CHECK_INTERVAL: u64 = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Pass,
+    /// The screen did not look as expected
+    Fail(String),
+    /// The ROM ran into an instruction the emulator does not support
+    Fault(String),
+    /// The cycle budget was not used up in time
+    Timeout,
+    /// The test could not be run, e.g. because its ROM is missing
+    Error(String),
+}
+
+pub struct TestResult {
+    pub name: String,
+    pub outcome: Outcome,
+    pub cycles: u64,
+    pub duration: Duration,
+}
+
+/// Runs every test on `jobs` threads, returning the results in manifest order
+pub fn run_all(tests: Vec<TestCase>, jobs: usize) -> Vec<TestResult> {
+    let count = tests.len();
+    let tests = Arc::new(tests);
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..jobs.max(1).min(count.max(1)))
+        .map(|_| {
+            let tests = tests.clone();
+            let next = next.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= tests.len() {
+                    break;
+                }
+                // A panic in the emulator only takes down the test that caused it
+                let test = &tests[index];
+                let result = panic::catch_unwind(AssertUnwindSafe(|| run(test)))
+                    .unwrap_or_else(|cause| crashed(test, &*cause));
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut results: Vec<Option<TestResult>> = (0..count).map(|_| None).collect();
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    results
+        .into_iter()
+        .zip(tests.iter())
+        .map(|(result, test)| {
+            result.unwrap_or_else(|| TestResult {
+                name: test.name.clone(),
+                outcome: Outcome::Error(String::from("The test crashed the emulator")),
+                cycles: 0,
+                duration: Duration::from_secs(0),
+            })
+        })
+        .collect()
+}
+
+/// The result of a test whose run panicked, with the panic's message
+fn crashed(test: &TestCase, cause: &(dyn Any + Send)) -> TestResult {
+    let message = cause
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| cause.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    TestResult {
+        name: test.name.clone(),
+        outcome: Outcome::Error(format!("The test crashed the emulator: {}", message)),
+        cycles: 0,
+        duration: Duration::from_secs(0),
+    }
+}
+
+pub fn run(test: &TestCase) -> TestResult {
+    let start = Instant::now();
+    let (outcome, cycles) = execute(test, start);
+    TestResult {
+        name: test.name.clone(),
+        outcome,
+        cycles,
+        duration: start.elapsed(),
+    }
+}
+
+fn execute(test: &TestCase, start: Instant) -> (Outcome, u64) {
+    let loaded = rom::load(&test.rom.to_string_lossy()).and_then(|rom| {
+        let platform = test.platform.unwrap_or_else(|| Platform::detect(&rom.data));
+        rom.check_fits(platform).map(|_| (rom, platform))
+    });
+    let (data, platform) = match loaded {
+        Ok((rom, platform)) => (rom.data, platform),
+        Err(e) => {
+            let message = format!("Unable to load {}: {}", test.rom.display(), e);
+            return (Outcome::Error(message), 0);
+        }
+    };
+    let mut cpu = Cpu::with_platform(data, test.clock_speed, false, platform);
+    cpu.set_quirks(test.quirks);
+    cpu.set_timing(test.timing);
+
+    let mut input = test.input.iter().peekable();
+    let mut cycle = 0;
+    while cycle < test.cycles {
+        while let Some(event) = input.next_if(|event| event.cycle <= cycle) {
+            cpu.set_key(event.key, event.pressed);
+        }
+
+        cpu.cycle();
+        if cpu.faulted {
+            return (Outcome::Fault(fault_message(&cpu)), cycle);
+        }
+        cycle += 1;
+
+        if cycle % TIMEOUT_CHECK_INTERVAL == 0 && start.elapsed() > test.timeout {
+            return (Outcome::Timeout, cycle);
+        }
+    }
+
+    (check_screen(test, &cpu.display.frame()), cycle)
+}
+
+fn fault_message(cpu: &Cpu) -> String {
+    let state = cpu.state();
+    let pc = state.pc as usize;
+    match state.memory.get(pc..pc + 2) {
+        Some(bytes) => format!(
+            "Unsupported instruction {:02X}{:02X} at 0x{:03X}",
+            bytes[0], bytes[1], pc
+        ),
+        None => format!("Ran off the end of memory at 0x{:03X}", pc),
+    }
+}
+
+fn check_screen(test: &TestCase, frame: &Frame) -> Outcome {
+    if let Some(ref expected) = test.expected.hash {
+        let actual = screen_hash(frame);
+        if actual != *expected {
+            return Outcome::Fail(format!(
+                "The screen hash is {}, expected {}",
+                actual, expected
+            ));
+        }
+    }
+
+    if let Some(ref png) = test.expected.png {
+        match compare_png(frame, png) {
+            Ok(0) => {}
+            Ok(different) => {
+                return Outcome::Fail(format!(
+                    "{} pixels differ from {} (screen hash {})",
+                    different,
+                    png.display(),
+                    screen_hash(frame)
+                ))
+            }
+            Err(e) => return Outcome::Error(e),
+        }
+    }
+
+    Outcome::Pass
+}
+
+/// SHA-1 of the red, green and blue bytes of every pixel of the screen, row
+/// by row
+pub fn screen_hash(frame: &Frame) -> String {
+    let mut bytes = Vec::with_capacity(frame.pixels.len() * 3);
+    for &pixel in frame.pixels.iter() {
+        bytes.extend_from_slice(&rgb(pixel));
+    }
+    Sha1::from(&bytes[..]).digest().to_string()
+}
+
+/// Counts the pixels that differ from a screenshot of any scale, where a
+/// pixel differs when any of its colors is off by half or more
+fn compare_png(frame: &Frame, path: &Path) -> Result<usize, String> {
+    let image = image::open(path)
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?
+        .to_rgb();
+    let (width, height) = image.dimensions();
+    let scale = width / frame.width as u32;
+    if scale == 0 || width != scale * frame.width as u32 || height != scale * frame.height as u32
+    {
+        return Err(format!(
+            "{} is {}x{}, which is not a multiple of {}x{}",
+            path.display(),
+            width,
+            height,
+            frame.width,
+            frame.height
+        ));
+    }
+
+    let mut different = 0;
+    for (index, &pixel) in frame.pixels.iter().enumerate() {
+        let (x, y) = ((index % frame.width) as u32, (index / frame.width) as u32);
+        let sample = image.get_pixel(x * scale + scale / 2, y * scale + scale / 2);
+        let differs = sample
+            .data
+            .iter()
+            .zip(rgb(pixel).iter())
+            .any(|(&a, &b)| (a as i32 - b as i32).abs() >= 0x80);
+        if differs {
+            different += 1;
+        }
+    }
+    Ok(different)
+}
+
+fn rgb(pixel: u32) -> [u8; 3] {
+    [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::quirks::Quirks;
+    use chip8::timing::Timing;
+    use manifest::{Expected, InputEvent};
+    use std::fs;
+    use std::path::PathBuf;
+    use testing::temp_path;
+
+    fn test_case(rom: &str, cycles: u64) -> TestCase {
+        TestCase {
+            name: String::from(rom),
+            rom: PathBuf::from(rom),
+            platform: None,
+            cycles,
+            clock_speed: 360,
+            timing: Timing::Simple,
+            quirks: Quirks::default(),
+            input: Vec::new(),
+            expected: Expected::default(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    fn blank_frame() -> Frame {
+        Frame {
+            width: 64,
+            height: 32,
+            pixels: vec![0xFF00_0000; 64 * 32],
+        }
+    }
+
+    #[test]
+    fn blank_screen_hash_is_stable() {
+        assert_eq!(
+            "c6fff00d41071ff3c363bbeaebd70338a55d1c94",
+            screen_hash(&blank_frame())
+        );
+    }
+
+    #[test]
+    fn outcomes_are_reported_per_test() {
+        let passing = test_case("roms/tictac.rom", 100);
+        let mut failing = test_case("roms/tictac.rom", 100);
+        failing.expected.hash = Some(String::from("0000"));
+        let mut with_input = test_case("roms/keypadtest.rom", 100);
+        with_input.input.push(InputEvent {
+            cycle: 10,
+            key: 0x5,
+            pressed: true,
+        });
+        let missing = test_case("roms/missing.rom", 100);
+
+        let results = run_all(vec![passing, failing, with_input, missing], 2);
+        assert_eq!(Outcome::Pass, results[0].outcome);
+        assert_eq!(100, results[0].cycles);
+        match results[1].outcome {
+            Outcome::Fail(ref message) => assert!(message.ends_with("expected 0000")),
+            ref other => panic!("Expected a failure, got {:?}", other),
+        }
+        assert_eq!("roms/keypadtest.rom", results[2].name);
+        match results[3].outcome {
+            Outcome::Error(ref message) => assert!(message.starts_with("Unable to load")),
+            ref other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn crashing_test_does_not_stop_the_others() {
+        // CALL 0x200, until the stack overflows
+        let path = temp_path("test-crash.ch8");
+        fs::write(&path, [0x22, 0x00]).unwrap();
+        let crashing = test_case(path.to_str().unwrap(), 100);
+        let passing = test_case("roms/tictac.rom", 100);
+
+        let results = run_all(vec![crashing, passing], 1);
+        match results[0].outcome {
+            Outcome::Error(ref message) => assert!(message.starts_with("The test crashed")),
+            ref other => panic!("Expected an error, got {:?}", other),
+        }
+        assert_eq!(Outcome::Pass, results[1].outcome);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn screenshots_are_compared_at_any_scale() {
+        let path = temp_path("test-screenshot.png");
+        let image = image::ImageBuffer::from_fn(64 * 3, 32 * 3, |x, y| {
+            if (x / 3, y / 3) == (5, 7) {
+                image::Rgb([0xFF, 0x00, 0x00])
+            } else {
+                image::Rgb([0x00, 0x00, 0x00])
+            }
+        });
+        image.save(&path).unwrap();
+
+        let mut frame = blank_frame();
+        frame.pixels[7 * 64 + 5] = 0xFFFF_0000;
+        assert_eq!(Ok(0), compare_png(&frame, &path));
+        frame.pixels[0] = 0xFFFF_FFFF;
+        frame.pixels[1] = 0xFF40_4040;
+        assert_eq!(Ok(1), compare_png(&frame, &path));
+        frame.pixels[7 * 64 + 5] = 0xFF00_FF00;
+        assert_eq!(Ok(2), compare_png(&frame, &path));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
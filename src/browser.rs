@@ -0,0 +1,290 @@
+use keyboard::Keyboard;
+use metadata::{RomDatabase, RomInfo};
+use piston_window::types::Color;
+use piston_window::{color, Button, Context, G2d, Key, Rectangle};
+use rom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use text::{self, ADVANCE, LINE_HEIGHT};
+
+/// Number of entries PageUp and PageDown move the selection by
+const PAGE_SIZE: usize = 10;
+/// Lines of text that should fit in a window of the default size
+const LINES_PER_SCREEN: f64 = 18.0;
+
+pub struct RomEntry {
+    pub path: PathBuf,
+    /// The file name, shown when the ROM is not in the database
+    pub file_name: String,
+    pub info: Option<RomInfo>,
+}
+
+impl RomEntry {
+    fn label(&self) -> &str {
+        match self.info {
+            Some(RomInfo {
+                title: Some(ref title),
+                ..
+            }) => title,
+            _ => &self.file_name,
+        }
+    }
+}
+
+/// The launcher shown when no ROM is given, listing the ROMs in a directory
+pub struct Browser {
+    directory: PathBuf,
+    entries: Vec<RomEntry>,
+    selected: usize,
+    /// Replaces the details of the selected ROM, e.g. when it failed to load
+    message: Option<String>,
+}
+
+impl Browser {
+    /// Lists the ROMs and zip archives in a directory, sorted by title
+    pub fn open(directory: &Path, database: &RomDatabase) -> io::Result<Browser> {
+        let mut entries = Vec::new();
+        for path in list_roms(directory)? {
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let info = rom::load(&path.to_string_lossy())
+                .ok()
+                .and_then(|rom| database.lookup(&rom.data).cloned());
+            entries.push(RomEntry {
+                path,
+                file_name,
+                info,
+            });
+        }
+        entries.sort_by_key(|entry| entry.label().to_lowercase());
+
+        Ok(Browser {
+            directory: directory.to_path_buf(),
+            entries,
+            selected: 0,
+            message: None,
+        })
+    }
+
+    pub fn selected(&self) -> Option<&RomEntry> {
+        self.entries.get(self.selected)
+    }
+
+    /// Moves the selection to the ROM at the given path if it is listed
+    pub fn select_path(&mut self, path: &Path) {
+        if let Some(index) = self.entries.iter().position(|entry| entry.path == path) {
+            self.selected = index;
+        }
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /// Handles a key press, returning the ROM to launch when one was chosen
+    pub fn handle_button(&mut self, button: Button) -> Option<PathBuf> {
+        let key = match button {
+            Button::Keyboard(key) => key,
+            _ => return None,
+        };
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let last = self.entries.len() - 1;
+        let previous = self.selected;
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(last),
+            Key::PageUp => self.selected = self.selected.saturating_sub(PAGE_SIZE),
+            Key::PageDown => self.selected = (self.selected + PAGE_SIZE).min(last),
+            Key::Home => self.selected = 0,
+            Key::End => self.selected = last,
+            Key::Return => return self.selected().map(|entry| entry.path.clone()),
+            _ => {
+                if let Some(letter) = Keyboard::character(key) {
+                    self.jump_to_letter(letter);
+                }
+            }
+        }
+
+        if self.selected != previous {
+            self.message = None;
+        }
+        None
+    }
+
+    /// Selects the next entry after the current one starting with the letter,
+    /// so pressing the same letter repeatedly cycles through them
+    fn jump_to_letter(&mut self, letter: char) {
+        let count = self.entries.len();
+        for offset in 1..=count {
+            let index = (self.selected + offset) % count;
+            let starts_with = self.entries[index]
+                .label()
+                .chars()
+                .next()
+                .map(|first| first.to_ascii_uppercase() == letter)
+                .unwrap_or(false);
+            if starts_with {
+                self.selected = index;
+                return;
+            }
+        }
+    }
+
+    pub fn draw(&self, context: &Context, graphics: &mut G2d) {
+        let view = context.get_view_size();
+        let size = (view[1] / (LINE_HEIGHT * LINES_PER_SCREEN)).floor().max(1.0);
+        let line_height = LINE_HEIGHT * size;
+        let margin = 2.0 * size;
+        let columns = ((view[0] - 2.0 * margin) / (ADVANCE * size)).max(1.0) as usize;
+        let lines = ((view[1] - 2.0 * margin) / line_height).max(1.0) as usize;
+        let line_y = |line: usize| margin + line as f64 * line_height;
+        let draw_line = |text: &str, line: usize, color: Color, graphics: &mut G2d| {
+            let text = truncate(text, columns);
+            text::draw_text(&text, [margin, line_y(line)], size, color, context, graphics);
+        };
+
+        let header = format!("CHIP-8 - {}", self.directory.display());
+        draw_line(&header, 0, color::WHITE, graphics);
+
+        // Two lines for the header and four for the details and help at the bottom
+        let rows = lines.saturating_sub(6).max(1);
+        let footer = lines.saturating_sub(3).max(rows + 2);
+
+        if self.entries.is_empty() {
+            draw_line("No ROMs found", 2, color::WHITE, graphics);
+        }
+
+        let first = (self.selected + 1).saturating_sub(rows);
+        for (row, entry) in self.entries.iter().enumerate().skip(first).take(rows) {
+            let line = 2 + row - first;
+            let color = if row == self.selected {
+                let highlight = [
+                    margin - size,
+                    line_y(line) - size,
+                    view[0] - 2.0 * margin + 2.0 * size,
+                    line_height - size,
+                ];
+                Rectangle::new(color::WHITE).draw(
+                    highlight,
+                    &context.draw_state,
+                    context.transform,
+                    graphics,
+                );
+                color::BLACK
+            } else {
+                color::WHITE
+            };
+            draw_line(entry.label(), line, color, graphics);
+        }
+
+        let details = match (self.message.as_ref(), self.selected()) {
+            (Some(message), _) => vec![message.clone()],
+            (None, Some(entry)) => entry_details(entry),
+            (None, None) => Vec::new(),
+        };
+        for (index, detail) in details.iter().take(2).enumerate() {
+            draw_line(detail, footer + index, color::WHITE, graphics);
+        }
+
+        draw_line("Enter: play   Esc: quit", footer + 2, color::WHITE, graphics);
+    }
+}
+
+/// Files in the directory that look like ROMs or zip archives
+fn list_roms(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let name = path.to_string_lossy().into_owned();
+        let is_zip = name.to_lowercase().ends_with(".zip");
+        if path.is_file() && (rom::has_rom_extension(&name) || is_zip) {
+            roms.push(path);
+        }
+    }
+    Ok(roms)
+}
+
+fn entry_details(entry: &RomEntry) -> Vec<String> {
+    let info = match entry.info {
+        Some(ref info) => info,
+        None => return vec![entry.file_name.clone(), String::from("Unknown ROM")],
+    };
+
+    let mut details = vec![match info.author {
+        Some(ref author) => format!("{} by {}", entry.file_name, author),
+        None => entry.file_name.clone(),
+    }];
+    let mut summary = Vec::new();
+    if let Some(ref platform) = info.platform {
+        summary.push(platform.clone());
+    }
+    if !info.keys.is_empty() {
+        summary.push(format!("{} keys", info.keys.len()));
+    }
+    details.push(summary.join(", "));
+    details
+}
+
+fn truncate(text: &str, columns: usize) -> String {
+    if text.chars().count() <= columns {
+        return String::from(text);
+    }
+    let mut truncated: String = text.chars().take(columns.saturating_sub(3)).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use testing::temp_path;
+
+    fn temp_directory(name: &str, files: &[&str]) -> PathBuf {
+        let directory = temp_path(&format!("browser-{}", name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for file in files {
+            let mut file = File::create(directory.join(file)).unwrap();
+            file.write_all(&[0x12, 0x00]).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn only_roms_are_listed() {
+        let directory = temp_directory("listing", &["pong.ch8", "notes.txt", "tetris.ZIP"]);
+        let mut names: Vec<_> = list_roms(&directory)
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(vec!["pong.ch8", "tetris.ZIP"], names);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn navigation_stays_in_the_list() {
+        let directory = temp_directory("navigation", &["a.ch8", "b.ch8", "c.ch8", "d.ch8"]);
+        let mut browser = Browser::open(&directory, &RomDatabase::embedded()).unwrap();
+
+        browser.handle_button(Button::Keyboard(Key::Up));
+        assert_eq!("a.ch8", browser.selected().unwrap().file_name);
+        browser.handle_button(Button::Keyboard(Key::PageDown));
+        assert_eq!("d.ch8", browser.selected().unwrap().file_name);
+        browser.handle_button(Button::Keyboard(Key::C));
+        assert_eq!("c.ch8", browser.selected().unwrap().file_name);
+
+        let launched = browser.handle_button(Button::Keyboard(Key::Return));
+        assert_eq!(Some(directory.join("c.ch8")), launched);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use display::Frame;
+use gif;
+use gif::SetParameter;
+use image;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Levels of red, green and blue in the recording palette, which together
+/// fill the 256 colors of a GIF palette
+const RED_LEVELS: u32 = 8;
+const GREEN_LEVELS: u32 = 8;
+const BLUE_LEVELS: u32 = 4;
+
+const FRAMES_PER_SECOND: u32 = 60;
+const CENTISECONDS_PER_SECOND: u32 = 100;
+
+// Most viewers treat delays below 2/100s as "as fast as possible" and
+// slow the frame down to 1/10s, so shorter frames are folded into the next one.
+const MIN_FRAME_DELAY: u32 = 2;
+
+pub fn save_screenshot(frame: &Frame, path: &Path, scale: u32) -> io::Result<()> {
+    let width = frame.width as u32;
+    let image = image::ImageBuffer::from_fn(width * scale, frame.height as u32 * scale, |x, y| {
+        let pixel = frame.pixels[((y / scale) * width + x / scale) as usize];
+        image::Rgb([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])
+    });
+    image.save(path)
+}
+
+/// Records the screen into an animated GIF.
+///
+/// `capture` is expected to be called once per emulated 60 Hz frame. Identical
+/// frames are merged and each GIF frame is given a delay that keeps the
+/// recording in sync with the emulated time, even though 1/60s is not a whole
+/// number of GIF time units. The GIF takes the size of the first frame, and
+/// later frames of another size, e.g. after a MEGA-CHIP switches modes, are
+/// stretched to it. Colors are rounded to the nearest of a fixed palette.
+pub struct Recorder {
+    /// The file until the first frame gives the size of the GIF
+    file: Option<BufWriter<File>>,
+    encoder: Option<gif::Encoder<BufWriter<File>>>,
+    /// Width and height of the GIF
+    size: (usize, usize),
+    path: PathBuf,
+    scale: u32,
+    pending: Option<Frame>,
+    frames: u32,
+    written_centiseconds: u32,
+}
+
+impl Recorder {
+    pub fn new(path: &Path, scale: u32) -> io::Result<Recorder> {
+        gif_size(1, scale)?;
+        let file = BufWriter::new(File::create(path)?);
+
+        Ok(Recorder {
+            file: Some(file),
+            encoder: None,
+            size: (0, 0),
+            path: path.to_path_buf(),
+            scale,
+            pending: None,
+            frames: 0,
+            written_centiseconds: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn capture(&mut self, frame: &Frame) -> io::Result<()> {
+        let changed = match self.pending {
+            Some(ref pending) => pending != frame,
+            None => true,
+        };
+
+        if changed {
+            if self.pending_delay() >= MIN_FRAME_DELAY {
+                self.write_pending()?;
+            }
+            self.pending = Some(frame.clone());
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_pending()
+    }
+
+    fn pending_delay(&self) -> u32 {
+        let elapsed = self.frames * CENTISECONDS_PER_SECOND / FRAMES_PER_SECOND;
+        elapsed - self.written_centiseconds
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        let delay = self.pending_delay();
+        let frame = match self.pending.take() {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+
+        if let Some(file) = self.file.take() {
+            let width = gif_size(frame.width, self.scale)?;
+            let height = gif_size(frame.height, self.scale)?;
+            let mut encoder = gif::Encoder::new(file, width, height, &palette())?;
+            encoder.set(gif::Repeat::Infinite)?;
+            self.encoder = Some(encoder);
+            self.size = (width as usize, height as usize);
+        }
+        let encoder = match self.encoder {
+            Some(ref mut encoder) => encoder,
+            None => return Ok(()),
+        };
+
+        let (width, height) = self.size;
+        let mut buffer = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = y * frame.height / height * frame.width;
+            for x in 0..width {
+                buffer.push(palette_index(frame.pixels[row + x * frame.width / width]));
+            }
+        }
+
+        let gif_frame = gif::Frame {
+            width: width as u16,
+            height: height as u16,
+            delay: delay as u16,
+            buffer: Cow::Owned(buffer),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&gif_frame)?;
+
+        self.written_centiseconds += delay;
+        Ok(())
+    }
+}
+
+/// Every combination of the red, green and blue levels, as RGB triples
+fn palette() -> Vec<u8> {
+    let mut palette = Vec::with_capacity(256 * 3);
+    for index in 0..(RED_LEVELS * GREEN_LEVELS * BLUE_LEVELS) {
+        let red = index / (GREEN_LEVELS * BLUE_LEVELS);
+        let green = index / BLUE_LEVELS % GREEN_LEVELS;
+        let blue = index % BLUE_LEVELS;
+        palette.push(level_value(red, RED_LEVELS));
+        palette.push(level_value(green, GREEN_LEVELS));
+        palette.push(level_value(blue, BLUE_LEVELS));
+    }
+    palette
+}
+
+fn level_value(level: u32, levels: u32) -> u8 {
+    (level * 0xFF / (levels - 1)) as u8
+}
+
+/// The palette entry nearest to an ARGB color
+fn palette_index(color: u32) -> u8 {
+    let level = |shift: u32, levels: u32| ((color >> shift & 0xFF) * (levels - 1) + 0x7F) / 0xFF;
+    let red = level(16, RED_LEVELS);
+    let green = level(8, GREEN_LEVELS);
+    let blue = level(0, BLUE_LEVELS);
+    ((red * GREEN_LEVELS + green) * BLUE_LEVELS + blue) as u8
+}
+
+/// Scales a side of the screen, failing when it does not fit the 16 bits
+/// GIFs store it in
+fn gif_size(pixels: usize, scale: u32) -> io::Result<u16> {
+    (pixels as u32)
+        .checked_mul(scale)
+        .and_then(|size| u16::try_from(size).ok())
+        .filter(|&size| size > 0)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unable to record at {} times the size of the screen", scale),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::temp_path;
+
+    use std::fs;
+
+    fn blank_frame(width: usize, height: usize) -> Frame {
+        Frame {
+            width,
+            height,
+            pixels: vec![0xFF00_0000; width * height],
+        }
+    }
+
+    #[test]
+    fn recorder_keeps_sixty_hertz_timing() {
+        let path = temp_path("recorder-timing.gif");
+        let mut recorder = Recorder::new(&path, 1).unwrap();
+        let mut frame = blank_frame(64, 32);
+        for i in 0..60 {
+            frame.pixels[0] = if (i / 3) % 2 == 0 { 0xFFFF_FFFF } else { 0xFF00_0000 };
+            recorder.capture(&frame).unwrap();
+        }
+        assert_eq!(60, recorder.frames);
+        // 20 changes, each lasting 3 frames (5/100s)
+        assert_eq!(95, recorder.written_centiseconds);
+        recorder.finish().unwrap();
+    }
+
+    #[test]
+    fn recorder_folds_frames_shorter_than_minimum_delay() {
+        let path = temp_path("recorder-flicker.gif");
+        let mut recorder = Recorder::new(&path, 1).unwrap();
+        let mut frame = blank_frame(64, 32);
+        for i in 0..3 {
+            frame.pixels[0] = if i % 2 == 0 { 0xFFFF_FFFF } else { 0xFF00_0000 };
+            recorder.capture(&frame).unwrap();
+        }
+        // The first frame is too short to be shown so the second one replaces it
+        assert_eq!(3, recorder.written_centiseconds);
+        recorder.finish().unwrap();
+    }
+
+    #[test]
+    fn recordings_must_fit_a_gif() {
+        let path = temp_path("recorder-size.gif");
+        assert!(Recorder::new(&path, 0).is_err());
+
+        let mut recorder = Recorder::new(&path, 1024).unwrap();
+        recorder.capture(&blank_frame(64, 32)).unwrap();
+        assert!(recorder.finish().is_err());
+
+        assert_eq!(65280, gif_size(256, 255).unwrap());
+        assert!(gif_size(256, 256).is_err());
+    }
+
+    #[test]
+    fn recording_takes_the_size_and_colors_of_the_frame() {
+        let path = temp_path("recorder-colors.gif");
+        let mut recorder = Recorder::new(&path, 1).unwrap();
+        let mut frame = blank_frame(256, 192);
+        frame.pixels[1] = 0xFFFF_0000;
+        frame.pixels[2] = 0xFF12_3456;
+        recorder.capture(&frame).unwrap();
+        recorder.finish().unwrap();
+
+        let data = fs::read(&path).unwrap();
+        let mut reader = gif::Decoder::new(&data[..]).read_info().unwrap();
+        assert_eq!((256, 192), (reader.width(), reader.height()));
+        let palette = reader.global_palette().unwrap().to_vec();
+        let color = |index: u8| &palette[index as usize * 3..index as usize * 3 + 3];
+        let frame = reader.read_next_frame().unwrap().unwrap();
+        assert_eq!([0x00, 0x00, 0x00], color(frame.buffer[0]));
+        assert_eq!([0xFF, 0x00, 0x00], color(frame.buffer[1]));
+        assert_eq!([0x00, 0x24, 0x55], color(frame.buffer[2]));
+    }
+
+    #[test]
+    fn screenshot_is_scaled() {
+        let path = temp_path("screenshot.png");
+        let mut frame = blank_frame(64, 32);
+        frame.pixels[64 + 2] = 0xFF12_3456;
+        save_screenshot(&frame, &path, 4).unwrap();
+
+        let image = image::open(&path).unwrap().to_rgb();
+        assert_eq!((256, 128), image.dimensions());
+        assert_eq!(image::Rgb([0x12, 0x34, 0x56]), *image.get_pixel(8, 4));
+        assert_eq!(image::Rgb([0x00, 0x00, 0x00]), *image.get_pixel(7, 4));
+    }
+}
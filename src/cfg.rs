@@ -0,0 +1,480 @@
+use instruction::{Address, Instruction};
+use platform::Platform;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const INSTRUCTION_SIZE: Address = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CfgFormat {
+    /// A Graphviz digraph
+    Dot,
+    /// A single JSON object
+    Json,
+}
+
+impl CfgFormat {
+    pub fn from_name(name: &str) -> Option<CfgFormat> {
+        match name {
+            "dot" => Some(CfgFormat::Dot),
+            "json" => Some(CfgFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction
+    Fallthrough,
+    Jump,
+    Call,
+    /// The instruction after a call, where the subroutine returns to
+    CallReturn,
+    /// A skip instruction skipping the next instruction
+    Skip,
+    Return,
+    /// BNNN, whose target depends on V0
+    Computed,
+}
+
+impl EdgeKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            EdgeKind::Fallthrough => "fallthrough",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Call => "call",
+            EdgeKind::CallReturn => "call-return",
+            EdgeKind::Skip => "skip",
+            EdgeKind::Return => "return",
+            EdgeKind::Computed => "computed",
+        }
+    }
+}
+
+/// An edge between the blocks starting at two addresses. Edges whose target
+/// is not known statically have no `to`: computed jumps, and returns from
+/// code that is never called.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edge {
+    pub from: Address,
+    pub to: Option<Address>,
+    pub kind: EdgeKind,
+}
+
+/// Instructions that are always executed one after another
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub start: Address,
+    /// Address and opcode of every instruction in the block
+    pub instructions: Vec<(Address, u16)>,
+}
+
+impl Block {
+    /// The address after the last instruction
+    pub fn end(&self) -> Address {
+        self.start + self.instructions.len() as Address * INSTRUCTION_SIZE
+    }
+
+    fn last(&self, platform: Platform) -> (Address, Instruction) {
+        let (address, opcode) = self.instructions[self.instructions.len() - 1];
+        (address, Instruction::parse_for(opcode, platform))
+    }
+}
+
+/// The control-flow graph of the code reachable from the start of a ROM,
+/// found without running it
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<Address, Block>,
+    pub edges: Vec<Edge>,
+    /// Entry points of the subroutines called from anywhere in the ROM
+    pub subroutines: BTreeSet<Address>,
+    platform: Platform,
+}
+
+impl ControlFlowGraph {
+    /// Follows the code of a ROM loaded and decoded as on `platform`
+    pub fn build(rom: &[u8], platform: Platform) -> ControlFlowGraph {
+        let program_start = platform.program_start();
+        let opcode_at = |address: Address| {
+            let offset = (address as usize).checked_sub(program_start)?;
+            if offset + 1 < rom.len() {
+                Some((rom[offset] as u16) << 8 | rom[offset + 1] as u16)
+            } else {
+                None
+            }
+        };
+
+        // Find every reachable instruction and the addresses that start blocks
+        let mut opcodes = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut subroutines = BTreeSet::new();
+        let mut pending = vec![program_start as Address];
+        leaders.insert(program_start as Address);
+        while let Some(address) = pending.pop() {
+            if opcodes.contains_key(&address) {
+                continue;
+            }
+            let opcode = match opcode_at(address) {
+                Some(opcode) => opcode,
+                None => continue,
+            };
+            opcodes.insert(address, opcode);
+
+            let instruction = Instruction::parse_for(opcode, platform);
+            let targets = successors(address, &instruction);
+            if let Instruction::Call(target) = instruction {
+                subroutines.insert(target);
+            }
+            for &(target, _) in targets.iter() {
+                if instruction.is_branch() {
+                    leaders.insert(target);
+                }
+                pending.push(target);
+            }
+        }
+
+        // Group consecutive instructions into blocks, ending them at branches
+        // and before the start of another block
+        let mut blocks = BTreeMap::new();
+        let mut current: Option<Block> = None;
+        for (&address, &opcode) in opcodes.iter() {
+            let continues = match current {
+                Some(ref block) => {
+                    let (_, last) = block.last(platform);
+                    block.end() == address && !leaders.contains(&address) && !last.is_branch()
+                }
+                None => false,
+            };
+            if !continues {
+                if let Some(block) = current.take() {
+                    blocks.insert(block.start, block);
+                }
+                current = Some(Block {
+                    start: address,
+                    instructions: Vec::new(),
+                });
+            }
+            if let Some(ref mut block) = current {
+                block.instructions.push((address, opcode));
+            }
+        }
+        if let Some(block) = current {
+            blocks.insert(block.start, block);
+        }
+
+        let mut edges = Vec::new();
+        for block in blocks.values() {
+            let (address, last) = block.last(platform);
+            let targets = successors(address, &last);
+            for &(target, kind) in targets.iter() {
+                if blocks.contains_key(&target) {
+                    edges.push(Edge {
+                        from: block.start,
+                        to: Some(target),
+                        kind,
+                    });
+                }
+            }
+            if let Instruction::JumpOffset(_) = last {
+                edges.push(Edge {
+                    from: block.start,
+                    to: None,
+                    kind: EdgeKind::Computed,
+                });
+            }
+        }
+
+        let mut graph = ControlFlowGraph {
+            blocks,
+            edges,
+            subroutines,
+            platform,
+        };
+        graph.add_return_edges();
+        graph
+    }
+
+    /// Connects every RET to the instructions after the calls of the
+    /// subroutines it belongs to, or leaves it unresolved if it belongs to none
+    fn add_return_edges(&mut self) {
+        let mut returns_to: BTreeMap<Address, BTreeSet<Address>> = BTreeMap::new();
+        for &entry in self.subroutines.iter() {
+            let sites = self.return_sites(entry);
+            for block in self.reachable_within(entry) {
+                if self.blocks[&block].last(self.platform).1 == Instruction::Return {
+                    returns_to
+                        .entry(block)
+                        .or_default()
+                        .extend(sites.iter().cloned());
+                }
+            }
+        }
+
+        for block in self.blocks.values() {
+            if block.last(self.platform).1 != Instruction::Return {
+                continue;
+            }
+            match returns_to.get(&block.start) {
+                Some(sites) if !sites.is_empty() => {
+                    for &site in sites.iter() {
+                        self.edges.push(Edge {
+                            from: block.start,
+                            to: Some(site),
+                            kind: EdgeKind::Return,
+                        });
+                    }
+                }
+                _ => self.edges.push(Edge {
+                    from: block.start,
+                    to: None,
+                    kind: EdgeKind::Return,
+                }),
+            }
+        }
+    }
+
+    /// The blocks following each call to `entry`
+    fn return_sites(&self, entry: Address) -> BTreeSet<Address> {
+        self.blocks
+            .values()
+            .filter(|block| block.last(self.platform).1 == Instruction::Call(entry))
+            .map(|block| block.end())
+            .filter(|site| self.blocks.contains_key(site))
+            .collect()
+    }
+
+    /// The blocks of a subroutine: those reachable from its entry without
+    /// following calls into other subroutines
+    fn reachable_within(&self, entry: Address) -> BTreeSet<Address> {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(block) = pending.pop() {
+            if !self.blocks.contains_key(&block) || !seen.insert(block) {
+                continue;
+            }
+            for edge in self.edges.iter().filter(|edge| edge.from == block) {
+                match (edge.kind, edge.to) {
+                    (EdgeKind::Call, _) | (EdgeKind::Return, _) => {}
+                    (_, Some(to)) => pending.push(to),
+                    (_, None) => {}
+                }
+            }
+        }
+        seen
+    }
+
+    pub fn write_file(&self, path: &Path, format: CfgFormat) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            CfgFormat::Dot => self.write_dot(&mut out)?,
+            CfgFormat::Json => self.write_json(&mut out)?,
+        }
+        out.flush()
+    }
+
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "digraph cfg {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for &(address, opcode) in block.instructions.iter() {
+                label.push_str(&format!(
+                    "{:03X}: {}\\l",
+                    address,
+                    Instruction::parse_for(opcode, self.platform)
+                ));
+            }
+            // Subroutine entries are drawn with a double border
+            let style = if self.subroutines.contains(&block.start) {
+                ", peripheries=2"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "    b{:03X} [label=\"{}\"{}];",
+                block.start, label, style
+            )?;
+        }
+        for edge in self.edges.iter() {
+            match edge.to {
+                Some(to) => writeln!(
+                    out,
+                    "    b{:03X} -> b{:03X} [label=\"{}\"];",
+                    edge.from,
+                    to,
+                    edge.kind.name()
+                )?,
+                None => {
+                    writeln!(
+                        out,
+                        "    u{:03X} [label=\"?\", shape=plaintext];",
+                        edge.from
+                    )?;
+                    writeln!(
+                        out,
+                        "    b{:03X} -> u{:03X} [label=\"{}\", style=dashed];",
+                        edge.from,
+                        edge.from,
+                        edge.kind.name()
+                    )?;
+                }
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    pub fn write_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let subroutines: Vec<_> = self.subroutines.iter().map(|s| s.to_string()).collect();
+        let blocks: Vec<_> = self
+            .blocks
+            .values()
+            .map(|block| {
+                let instructions: Vec<_> = block
+                    .instructions
+                    .iter()
+                    .map(|&(address, opcode)| {
+                        format!(
+                            "{{\"address\":{},\"opcode\":\"{:04X}\",\"instruction\":\"{}\"}}",
+                            address,
+                            opcode,
+                            Instruction::parse_for(opcode, self.platform)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"start\":{},\"end\":{},\"instructions\":[{}]}}",
+                    block.start,
+                    block.end(),
+                    instructions.join(",")
+                )
+            })
+            .collect();
+        let edges: Vec<_> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let to = edge
+                    .to
+                    .map_or_else(|| String::from("null"), |to| to.to_string());
+                format!(
+                    "{{\"from\":{},\"to\":{},\"kind\":\"{}\"}}",
+                    edge.from,
+                    to,
+                    edge.kind.name()
+                )
+            })
+            .collect();
+        // As in traces, the instruction text needs no escaping
+        writeln!(
+            out,
+            "{{\"entry\":{},\"subroutines\":[{}],\"blocks\":[{}],\"edges\":[{}]}}",
+            self.platform.program_start(),
+            subroutines.join(","),
+            blocks.join(","),
+            edges.join(",")
+        )
+    }
+}
+
+/// The statically known addresses an instruction can continue at
+fn successors(address: Address, instruction: &Instruction) -> Vec<(Address, EdgeKind)> {
+    let next = address + INSTRUCTION_SIZE;
+    match *instruction {
+        Instruction::Jump(target) => vec![(target, EdgeKind::Jump)],
+        Instruction::Call(target) => vec![(target, EdgeKind::Call), (next, EdgeKind::CallReturn)],
+        Instruction::SkipIfEqual(_, _)
+        | Instruction::SkipIfNotEqual(_, _)
+        | Instruction::SkipIfRegEqual(_, _)
+        | Instruction::SkipIfRegNotEqual(_, _)
+        | Instruction::SkipIfKey(_)
+        | Instruction::SkipIfNotKey(_)
+        | Instruction::SkipIfKey2(_)
+        | Instruction::SkipIfNotKey2(_) => vec![
+            (next, EdgeKind::Fallthrough),
+            (next + INSTRUCTION_SIZE, EdgeKind::Skip),
+        ],
+        Instruction::Return | Instruction::JumpOffset(_) | Instruction::InvalidOperation => {
+            Vec::new()
+        }
+        _ => vec![(next, EdgeKind::Fallthrough)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from: Address, to: Option<Address>, kind: EdgeKind) -> Edge {
+        Edge { from, to, kind }
+    }
+
+    #[test]
+    fn blocks_are_split_at_branches_and_targets() {
+        let rom = vec![
+            0x60, 0x00, // 200: LD V0, 0
+            0x30, 0x05, // 202: SE V0, 5
+            0x22, 0x0A, // 204: CALL 0x20A
+            0x70, 0x01, // 206: ADD V0, 1
+            0x12, 0x02, // 208: JP 0x202
+            0x61, 0x01, // 20A: LD V1, 1
+            0x00, 0xEE, // 20C: RET
+        ];
+        let graph = ControlFlowGraph::build(&rom, Platform::Chip8);
+
+        let starts: Vec<_> = graph.blocks.keys().cloned().collect();
+        assert_eq!(vec![0x200, 0x202, 0x204, 0x206, 0x20A], starts);
+        assert_eq!(2, graph.blocks[&0x206].instructions.len());
+        assert!(graph.subroutines.contains(&0x20A));
+
+        let expected = vec![
+            edge(0x200, Some(0x202), EdgeKind::Fallthrough),
+            edge(0x202, Some(0x204), EdgeKind::Fallthrough),
+            edge(0x202, Some(0x206), EdgeKind::Skip),
+            edge(0x204, Some(0x20A), EdgeKind::Call),
+            edge(0x204, Some(0x206), EdgeKind::CallReturn),
+            edge(0x206, Some(0x202), EdgeKind::Jump),
+            edge(0x20A, Some(0x206), EdgeKind::Return),
+        ];
+        assert_eq!(expected, graph.edges);
+    }
+
+    #[test]
+    fn computed_jumps_are_unresolved() {
+        // LD V0, 2; JP V0, 0x300; RET
+        let rom = vec![0x60, 0x02, 0xB3, 0x00, 0x00, 0xEE];
+        let graph = ControlFlowGraph::build(&rom, Platform::Chip8);
+        assert_eq!(1, graph.blocks.len());
+        assert_eq!(vec![edge(0x200, None, EdgeKind::Computed)], graph.edges);
+
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("b200 [label=\"200: LD V0, 2\\l202: JP V0, 0x300\\l\"];"));
+        assert!(dot.contains("b200 -> u200 [label=\"computed\", style=dashed];"));
+
+        let mut json = Vec::new();
+        graph.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"edges\":[{\"from\":512,\"to\":null,\"kind\":\"computed\"}]"));
+    }
+
+    #[test]
+    fn chip8x_roms_start_later_and_color_with_bnnn() {
+        // LD V0, 2; COL V0, V1, 1; JP 0x300
+        let rom = vec![0x60, 0x02, 0xB0, 0x11, 0x13, 0x00];
+        let graph = ControlFlowGraph::build(&rom, Platform::Chip8X);
+        let starts: Vec<_> = graph.blocks.keys().cloned().collect();
+        assert_eq!(vec![0x300], starts);
+        assert_eq!(3, graph.blocks[&0x300].instructions.len());
+        assert_eq!(vec![edge(0x300, Some(0x300), EdgeKind::Jump)], graph.edges);
+
+        let mut json = Vec::new();
+        graph.write_json(&mut json).unwrap();
+        assert!(String::from_utf8(json).unwrap().starts_with("{\"entry\":768,"));
+    }
+}
@@ -0,0 +1,182 @@
+use chip8::cheats::{Cheat, CheatSearch, Comparison};
+use chip8::cpu::Cpu;
+use keyboard::Keyboard;
+use piston_window::{Button, Key};
+
+/// Candidates listed in the panel
+const ROWS: usize = 14;
+
+/// Searches memory step by step for where the game keeps a value, and
+/// freezes the address once it is found
+pub struct CheatFinder {
+    search: Option<CheatSearch>,
+    selected: usize,
+    /// The decimal value being typed to search for
+    value: String,
+    message: String,
+}
+
+impl CheatFinder {
+    pub fn new() -> CheatFinder {
+        CheatFinder {
+            search: None,
+            selected: 0,
+            value: String::new(),
+            message: String::new(),
+        }
+    }
+
+    /// Handles a key press, returning the cheat when one was added to the CPU
+    pub fn handle_button(&mut self, button: Button, cpu: &mut Cpu) -> Option<Cheat> {
+        let key = match button {
+            Button::Keyboard(key) => key,
+            _ => return None,
+        };
+
+        match key {
+            Key::N => {
+                self.search = Some(CheatSearch::new(cpu.memory()));
+                self.selected = 0;
+                self.message = String::from("NEW SEARCH");
+            }
+            Key::C => self.filter(cpu, Comparison::Changed),
+            Key::U => self.filter(cpu, Comparison::Unchanged),
+            // = is the + key without shift
+            Key::Equals | Key::NumPadPlus => self.filter(cpu, Comparison::Increased),
+            Key::Minus | Key::NumPadMinus => self.filter(cpu, Comparison::Decreased),
+            Key::Return => match self.value.parse::<u8>() {
+                Ok(value) => self.filter(cpu, Comparison::Equal(value)),
+                Err(_) => self.message = String::from("TYPE A VALUE UP TO 255"),
+            },
+            Key::Backspace => {
+                self.value.pop();
+            }
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.count().saturating_sub(1)),
+            Key::K => return self.freeze_selected(cpu),
+            _ => {
+                if let Some(digit) = Keyboard::character(key).filter(char::is_ascii_digit) {
+                    if self.value.len() < 3 {
+                        self.value.push(digit);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// The text of the panel, one string per line
+    pub fn lines(&self, memory: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        let search = match self.search {
+            Some(ref search) => search,
+            None => {
+                lines.push(String::from("CHEATS"));
+                lines.push(String::new());
+                lines.push(String::from("PRESS N TO SEARCH MEMORY"));
+                return lines;
+            }
+        };
+
+        lines.push(format!("CHEATS  {} LEFT", search.count()));
+        lines.push(String::new());
+        // Addresses are only listed once the search has narrowed down
+        let first = (self.selected + 1).saturating_sub(ROWS);
+        for (row, address) in search.candidates().enumerate().skip(first).take(ROWS) {
+            let marker = if row == self.selected { '>' } else { ' ' };
+            lines.push(format!(
+                "{}{:04X} {:3} WAS {:3}",
+                marker,
+                address,
+                memory.get(address).cloned().unwrap_or(0),
+                search.previous(address).unwrap_or(0)
+            ));
+        }
+        while lines.len() < ROWS + 2 {
+            lines.push(String::new());
+        }
+
+        lines.push(String::new());
+        if self.value.is_empty() {
+            lines.push(self.message.clone());
+        } else {
+            lines.push(format!("VALUE {}_", self.value));
+        }
+        lines.push(String::from("N NEW  C CHANGED  U SAME"));
+        lines.push(String::from("= MORE  - LESS  ENTER VALUE"));
+        lines.push(String::from("K FREEZE"));
+        lines
+    }
+
+    fn count(&self) -> usize {
+        self.search.as_ref().map_or(0, |search| search.count())
+    }
+
+    fn filter(&mut self, cpu: &Cpu, comparison: Comparison) {
+        self.value.clear();
+        match self.search {
+            Some(ref mut search) => {
+                search.filter(cpu.memory(), comparison);
+                self.message = format!("{} LEFT", search.count());
+            }
+            None => {
+                self.message = String::from("PRESS N FIRST");
+                return;
+            }
+        }
+        self.selected = self.selected.min(self.count().saturating_sub(1));
+    }
+
+    /// Freezes the selected address at its current value
+    fn freeze_selected(&mut self, cpu: &mut Cpu) -> Option<Cheat> {
+        let address = self.search.as_ref()?.candidates().nth(self.selected)?;
+        let cheat = Cheat::FreezeMemory(address, cpu.memory()[address]);
+        cpu.add_cheat(cheat.clone());
+        self.message = format!("FROZE {}", cheat);
+        Some(cheat)
+    }
+}
+
+impl Default for CheatFinder {
+    fn default() -> CheatFinder {
+        CheatFinder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use overlay::PANEL_COLUMNS;
+
+    fn press(finder: &mut CheatFinder, cpu: &mut Cpu, keys: &[Key]) -> Option<Cheat> {
+        keys.iter()
+            .filter_map(|&key| finder.handle_button(Button::Keyboard(key), cpu))
+            .last()
+    }
+
+    #[test]
+    fn found_values_can_be_frozen() {
+        // LD V0, 3; LD I, 0x300; LD [I], V0; ADD V0, -1; LD [I], V0
+        let rom = vec![0x60, 0x03, 0xA3, 0x00, 0xF0, 0x55, 0x70, 0xFF, 0xF0, 0x55];
+        let mut cpu = Cpu::new(rom, 360, false);
+        let mut finder = CheatFinder::new();
+        for _ in 0..3 {
+            cpu.cycle();
+        }
+        press(&mut finder, &mut cpu, &[Key::N, Key::D3, Key::Return]);
+        cpu.cycle();
+        cpu.cycle();
+        assert_eq!(None, press(&mut finder, &mut cpu, &[Key::Minus]));
+        let search = finder.search.as_ref().unwrap();
+        assert_eq!(vec![0x300], search.candidates().collect::<Vec<_>>());
+
+        let lines = finder.lines(cpu.memory());
+        // The value at the last step is the one just searched for
+        assert_eq!(">0300   2 WAS   2", lines[2]);
+        assert!(lines.iter().all(|line| line.len() <= PANEL_COLUMNS));
+
+        let cheat = press(&mut finder, &mut cpu, &[Key::K]);
+        assert_eq!(Some(Cheat::FreezeMemory(0x300, 2)), cheat);
+        assert_eq!(1, cpu.take_cheats().unwrap().cheats().len());
+    }
+}
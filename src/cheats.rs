@@ -0,0 +1,371 @@
+use cpu::Cpu;
+use instruction::Register;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// A change to the game that makes it easier, see `parse_cheats` for how
+/// they are written
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cheat {
+    /// Keeps a byte of memory at a value by writing it every frame
+    FreezeMemory(usize, u8),
+    FreezeRegister(Register, u8),
+    /// Writes bytes to memory once, when the ROM is loaded
+    Patch(usize, Vec<u8>),
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cheat::FreezeMemory(address, value) => write!(f, "{:03X} = {:02X}", address, value),
+            Cheat::FreezeRegister(register, value) => {
+                write!(f, "V{:X} = {:02X}", register, value)
+            }
+            Cheat::Patch(address, ref bytes) => {
+                let bytes: Vec<_> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                write!(f, "{:03X}: {}", address, bytes.join(" "))
+            }
+        }
+    }
+}
+
+impl Cheat {
+    /// Writes the bytes of a patch, or the value of a freeze
+    fn apply(&self, cpu: &mut Cpu) {
+        match *self {
+            Cheat::FreezeMemory(address, value) => {
+                cpu.poke(address, &[value]);
+            }
+            Cheat::FreezeRegister(register, value) => cpu.poke_register(register, value),
+            Cheat::Patch(address, ref bytes) => {
+                cpu.poke(address, bytes);
+            }
+        }
+    }
+
+    fn is_patch(&self) -> bool {
+        matches!(*self, Cheat::Patch(_, _))
+    }
+}
+
+/// The cheats active for a ROM
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new(cheats: Vec<Cheat>) -> Cheats {
+        Cheats { cheats }
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn add(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+    }
+
+    /// Writes the patches, for when the ROM was loaded
+    pub fn patch(&self, cpu: &mut Cpu) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.is_patch()) {
+            cheat.apply(cpu);
+        }
+    }
+
+    /// Writes the frozen values, for every frame
+    pub fn freeze(&self, cpu: &mut Cpu) {
+        for cheat in self.cheats.iter().filter(|cheat| !cheat.is_patch()) {
+            cheat.apply(cpu);
+        }
+    }
+}
+
+/// Reads the cheats for the ROM with the given hash from a cheat file, see
+/// `parse_cheats`
+pub fn load_cheats(path: &Path, hash: &str) -> Result<Cheats, String> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    parse_cheats(&source, hash).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Parses a cheat file and returns the cheats for the ROM with the given
+/// hash. Each ROM's cheats follow a line with its SHA-1 in brackets, one per
+/// line, all numbers in hex:
+///
+/// ```text
+/// [0df2789f7ec5ff5e4dd35d58b5ac2df27e3f3dab]
+/// # Infinite lives
+/// V3 = 05
+/// 2F4 = 03
+/// # Skip the title screen
+/// 200: 12 40
+/// ```
+///
+/// `VX = NN` and `NNN = NN` freeze a register or a byte of memory, and
+/// `NNN: NN NN ...` patches bytes into memory when the ROM is loaded.
+/// Everything after a `#` is a comment.
+pub fn parse_cheats(source: &str, hash: &str) -> Result<Cheats, String> {
+    let hash = hash.to_lowercase();
+    let mut section = None;
+    let mut cheats = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = Some(line[1..line.len() - 1].trim().to_lowercase());
+            continue;
+        }
+
+        let cheat = parse_cheat(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        match section {
+            Some(ref section) if *section == hash => cheats.push(cheat),
+            Some(_) => {}
+            None => {
+                return Err(format!(
+                    "line {}: Cheats must follow the [hash] of their ROM",
+                    number + 1
+                ))
+            }
+        }
+    }
+    Ok(Cheats::new(cheats))
+}
+
+fn parse_cheat(line: &str) -> Result<Cheat, String> {
+    if let Some(separator) = line.find('=') {
+        let (target, value) = (line[..separator].trim(), line[separator + 1..].trim());
+        let value = parse_byte(value)?;
+        let register = target
+            .strip_prefix('V')
+            .or_else(|| target.strip_prefix('v'));
+        return match register {
+            Some(register) => match u8::from_str_radix(register, 16) {
+                Ok(register) if register < 16 => Ok(Cheat::FreezeRegister(register, value)),
+                _ => Err(format!("Invalid register '{}'", target)),
+            },
+            None => Ok(Cheat::FreezeMemory(parse_address(target)?, value)),
+        };
+    }
+
+    if let Some(separator) = line.find(':') {
+        let address = parse_address(line[..separator].trim())?;
+        let bytes = line[separator + 1..]
+            .split_whitespace()
+            .map(parse_byte)
+            .collect::<Result<Vec<_>, _>>()?;
+        if bytes.is_empty() {
+            return Err(String::from("A patch needs at least one byte"));
+        }
+        return Ok(Cheat::Patch(address, bytes));
+    }
+
+    Err(format!(
+        "Expected 'address = value' or 'address: bytes', got '{}'",
+        line
+    ))
+}
+
+fn parse_address(text: &str) -> Result<usize, String> {
+    usize::from_str_radix(text, 16).map_err(|_| format!("Invalid address '{}'", text))
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    u8::from_str_radix(text, 16).map_err(|_| format!("Invalid byte '{}'", text))
+}
+
+/// How a value must have changed to stay in a search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    /// Is now the given value
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn matches(&self, previous: u8, current: u8) -> bool {
+        match *self {
+            Comparison::Equal(value) => current == value,
+            Comparison::Changed => current != previous,
+            Comparison::Unchanged => current == previous,
+            Comparison::Increased => current > previous,
+            Comparison::Decreased => current < previous,
+        }
+    }
+}
+
+/// Narrows down where a game keeps a value like the lives or the score by
+/// comparing memory with how it was at the last step of the search, e.g.
+/// keeping the addresses that decreased after losing a life
+pub struct CheatSearch {
+    previous: Vec<u8>,
+    /// One bit per address that matched every step, which keeps the search
+    /// small on the 16 MB of the MEGA-CHIP
+    candidates: Vec<u64>,
+    count: usize,
+    /// Whether a step was taken, before which every address matches
+    narrowed: bool,
+}
+
+impl CheatSearch {
+    /// Starts a search with every address as a candidate
+    pub fn new(memory: &[u8]) -> CheatSearch {
+        let mut candidates = vec![!0; memory.len() / 64];
+        let rest = memory.len() % 64;
+        if rest > 0 {
+            candidates.push((1 << rest) - 1);
+        }
+        CheatSearch {
+            previous: memory.to_vec(),
+            candidates,
+            count: memory.len(),
+            narrowed: false,
+        }
+    }
+
+    /// Keeps the candidates whose value compares to their value at the last
+    /// step as asked, and remembers memory for the next step
+    pub fn filter(&mut self, memory: &[u8], comparison: Comparison) {
+        let previous = &self.previous;
+        let mut count = 0;
+        for (word_index, word) in self.candidates.iter_mut().enumerate() {
+            let mut bits = *word;
+            while bits != 0 {
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                let address = word_index * 64 + bit;
+                if address < memory.len() && comparison.matches(previous[address], memory[address])
+                {
+                    count += 1;
+                } else {
+                    *word &= !(1 << bit);
+                }
+            }
+        }
+        self.count = count;
+        self.narrowed = true;
+        self.previous = memory.to_vec();
+    }
+
+    /// Number of addresses that still match
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The addresses that still match in order, none before the first step
+    pub fn candidates<'a>(&'a self) -> impl Iterator<Item = usize> + 'a {
+        let words = if self.narrowed {
+            &self.candidates[..]
+        } else {
+            &[]
+        };
+        words.iter().enumerate().flat_map(|(word_index, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| word_index * 64 + bit)
+        })
+    }
+
+    /// The value of an address at the last step
+    pub fn previous(&self, address: usize) -> Option<u8> {
+        self.previous.get(address).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0df2789f7ec5ff5e4dd35d58b5ac2df27e3f3dab";
+
+    #[test]
+    fn cheats_are_read_for_their_rom() {
+        let source = format!(
+            "# Cheats\n[{}]\nV3 = 05  # lives\n2F4=3\n200: 12 40\n\n[1234]\nV0 = 01\n",
+            HASH.to_uppercase()
+        );
+        let cheats = parse_cheats(&source, HASH).unwrap();
+        assert_eq!(
+            &[
+                Cheat::FreezeRegister(0x3, 0x05),
+                Cheat::FreezeMemory(0x2F4, 0x03),
+                Cheat::Patch(0x200, vec![0x12, 0x40]),
+            ],
+            cheats.cheats()
+        );
+        assert_eq!("200: 12 40", cheats.cheats()[2].to_string());
+        assert_eq!(Ok(Cheats::default()), parse_cheats(&source, "5678"));
+
+        assert!(parse_cheats("V0 = 01", HASH).is_err());
+        let error = parse_cheats(&format!("[{}]\nVG = 01", HASH), HASH).unwrap_err();
+        assert_eq!("line 2: Invalid register 'VG'", error);
+        assert!(parse_cheats(&format!("[{}]\n200 = 100", HASH), HASH).is_err());
+        assert!(parse_cheats(&format!("[{}]\n200:", HASH), HASH).is_err());
+    }
+
+    #[test]
+    fn cheats_patch_on_load_and_freeze_every_frame() {
+        // LD V3, 0; ADD V3, 1; JP 0x202
+        let rom = vec![0x63, 0x00, 0x73, 0x01, 0x12, 0x02];
+        let mut cpu = Cpu::new(rom.clone(), 360, false);
+        cpu.set_cheats(Cheats::new(vec![Cheat::Patch(0x201, vec![0x09])]));
+        cpu.add_cheat(Cheat::FreezeRegister(0x3, 0x05));
+        cpu.cycle();
+        assert_eq!(9, cpu.registers()[0x3]);
+
+        // Six instructions make a frame at 360 Hz
+        for _ in 0..5 {
+            cpu.cycle();
+        }
+        assert_eq!(5, cpu.registers()[0x3]);
+
+        cpu.reset(rom);
+        assert_eq!(0x09, cpu.memory()[0x201]);
+        assert_eq!(2, cpu.take_cheats().unwrap().cheats().len());
+    }
+
+    #[test]
+    fn searches_narrow_down_to_the_changing_value() {
+        let mut memory = vec![3, 3, 7, 0];
+        let mut search = CheatSearch::new(&memory);
+        assert_eq!(4, search.count());
+        assert_eq!(0, search.candidates().count());
+
+        search.filter(&memory, Comparison::Equal(3));
+        assert_eq!(vec![0, 1], search.candidates().collect::<Vec<_>>());
+
+        memory[0] = 2;
+        memory[3] = 9;
+        search.filter(&memory, Comparison::Decreased);
+        assert_eq!(vec![0], search.candidates().collect::<Vec<_>>());
+        assert_eq!(Some(2), search.previous(0));
+
+        search.filter(&memory, Comparison::Unchanged);
+        assert_eq!(1, search.count());
+        search.filter(&memory, Comparison::Changed);
+        assert_eq!(0, search.count());
+    }
+
+    #[test]
+    fn searches_reach_addresses_past_the_first_word() {
+        let mut memory = vec![0; 130];
+        memory[64] = 5;
+        memory[129] = 5;
+        let mut search = CheatSearch::new(&memory);
+        assert_eq!(130, search.count());
+        search.filter(&memory, Comparison::Equal(5));
+        assert_eq!(vec![64, 129], search.candidates().collect::<Vec<_>>());
+        assert_eq!(2, search.count());
+    }
+}
@@ -0,0 +1,200 @@
+use instruction::{Address, Instruction};
+use platform::Platform;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const EXECUTED: u8 = 0b0001;
+const READ: u8 = 0b0010;
+const WRITTEN: u8 = 0b0100;
+/// The first byte of an executed instruction
+const INSTRUCTION_START: u8 = 0b1000;
+
+/// Records how every byte of memory was used: executed as part of an
+/// instruction, read as data by DXYN or FX65, or written by FX33 or FX55
+pub struct Coverage {
+    flags: Vec<u8>,
+    rom_size: usize,
+    platform: Platform,
+}
+
+/// Number of ROM bytes used in each way
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CoverageSummary {
+    pub rom_size: usize,
+    pub executed: usize,
+    pub read: usize,
+    pub written: usize,
+    /// Bytes used in any of the ways above
+    pub covered: usize,
+}
+
+impl Coverage {
+    /// Coverage of a ROM loaded and decoded as on `platform`
+    pub fn new(rom_size: usize, platform: Platform) -> Coverage {
+        Coverage {
+            flags: vec![0; platform.memory_size()],
+            rom_size: rom_size.min(platform.memory_size() - platform.program_start()),
+            platform,
+        }
+    }
+
+    pub fn executed(&mut self, address: Address) {
+        let address = address as usize;
+        self.mark(address, EXECUTED | INSTRUCTION_START);
+        self.mark(address + 1, EXECUTED);
+    }
+
+    pub fn read(&mut self, address: usize) {
+        self.mark(address, READ);
+    }
+
+    pub fn written(&mut self, address: usize) {
+        self.mark(address, WRITTEN);
+    }
+
+    fn mark(&mut self, address: usize, flag: u8) {
+        if let Some(flags) = self.flags.get_mut(address) {
+            *flags |= flag;
+        }
+    }
+
+    fn rom_flags(&self) -> &[u8] {
+        let start = self.platform.program_start();
+        &self.flags[start..start + self.rom_size]
+    }
+
+    pub fn summary(&self) -> CoverageSummary {
+        let count = |flag: u8| {
+            self.rom_flags()
+                .iter()
+                .filter(|&&flags| flags & flag != 0)
+                .count()
+        };
+        CoverageSummary {
+            rom_size: self.rom_size,
+            executed: count(EXECUTED),
+            read: count(READ),
+            written: count(WRITTEN),
+            covered: count(EXECUTED | READ | WRITTEN),
+        }
+    }
+
+    pub fn write_report_file(&self, memory: &[u8], path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_report(memory, &mut out)?;
+        out.flush()
+    }
+
+    /// Writes the summary followed by a disassembly of the ROM where every
+    /// line is marked with how its bytes were used: X for executed, R for
+    /// read and W for written
+    pub fn write_report<W: Write>(&self, memory: &[u8], out: &mut W) -> io::Result<()> {
+        let summary = self.summary();
+        let percent = |count: usize| count as f64 * 100.0 / summary.rom_size.max(1) as f64;
+        writeln!(out, "ROM size: {} bytes", summary.rom_size)?;
+        writeln!(
+            out,
+            "Executed: {} bytes ({:.1}%)",
+            summary.executed,
+            percent(summary.executed)
+        )?;
+        writeln!(
+            out,
+            "Read:     {} bytes ({:.1}%)",
+            summary.read,
+            percent(summary.read)
+        )?;
+        writeln!(
+            out,
+            "Written:  {} bytes ({:.1}%)",
+            summary.written,
+            percent(summary.written)
+        )?;
+        writeln!(
+            out,
+            "Covered:  {} bytes ({:.1}%)",
+            summary.covered,
+            percent(summary.covered)
+        )?;
+        writeln!(out)?;
+
+        let mut address = self.platform.program_start();
+        let end = address + self.rom_size;
+        while address < end {
+            let flags = self.flags[address];
+            // Instructions are two bytes, except where an instruction was
+            // executed starting at the next byte
+            let length = if address + 1 < end
+                && (flags & INSTRUCTION_START != 0
+                    || self.flags[address + 1] & INSTRUCTION_START == 0)
+            {
+                2
+            } else {
+                1
+            };
+
+            let usage = self.flags[address..address + length]
+                .iter()
+                .fold(0, |usage, &flags| usage | flags);
+            let marks = format!(
+                "{}{}{}",
+                if usage & EXECUTED != 0 { 'X' } else { '-' },
+                if usage & READ != 0 { 'R' } else { '-' },
+                if usage & WRITTEN != 0 { 'W' } else { '-' }
+            );
+
+            if length == 2 {
+                let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+                writeln!(
+                    out,
+                    "{:03X}  {:04X}  {}  {}",
+                    address,
+                    opcode,
+                    marks,
+                    Instruction::parse_for(opcode, self.platform)
+                )?;
+            } else {
+                writeln!(out, "{:03X}  {:02X}    {}", address, memory[address], marks)?;
+            }
+            address += length;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::{Cpu, MEMORY_SIZE, PROGRAM_START};
+
+    #[test]
+    fn executed_read_and_written_bytes_are_counted() {
+        // LD I, 0x208; LD [I], V0; DRW V0, V0, 1; JP 0x206; data
+        let rom = vec![0xA2, 0x08, 0xF0, 0x55, 0xD0, 0x01, 0x12, 0x06, 0x00, 0xFF];
+        let mut cpu = Cpu::new(rom.clone(), 360, false);
+        cpu.set_coverage(Coverage::new(rom.len(), Platform::Chip8));
+        for _ in 0..5 {
+            cpu.cycle();
+        }
+
+        let coverage = cpu.take_coverage().unwrap();
+        let summary = coverage.summary();
+        assert_eq!(10, summary.rom_size);
+        assert_eq!(8, summary.executed);
+        assert_eq!(1, summary.read);
+        assert_eq!(1, summary.written);
+        assert_eq!(9, summary.covered);
+
+        let mut memory = vec![0; MEMORY_SIZE];
+        memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        let mut report = Vec::new();
+        coverage.write_report(&memory, &mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("Covered:  9 bytes (90.0%)"));
+        assert!(report.contains("204  D001  X--  DRW V0, V0, 1"));
+        assert!(report.contains("208  00FF  -RW  SYS 0xFF"));
+    }
+}
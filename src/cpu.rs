@@ -1,14 +1,38 @@
-use display::{Display, Screen, SPRITES};
+use alu;
+use alu::Operation;
+use analysis::Analyzer;
+use cheats::{Cheat, Cheats};
+use coverage::Coverage;
+use decode::DecodeCache;
+use display::{Display, Screen, HEIGHT, SPRITES, WIDTH, ZONE_HEIGHT, ZONE_WIDTH};
 use instruction::{Address, Instruction, Register, Value};
+use megachip::{BlendMode, Sample, MEGA_HEIGHT, MEGA_WIDTH};
+use observer::Observer;
+use platform::Platform;
+use profiler::Profiler;
+use quirks::Quirks;
 use rand;
 use rand::Rng;
+use routines;
+use routines::{Routine, HIRES_CLEAR, HIRES_ENTRY};
+use std::collections::HashMap;
+use std::mem;
+use timing;
+use timing::{Timing, VIP_CYCLES_PER_FRAME};
+use trace::{Change, TraceEntry, Tracer};
 
 const INSTRUCTION_SIZE: u16 = 2;
 
+pub const MEMORY_SIZE: usize = 4096;
+pub const PROGRAM_START: usize = 0x200;
+
 pub struct Cpu {
-    memory: [u8; 4096],
+    memory: Vec<u8>,
+    /// Addresses the program has written to since it was loaded
+    written: Vec<bool>,
     registers: [u8; 16],
-    index: u16,
+    /// The MEGA-CHIP loads 24 bit addresses into I
+    index: u32,
     pc: u16,
     stack: [u16; 16],
     sp: u16,
@@ -16,38 +40,122 @@ pub struct Cpu {
     sound_timer: u8,
     tick: u32,
     timer_tick: u32,
+    timing: Timing,
+    /// VIP machine cycles spent in the current frame and frames completed
+    frame_cycles: u32,
+    vip_frames: u32,
     keys: [bool; 16],
+    /// Whether FX0A is waiting for a key
+    waiting_for_key: bool,
+    /// The second keypad of the CHIP-8X
+    keys2: [bool; 16],
+    /// The last value sent to the CHIP-8X sound output port
+    port: u8,
+    /// The MEGA-CHIP sound sample playing, if any
+    sample: Option<Sample>,
+    platform: Platform,
+    quirks: Quirks,
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    analyzer: Option<Analyzer>,
+    /// Native routines run in place of machine code subroutines by 0NNN
+    routines: HashMap<Address, Routine>,
+    observers: Vec<Box<dyn Observer>>,
+    cheats: Option<Cheats>,
+    decode_cache: Option<DecodeCache>,
     debug_mode: bool,
     pub display: Display,
     pub draw_flag: bool,
     pub faulted: bool,
 }
 
+/// A read-only view of the CPU for debugging tools
+pub struct CpuState<'a> {
+    pub registers: &'a [u8; 16],
+    pub index: u32,
+    pub pc: u16,
+    pub sp: u16,
+    pub stack: &'a [u16; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keys: &'a [bool; 16],
+    pub memory: &'a [u8],
+    pub tick: u32,
+    pub platform: Platform,
+}
+
 impl Cpu {
     pub fn new(game_data: Vec<u8>, clock_speed: u32, debug_mode: bool) -> Cpu {
-        let mut memory = [0; 4096];
-        for (i, byte) in game_data.iter().enumerate() {
-            memory[0x200 + i] = byte.clone();
+        Cpu::with_platform(game_data, clock_speed, debug_mode, Platform::Chip8)
+    }
+
+    /// A CPU for a variant of CHIP-8, which decides where the ROM is loaded
+    /// and how opcodes are decoded
+    pub fn with_platform(
+        game_data: Vec<u8>,
+        clock_speed: u32,
+        debug_mode: bool,
+        platform: Platform,
+    ) -> Cpu {
+        let program_start = platform.program_start();
+        let memory_size = platform.memory_size();
+        let mut memory = vec![0; memory_size];
+        for (i, byte) in game_data
+            .iter()
+            .take(memory_size - program_start)
+            .enumerate()
+        {
+            memory[program_start + i] = byte.clone();
         }
         for (i, byte) in SPRITES.iter().enumerate() {
             memory[i] = byte.clone();
         }
 
-        let display = Display::new(debug_mode);
+        let mut display = Display::new(debug_mode);
+        let mut routines: HashMap<Address, Routine> = HashMap::new();
+        match platform {
+            Platform::Chip8 => {}
+            Platform::HiresChip8 => {
+                display.set_hires(true);
+                memory[program_start..program_start + 2].copy_from_slice(&HIRES_ENTRY);
+                routines.insert(HIRES_CLEAR, Box::new(routines::clear_hires));
+            }
+            Platform::Chip8X => display.enable_colors(),
+            Platform::MegaChip => display.enable_mega(),
+        }
 
         Cpu {
             memory: memory,
+            written: vec![false; memory_size],
             registers: [0; 16],
             index: 0,
-            pc: 0x200,
+            pc: program_start as u16,
             stack: [0; 16],
             sp: 0,
             del_timer: 0,
             sound_timer: 0,
             tick: 0,
-            timer_tick: clock_speed / 60,
+            timer_tick: (clock_speed / 60).max(1),
+            timing: Timing::Simple,
+            frame_cycles: 0,
+            vip_frames: 0,
             display: display,
             keys: [false; 16],
+            waiting_for_key: false,
+            keys2: [false; 16],
+            port: 0,
+            sample: None,
+            platform,
+            quirks: Quirks::default(),
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            analyzer: None,
+            routines,
+            observers: Vec::new(),
+            cheats: None,
+            decode_cache: Some(DecodeCache::new(platform)),
             draw_flag: false,
             faulted: false,
             debug_mode: debug_mode,
@@ -59,22 +167,76 @@ impl Cpu {
             return;
         }
 
-        let raw_instruction = self.read_next_instruction();
-        let instruction = Instruction::parse(raw_instruction);
+        let decoded = match self.decode_cache {
+            Some(ref mut cache) => cache.get(&self.memory, self.pc),
+            None => None,
+        };
+        let (raw_instruction, instruction) = decoded.unwrap_or_else(|| {
+            let raw_instruction = self.read_next_instruction();
+            (
+                raw_instruction,
+                Instruction::parse_for(raw_instruction, self.platform),
+            )
+        });
+        self.execute(raw_instruction, instruction);
+    }
+
+    /// Executes the instruction at the program counter, already decoded from
+    /// `raw_instruction`, e.g. by recompiled code
+    pub fn execute(&mut self, raw_instruction: u16, instruction: Instruction) {
+        if self.faulted {
+            return;
+        }
 
-        println!(
-            "[PC:0x{:X}] [RAW:0x{:04X}] {}",
-            self.pc, raw_instruction, instruction
-        );
+        if self.debug_mode {
+            println!(
+                "[PC:0x{:X}] [RAW:0x{:04X}] {}",
+                self.pc, raw_instruction, instruction
+            );
+        }
 
         if instruction == Instruction::InvalidOperation {
             self.faulted = true;
+            let pc = self.pc;
+            self.notify(|observer| observer.fault(pc, raw_instruction));
         } else {
+            let (frame, sounding) = (self.frame(), self.sound_timer > 0);
+            let (cycle, pc) = (self.tick, self.pc);
+            let traced = match self.tracer {
+                Some(ref tracer) => tracer.wants(pc, &instruction),
+                None => false,
+            };
+            let before = (self.registers, self.index);
+            let cost = self.instruction_cost(&instruction);
+            if let Some(ref mut profiler) = self.profiler {
+                profiler.record(pc, raw_instruction, &instruction, u64::from(cost));
+            }
+            if let Some(ref mut coverage) = self.coverage {
+                coverage.executed(pc);
+            }
+            if let Some(ref mut analyzer) = self.analyzer {
+                analyzer.executing(pc, cycle, &instruction);
+            }
+
             self.execute_instruction(instruction);
 
-            self.handle_timers();
+            self.retire(1, cost);
+
+            if self.frame() != frame {
+                let frame = self.frame();
+                self.notify(|observer| observer.frame(frame));
+                self.freeze_cheats();
+            }
+            match (sounding, self.sound_timer > 0) {
+                (false, true) => self.notify(|observer| observer.sound_started()),
+                (true, false) => self.notify(|observer| observer.sound_stopped()),
+                _ => {}
+            }
 
-            self.tick += 1;
+            if traced {
+                let (registers, index) = before;
+                self.trace(cycle, pc, raw_instruction, &instruction, registers, index);
+            }
 
             if self.debug_mode {
                 self.debug();
@@ -82,14 +244,295 @@ impl Cpu {
         }
     }
 
+    pub fn pc(&self) -> Address {
+        self.pc
+    }
+
+    /// The registers V0 to VF
+    pub fn registers(&self) -> &[Value; 16] {
+        &self.registers
+    }
+
+    /// The index register, which is 24 bits wide on the MEGA-CHIP
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// Changes a register from outside the interpreter, e.g. in recompiled code
+    /// or the debugger
+    pub fn poke_register(&mut self, register: Register, value: Value) {
+        self.set_register(register, value);
+    }
+
+    pub fn poke_index(&mut self, index: u32) {
+        self.index = index;
+    }
+
+    pub fn poke_pc(&mut self, pc: Address) {
+        self.set_program_counter(pc);
+    }
+
+    /// Counts instructions that were run without `execute`, e.g. by
+    /// recompiled code, running the timers as if they had been interpreted.
+    /// `vip_cycles` is the machine cycles they take with VIP timing.
+    pub fn retire(&mut self, instructions: u32, vip_cycles: u32) {
+        match self.timing {
+            Timing::Simple => {
+                for _ in 0..instructions {
+                    self.handle_timers();
+                    self.tick += 1;
+                }
+            }
+            Timing::Vip => {
+                self.spend_machine_cycles(vip_cycles);
+                self.tick += instructions;
+            }
+        }
+    }
+
+    /// Whether the program wrote to any address from `start` up to `end`
+    pub fn code_modified(&self, start: Address, end: Address) -> bool {
+        let end = (end as usize).min(self.written.len());
+        self.written[(start as usize).min(end)..end]
+            .iter()
+            .any(|&written| written)
+    }
+
+    /// Number of 60 Hz timer frames emulated so far
+    pub fn frame(&self) -> u32 {
+        match self.timing {
+            Timing::Simple => self.tick / self.timer_tick,
+            Timing::Vip => self.vip_frames,
+        }
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Switches between running a fixed number of instructions per frame and
+    /// charging each instruction its COSMAC VIP machine cycles
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
     pub fn get_screen(&mut self) -> &Screen {
         self.display.get_screen()
     }
 
+    pub fn state(&self) -> CpuState<'_> {
+        CpuState {
+            registers: &self.registers,
+            index: self.index,
+            pc: self.pc,
+            sp: self.sp,
+            stack: &self.stack,
+            delay_timer: self.del_timer,
+            sound_timer: self.sound_timer,
+            keys: &self.keys,
+            memory: &self.memory,
+            tick: self.tick,
+            platform: self.platform,
+        }
+    }
+
+    /// The return addresses on the stack, innermost last
+    pub fn stack(&self) -> &[Address] {
+        &self.stack[1..=self.sp as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.del_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// All of memory, the size of which depends on the platform
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn poke_delay_timer(&mut self, value: u8) {
+        self.del_timer = value;
+    }
+
+    pub fn poke_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Writes bytes to memory from outside the program. They count as
+    /// written like the program's own writes, so self-modifying code checks
+    /// and the decode cache see them, but they are not reported to coverage,
+    /// analysis or observers. Returns false and writes nothing when the bytes
+    /// do not fit in memory.
+    pub fn poke(&mut self, address: usize, bytes: &[u8]) -> bool {
+        if address + bytes.len() > self.memory.len() {
+            return false;
+        }
+        for (offset, &byte) in bytes.iter().enumerate() {
+            self.memory[address + offset] = byte;
+            self.written[address + offset] = true;
+            if let Some(ref mut cache) = self.decode_cache {
+                cache.invalidate(address + offset);
+            }
+        }
+        true
+    }
+
     pub fn set_key(&mut self, key: u8, pressed: bool) {
         self.keys[key as usize] = pressed;
     }
 
+    /// Presses or releases a key of the CHIP-8X's second keypad
+    pub fn set_second_key(&mut self, key: u8, pressed: bool) {
+        self.keys2[key as usize] = pressed;
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// The MEGA-CHIP sound sample the program is playing
+    pub fn sample(&self) -> Option<&Sample> {
+        self.sample.as_ref()
+    }
+
+    /// The last value the program sent to the CHIP-8X sound output port,
+    /// which sets the pitch of its tone
+    pub fn port(&self) -> u8 {
+        self.port
+    }
+
+    /// Starts writing every executed instruction to the tracer
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// Starts counting where cycles are spent
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    /// Starts recording which bytes of memory are executed, read and written
+    pub fn set_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(coverage);
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Starts watching for suspicious behavior such as self-modifying code
+    pub fn set_analyzer(&mut self, analyzer: Analyzer) {
+        self.analyzer = Some(analyzer);
+    }
+
+    pub fn take_analyzer(&mut self) -> Option<Analyzer> {
+        self.analyzer.take()
+    }
+
+    /// Runs `routine` whenever the program calls `address` with 0NNN, in
+    /// place of the machine code subroutine of the original interpreter. It
+    /// runs with the program counter already on the next instruction.
+    pub fn register_routine(&mut self, address: Address, routine: Routine) {
+        self.routines.insert(address, routine);
+    }
+
+    pub fn remove_routine(&mut self, address: Address) -> Option<Routine> {
+        self.routines.remove(&address)
+    }
+
+    /// Starts telling an observer about frames, draws, sound, key waits,
+    /// faults and memory writes
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    pub fn take_observers(&mut self) -> Vec<Box<dyn Observer>> {
+        mem::take(&mut self.observers)
+    }
+
+    /// Writes the patches of the cheats now and whenever the CPU is reset,
+    /// and the frozen values now and after every frame
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        cheats.patch(self);
+        self.cheats = Some(cheats);
+        self.freeze_cheats();
+    }
+
+    /// Adds a cheat, applying it right away
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        let mut cheats = self.take_cheats().unwrap_or_default();
+        cheats.add(cheat);
+        self.set_cheats(cheats);
+    }
+
+    pub fn take_cheats(&mut self) -> Option<Cheats> {
+        self.cheats.take()
+    }
+
+    /// Clears the screen like 00E0, for routines standing in for the
+    /// interpreter
+    pub fn clear_screen(&mut self) {
+        self.display.clear();
+        self.draw_flag = true;
+        self.notify(|observer| observer.clear());
+    }
+
+    /// Turns caching of decoded instructions on or off, it is on by default
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = if enabled {
+            Some(DecodeCache::new(self.platform))
+        } else {
+            None
+        };
+    }
+
+    /// Starts the program over as if the CPU was created again, keeping the
+    /// speed, quirks, routines, observers, cheats and any tracer, profiler,
+    /// coverage or analyzer
+    pub fn reset(&mut self, game_data: Vec<u8>) {
+        let mut fresh = Cpu::with_platform(game_data, 0, self.debug_mode, self.platform);
+        fresh.timer_tick = self.timer_tick;
+        fresh.timing = self.timing;
+        fresh.set_quirks(self.quirks);
+        fresh.tracer = self.tracer.take();
+        fresh.profiler = self.profiler.take();
+        fresh.coverage = self.coverage.take();
+        fresh.analyzer = self.analyzer.take();
+        fresh.routines.extend(self.routines.drain());
+        fresh.observers = self.take_observers();
+        if let Some(cheats) = self.take_cheats() {
+            fresh.set_cheats(cheats);
+        }
+        if self.decode_cache.is_none() {
+            fresh.decode_cache = None;
+        }
+        if let Some(ref mut profiler) = fresh.profiler {
+            profiler.restart();
+        }
+        *self = fresh;
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.display.set_clipping(quirks.clip_sprites);
+    }
+
     fn read_next_instruction(&self) -> u16 {
         let upper = self.memory[self.pc as usize] as u16;
         let lower = self.memory[(self.pc + 1) as usize] as u16;
@@ -100,41 +543,132 @@ impl Cpu {
         match instruction {
             Instruction::Clear => self.clear(),
             Instruction::Return => self.ret(),
-            Instruction::ExRoutine(_a) => self.noop(), // Not sure how to implement this
+            Instruction::ExRoutine(a) => self.call_routine(a),
             Instruction::Jump(a) => self.jump(a),
             Instruction::Call(a) => self.call(a),
             Instruction::LoadVal(r, v) => self.load_val(r, v),
             Instruction::SkipIfEqual(r, v) => self.skip_equal(r, v),
             Instruction::SkipIfNotEqual(r, v) => self.skip_not_equal(r, v),
             Instruction::SkipIfRegEqual(r1, r2) => self.skip_reg_equal(r1, r2),
+            Instruction::SkipIfRegNotEqual(r1, r2) => self.skip_reg_not_equal(r1, r2),
             Instruction::AddVal(r, v) => self.add_val(r, v),
-            Instruction::LoadReg(r1, r2) => self.load_reg(r1, r2),
-            Instruction::Or(r1, r2) => self.or(r1, r2),
-            Instruction::And(r1, r2) => self.and(r1, r2),
-            Instruction::Xor(r1, r2) => self.xor(r1, r2),
-            Instruction::AddReg(r1, r2) => self.add_reg(r1, r2),
-            Instruction::SubReg(r1, r2) => self.sub_reg(r1, r2),
-            Instruction::ShiftRight(r) => self.shift_right(r),
-            Instruction::ShiftLeft(r) => self.shift_left(r),
+            Instruction::LoadReg(r1, r2) => self.alu(Operation::Load, r1, r2),
+            Instruction::Or(r1, r2) => self.alu(Operation::Or, r1, r2),
+            Instruction::And(r1, r2) => self.alu(Operation::And, r1, r2),
+            Instruction::Xor(r1, r2) => self.alu(Operation::Xor, r1, r2),
+            Instruction::AddReg(r1, r2) => self.alu(Operation::Add, r1, r2),
+            Instruction::SubReg(r1, r2) => self.alu(Operation::Sub, r1, r2),
+            Instruction::ShiftRight(r1, r2) => self.alu(Operation::ShiftRight, r1, r2),
+            Instruction::SubRegReverse(r1, r2) => self.alu(Operation::SubReverse, r1, r2),
+            Instruction::ShiftLeft(r1, r2) => self.alu(Operation::ShiftLeft, r1, r2),
             Instruction::SetIndexRegister(a) => self.set_index(a),
+            Instruction::JumpOffset(a) => self.jump_offset(a),
             Instruction::Random(r, v) => self.rand(r, v),
             Instruction::Draw(r1, r2, v) => self.draw(r1, r2, v),
             Instruction::SkipIfKey(r) => self.skip_key(r),
             Instruction::SkipIfNotKey(r) => self.skip_not_key(r),
+            Instruction::LoadDelay(r) => self.load_delay(r),
+            Instruction::WaitKey(r) => self.wait_key(r),
+            Instruction::SetDelay(r) => self.set_delay(r),
+            Instruction::SetSound(r) => self.set_sound(r),
             Instruction::AddIndex(r) => self.add_index(r),
             Instruction::LoadDigit(r) => self.load_digit(r),
             Instruction::LoadBCD(r) => self.load_bcd(r),
             Instruction::StoreIndex(r) => self.store_index(r),
             Instruction::ReadIndex(r) => self.read_index(r),
+            Instruction::CycleBackground => self.cycle_background(),
+            Instruction::SetZoneColor(r1, r2) => self.set_zone_color(r1, r2),
+            Instruction::SetSpriteColor(r1, r2, v) => self.set_sprite_color(r1, r2, v),
+            Instruction::SkipIfKey2(r) => self.skip_key2(r),
+            Instruction::SkipIfNotKey2(r) => self.skip_not_key2(r),
+            Instruction::OutputPort(r) => self.output_port(r),
+            Instruction::MegaOff => self.set_mega_mode(false),
+            Instruction::MegaOn => self.set_mega_mode(true),
+            Instruction::LoadLongIndex(v) => self.load_long_index(v),
+            Instruction::LoadPalette(v) => self.load_palette(v),
+            Instruction::SetSpriteWidth(v) => self.set_sprite_width(v),
+            Instruction::SetSpriteHeight(v) => self.set_sprite_height(v),
+            Instruction::SetAlpha(v) => self.set_alpha(v),
+            Instruction::PlaySample(v) => self.play_sample(v),
+            Instruction::StopSample => self.stop_sample(),
+            Instruction::SetBlendMode(v) => self.set_blend_mode(v),
+            Instruction::SetCollisionColor(v) => self.set_collision_color(v),
             Instruction::InvalidOperation => {}
         };
     }
 
+    /// Records an executed instruction along with the registers it changed,
+    /// given their values from before it ran
+    fn trace(
+        &mut self,
+        cycle: u32,
+        pc: Address,
+        opcode: u16,
+        instruction: &Instruction,
+        registers: [u8; 16],
+        index: u32,
+    ) {
+        let mut changes = Vec::new();
+        for (register, (&old, &new)) in registers.iter().zip(self.registers.iter()).enumerate() {
+            if old != new {
+                changes.push(Change::Register(register as u8, old, new));
+            }
+        }
+        if index != self.index {
+            changes.push(Change::Index(index, self.index));
+        }
+
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.record(&TraceEntry {
+                cycle,
+                pc,
+                opcode,
+                instruction,
+                changes: &changes,
+            });
+        }
+    }
+
+    /// How long an instruction takes: one instruction, or its VIP machine
+    /// cycles including the wait of draws for the start of the next frame
+    fn instruction_cost(&self, instruction: &Instruction) -> u32 {
+        match self.timing {
+            Timing::Simple => 1,
+            Timing::Vip => {
+                let cycles = timing::vip_cycles(
+                    instruction,
+                    &self.registers,
+                    self.display.height(),
+                    self.quirks.clip_sprites,
+                );
+                match *instruction {
+                    Instruction::Draw(_, _, _) => cycles + VIP_CYCLES_PER_FRAME - self.frame_cycles,
+                    _ => cycles,
+                }
+            }
+        }
+    }
+
     fn handle_timers(&mut self) {
         if self.tick % self.timer_tick != 0 {
             return;
         }
 
+        self.count_down_timers();
+    }
+
+    /// Advances the VIP's clock, counting the timers down at the start of
+    /// every frame like its display interrupt
+    fn spend_machine_cycles(&mut self, cycles: u32) {
+        self.frame_cycles += cycles;
+        while self.frame_cycles >= VIP_CYCLES_PER_FRAME {
+            self.frame_cycles -= VIP_CYCLES_PER_FRAME;
+            self.vip_frames += 1;
+            self.count_down_timers();
+        }
+    }
+
+    fn count_down_timers(&mut self) {
         if self.del_timer > 0 {
             self.del_timer -= 1;
         }
@@ -145,6 +679,22 @@ impl Cpu {
         }
     }
 
+    fn freeze_cheats(&mut self) {
+        if let Some(cheats) = self.cheats.take() {
+            cheats.freeze(self);
+            self.cheats = Some(cheats);
+        }
+    }
+
+    fn notify<F>(&mut self, mut event: F)
+    where
+        F: FnMut(&mut dyn Observer),
+    {
+        for observer in self.observers.iter_mut() {
+            event(observer.as_mut());
+        }
+    }
+
     fn read_register(&self, register: Register) -> Value {
         self.registers[register as usize]
     }
@@ -153,6 +703,32 @@ impl Cpu {
         self.registers[register as usize] = value;
     }
 
+    /// Reads a byte of memory as data, as opposed to fetching an instruction
+    fn read_memory(&mut self, address: usize) -> u8 {
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.read(address);
+        }
+        if let Some(ref mut analyzer) = self.analyzer {
+            analyzer.read(address);
+        }
+        self.memory[address]
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) {
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.written(address);
+        }
+        if let Some(ref mut analyzer) = self.analyzer {
+            analyzer.written(address);
+        }
+        self.memory[address] = value;
+        self.written[address] = true;
+        self.notify(|observer| observer.memory_written(address, value));
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.invalidate(address);
+        }
+    }
+
     fn set_program_counter(&mut self, new_addr: Address) {
         self.pc = new_addr;
     }
@@ -168,8 +744,15 @@ impl Cpu {
         val
     }
 
-    fn noop(&mut self) {
+    /// Runs the native routine registered for the address, returning to the
+    /// next instruction like a machine code subroutine. Calls to addresses
+    /// without one are skipped.
+    fn call_routine(&mut self, address: Address) {
         self.pc += INSTRUCTION_SIZE;
+        if let Some(mut routine) = self.routines.remove(&address) {
+            routine(self);
+            self.routines.entry(address).or_insert(routine);
+        }
     }
 
     fn load_val(&mut self, register: Register, value: Value) {
@@ -187,66 +770,30 @@ impl Cpu {
         self.pc += INSTRUCTION_SIZE;
     }
 
-    fn add_reg(&mut self, register1: Register, register2: Register) {
+    /// Runs an 8XYN instruction, applying the shift and VF reset quirks
+    fn alu(&mut self, operation: Operation, register1: Register, register2: Register) {
         let reg1_val = self.read_register(register1);
         let reg2_val = self.read_register(register2);
 
-        let new_val: u8;
-        let carry_val: u8;
-        if let Some(result) = reg1_val.checked_add(reg2_val) {
-            new_val = result;
-            carry_val = 0;
+        let operand = if operation.is_shift() && self.quirks.shift_uses_vy {
+            reg2_val
         } else {
-            // Overflow!
-            // Take lower 8 bits of full addition result
-            new_val = (reg1_val as u16 + reg2_val as u16) as u8;
-            carry_val = 1;
-        }
-
-        self.set_register(register1, new_val);
-        self.set_register(0xF, carry_val);
-
-        self.pc += INSTRUCTION_SIZE;
-    }
-
-    fn sub_reg(&mut self, register1: Register, register2: Register) {
-        let reg1_val = self.read_register(register1);
-        let reg2_val = self.read_register(register2);
+            reg1_val
+        };
+        let output = alu::apply(operation, operand, reg2_val);
 
-        let new_val: u8;
-        let borrow_val: u8;
-        if let Some(result) = reg1_val.checked_sub(reg2_val) {
-            new_val = result;
-            borrow_val = 1;
-        } else {
-            new_val = (reg1_val as i8 - reg2_val as i8) as u8;
-            borrow_val = 0;
+        self.set_register(register1, output.result);
+        if let Some(flag) = output.flag {
+            self.set_register(0xF, flag);
+        } else if operation.is_logic() && self.quirks.logic_resets_vf {
+            self.set_register(0xF, 0);
         }
 
-        self.set_register(register1, new_val);
-        self.set_register(0xF, borrow_val);
-
-        self.pc += INSTRUCTION_SIZE;
-    }
-
-    fn shift_right(&mut self, register: Register) {
-        let reg_val = self.read_register(register);
-        self.set_register(0xF, 0b00000001 & reg_val);
-        self.set_register(register, reg_val >> 1);
-
-        self.pc += INSTRUCTION_SIZE;
-    }
-
-    fn shift_left(&mut self, register: Register) {
-        let reg_val = self.read_register(register);
-        self.set_register(0xF, 0b10000000 & reg_val);
-        self.set_register(register, reg_val << 1);
-
         self.pc += INSTRUCTION_SIZE;
     }
 
     fn set_index(&mut self, addr: Address) {
-        self.index = addr;
+        self.index = addr as u32;
         self.pc += INSTRUCTION_SIZE;
     }
 
@@ -254,9 +801,15 @@ impl Cpu {
         self.set_program_counter(addr);
     }
 
+    /// Jumps to NNN + V0, which wraps around past the last address
+    fn jump_offset(&mut self, addr: Address) {
+        let offset = self.read_register(0x0) as Address;
+        self.set_program_counter((addr + offset) & 0xFFF);
+    }
+
     fn call(&mut self, addr: Address) {
-        let current_pc = self.pc;
-        self.push_stack(current_pc);
+        let return_pc = self.pc + INSTRUCTION_SIZE;
+        self.push_stack(return_pc);
         self.set_program_counter(addr);
     }
 
@@ -296,34 +849,15 @@ impl Cpu {
         self.pc += pc_skip;
     }
 
-    fn load_reg(&mut self, register1: Register, register2: Register) {
-        let reg2_val = self.read_register(register2);
-        self.set_register(register1, reg2_val);
-        self.pc += INSTRUCTION_SIZE;
-    }
-
-    fn or(&mut self, register1: Register, register2: Register) {
-        let reg1_val = self.read_register(register1);
-        let reg2_val = self.read_register(register2);
-        self.set_register(register1, reg1_val | reg2_val);
-
-        self.pc += INSTRUCTION_SIZE;
-    }
-
-    fn and(&mut self, register1: Register, register2: Register) {
-        let reg1_val = self.read_register(register1);
-        let reg2_val = self.read_register(register2);
-        self.set_register(register1, reg1_val & reg2_val);
-
-        self.pc += INSTRUCTION_SIZE;
-    }
-
-    fn xor(&mut self, register1: Register, register2: Register) {
-        let reg1_val = self.read_register(register1);
-        let reg2_val = self.read_register(register2);
-        self.set_register(register1, reg1_val ^ reg2_val);
-
-        self.pc += INSTRUCTION_SIZE;
+    fn skip_reg_not_equal(&mut self, register1: Register, register2: Register) {
+        let reg_val1 = self.read_register(register1);
+        let reg_val2 = self.read_register(register2);
+        let pc_skip = if reg_val1 != reg_val2 {
+            INSTRUCTION_SIZE * 2
+        } else {
+            INSTRUCTION_SIZE
+        };
+        self.pc += pc_skip;
     }
 
     fn rand(&mut self, register: Register, value: Value) {
@@ -333,17 +867,51 @@ impl Cpu {
     }
 
     fn draw(&mut self, register1: Register, register2: Register, value: Value) {
-        let x = self.read_register(register1);
-        let y = self.read_register(register2);
+        if self.display.mega_mode() {
+            return self.draw_mega(register1, register2);
+        }
+
+        // The starting position always wraps, only the sprite can be clipped
+        let x = self.read_register(register1) as usize % WIDTH;
+        let y = self.read_register(register2) as usize % self.display.height();
 
         let mut sprite = Vec::new();
         for i in 0..value {
-            let sprite_index = (self.index + i as u16) as usize;
-            sprite.push(self.memory[sprite_index]);
+            let sprite_index = self.index as usize + i as usize;
+            sprite.push(self.read_memory(sprite_index));
         }
 
-        let flipped = self.display.draw_sprite(&sprite, x as usize, y as usize);
+        let flipped = self.display.draw_sprite(&sprite, x, y);
         self.set_register(0xF, flipped as u8);
+        self.notify(|observer| observer.draw(x, y, flipped));
+
+        self.draw_flag = true;
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    /// Draws a sprite of the set size with one palette color per byte, which
+    /// sets VF when it covers a pixel of the collision color
+    fn draw_mega(&mut self, register1: Register, register2: Register) {
+        let x = self.read_register(register1) as usize % MEGA_WIDTH;
+        let y = self.read_register(register2) as usize % MEGA_HEIGHT;
+        let (width, height) = match self.display.mega() {
+            Some(mega) => mega.sprite_size(),
+            None => (0, 0),
+        };
+
+        let mut sprite = Vec::with_capacity(width * height);
+        for i in 0..width * height {
+            let sprite_index = self.index as usize + i;
+            sprite.push(self.read_memory(sprite_index));
+        }
+
+        let collided = match self.display.mega_mut() {
+            Some(mega) => mega.draw_sprite(&sprite, x, y),
+            None => false,
+        };
+        self.set_register(0xF, collided as u8);
+        self.notify(|observer| observer.draw(x, y, collided));
 
         self.draw_flag = true;
 
@@ -351,7 +919,7 @@ impl Cpu {
     }
 
     fn clear(&mut self) {
-        self.display.clear();
+        self.clear_screen();
 
         self.pc += INSTRUCTION_SIZE;
     }
@@ -376,8 +944,43 @@ impl Cpu {
         self.pc += pc_skip;
     }
 
+    fn load_delay(&mut self, register: Register) {
+        let delay = self.del_timer;
+        self.set_register(register, delay);
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    /// Stores the lowest key that is held down, or runs again next cycle
+    /// when there is none
+    fn wait_key(&mut self, register: Register) {
+        if let Some(key) = self.keys.iter().position(|&pressed| pressed) {
+            self.set_register(register, key as u8);
+            self.pc += INSTRUCTION_SIZE;
+            if self.waiting_for_key {
+                self.waiting_for_key = false;
+                self.notify(|observer| observer.key_wait_ended(register, key as u8));
+            }
+        } else if !self.waiting_for_key {
+            self.waiting_for_key = true;
+            self.notify(|observer| observer.key_wait_started(register));
+        }
+    }
+
+    fn set_delay(&mut self, register: Register) {
+        self.del_timer = self.read_register(register);
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn set_sound(&mut self, register: Register) {
+        self.sound_timer = self.read_register(register);
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
     fn add_index(&mut self, register: Register) {
-        self.index += self.read_register(register) as u16;
+        self.index += self.read_register(register) as u32;
 
         self.pc += INSTRUCTION_SIZE;
     }
@@ -385,7 +988,7 @@ impl Cpu {
     fn load_digit(&mut self, register: Register) {
         // Each digit sprite occupies five bytes of space starting at 0x00
         let sprite_location = 0x00 + (self.read_register(register) as u16 * 5);
-        self.index = sprite_location as u16;
+        self.index = sprite_location as u32;
 
         self.pc += INSTRUCTION_SIZE;
     }
@@ -393,28 +996,192 @@ impl Cpu {
     fn load_bcd(&mut self, register: Register) {
         let reg_val = self.read_register(register);
 
-        self.memory[self.index as usize] = reg_val / 100;
-        self.memory[(self.index + 1) as usize] = (reg_val / 10) % 10;
-        self.memory[(self.index + 2) as usize] = (reg_val % 100) % 10;
+        let index = self.index as usize;
+        self.write_memory(index, reg_val / 100);
+        self.write_memory(index + 1, (reg_val / 10) % 10);
+        self.write_memory(index + 2, (reg_val % 100) % 10);
 
         self.pc += INSTRUCTION_SIZE;
     }
 
     fn store_index(&mut self, register: Register) {
         for i in 0..(register + 1) {
-            let index = (self.index + i as u16) as usize;
-            self.memory[index] = self.read_register(i);
+            let index = self.index as usize + i as usize;
+            let value = self.read_register(i);
+            self.write_memory(index, value);
         }
+        self.increment_index_after_load_store(register);
 
         self.pc += INSTRUCTION_SIZE;
     }
 
     fn read_index(&mut self, register: Register) {
         for i in 0..(register + 1) {
-            let index = (self.index + i as u16) as usize;
-            let new_val = self.memory[index];
+            let index = self.index as usize + i as usize;
+            let new_val = self.read_memory(index);
             self.set_register(i, new_val);
         }
+        self.increment_index_after_load_store(register);
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn increment_index_after_load_store(&mut self, register: Register) {
+        if self.quirks.load_store_increments_index {
+            self.index += register as u32 + 1;
+        }
+    }
+
+    fn cycle_background(&mut self) {
+        if let Some(colors) = self.display.colors_mut() {
+            colors.cycle_background();
+        }
+        self.draw_flag = true;
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    /// The low nibbles of VX and VX+1 give the first zone column and row,
+    /// and their high nibbles how many more to color
+    fn set_zone_color(&mut self, register1: Register, register2: Register) {
+        let horizontal = self.read_register(register1) as usize;
+        let vertical = self.read_register((register1 + 1) & 0xF) as usize;
+        let first = (horizontal & 0xF, vertical & 0xF);
+        let last = (first.0 + (horizontal >> 4), first.1 + (vertical >> 4));
+        let color = self.read_register(register2);
+        if let Some(colors) = self.display.colors_mut() {
+            colors.set_zones(first, last, color);
+        }
+        self.draw_flag = true;
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    /// Colors the zones an 8 pixel wide sprite of `height` rows at VX, VX+1
+    /// would cover
+    fn set_sprite_color(&mut self, register1: Register, register2: Register, height: Value) {
+        let x = self.read_register(register1) as usize % WIDTH;
+        let y = self.read_register((register1 + 1) & 0xF) as usize % HEIGHT;
+        let right = x + 7;
+        let bottom = y + (height.max(1) as usize) - 1;
+        let first = (x / ZONE_WIDTH, y / ZONE_HEIGHT);
+        let last = (right / ZONE_WIDTH, bottom / ZONE_HEIGHT);
+        let color = self.read_register(register2);
+        if let Some(colors) = self.display.colors_mut() {
+            colors.set_zones(first, last, color);
+        }
+        self.draw_flag = true;
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn skip_key2(&mut self, register: Register) {
+        let reg_val = self.read_register(register);
+        let pc_skip = if self.keys2[(reg_val & 0xF) as usize] {
+            INSTRUCTION_SIZE * 2
+        } else {
+            INSTRUCTION_SIZE
+        };
+        self.pc += pc_skip;
+    }
+
+    fn skip_not_key2(&mut self, register: Register) {
+        let reg_val = self.read_register(register);
+        let pc_skip = if self.keys2[(reg_val & 0xF) as usize] {
+            INSTRUCTION_SIZE
+        } else {
+            INSTRUCTION_SIZE * 2
+        };
+        self.pc += pc_skip;
+    }
+
+    fn output_port(&mut self, register: Register) {
+        self.port = self.read_register(register);
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn set_mega_mode(&mut self, on: bool) {
+        self.display.set_mega_mode(on);
+        self.draw_flag = true;
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    /// Loads I with the byte of the instruction followed by the next word
+    fn load_long_index(&mut self, high: Value) {
+        let pc = self.pc as usize;
+        let low = (self.memory[pc + 2] as u32) << 8 | self.memory[pc + 3] as u32;
+        self.index = (high as u32) << 16 | low;
+
+        self.pc += Instruction::LoadLongIndex(high).size();
+    }
+
+    fn load_palette(&mut self, count: Value) {
+        let mut colors = Vec::with_capacity(count as usize * 4);
+        for i in 0..count as usize * 4 {
+            let address = self.index as usize + i;
+            colors.push(self.read_memory(address));
+        }
+        if let Some(mega) = self.display.mega_mut() {
+            mega.load_palette(&colors);
+        }
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn set_sprite_width(&mut self, width: Value) {
+        if let Some(mega) = self.display.mega_mut() {
+            mega.set_sprite_width(width);
+        }
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn set_sprite_height(&mut self, height: Value) {
+        if let Some(mega) = self.display.mega_mut() {
+            mega.set_sprite_height(height);
+        }
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn set_alpha(&mut self, alpha: Value) {
+        if let Some(mega) = self.display.mega_mut() {
+            mega.set_alpha(alpha);
+        }
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    /// Starts the sample at I, looping it unless `mode` is 1
+    fn play_sample(&mut self, mode: Value) {
+        self.sample = Sample::at(&self.memory, self.index as usize, mode != 1);
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn stop_sample(&mut self) {
+        self.sample = None;
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    /// Unknown blend modes are ignored
+    fn set_blend_mode(&mut self, mode: Value) {
+        if let (Some(blend_mode), Some(mega)) =
+            (BlendMode::from_value(mode), self.display.mega_mut())
+        {
+            mega.set_blend_mode(blend_mode);
+        }
+
+        self.pc += INSTRUCTION_SIZE;
+    }
+
+    fn set_collision_color(&mut self, color: Value) {
+        if let Some(mega) = self.display.mega_mut() {
+            mega.set_collision_color(color);
+        }
 
         self.pc += INSTRUCTION_SIZE;
     }
@@ -450,6 +1217,12 @@ impl Cpu {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use display::HIRES_HEIGHT;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+    use testing::temp_path;
+    use trace::{TraceFilter, TraceFormat};
 
     fn get_cpu() -> Cpu {
         return Cpu::new(Vec::new(), 360, false);
@@ -463,17 +1236,41 @@ mod tests {
         assert_eq!(0x1337, cpu.pc);
     }
 
+    #[test]
+    fn decode_cache_sees_self_modifying_code() {
+        // LD V2, 0; LD V0, 0x62; LD V1, 7; LD I, 0x200; LD [I], V1; JP 0x200
+        let rom = vec![
+            0x62, 0x00, 0x60, 0x62, 0x61, 0x07, 0xA2, 0x00, 0xF1, 0x55, 0x12, 0x00,
+        ];
+        let mut cpu = Cpu::new(rom, 360, false);
+        for _ in 0..7 {
+            cpu.cycle();
+        }
+        assert_eq!(0x07, cpu.registers[2]);
+    }
+
+    #[test]
+    fn jump_offset() {
+        let mut cpu = get_cpu();
+        cpu.registers[0x0] = 0x04;
+        cpu.jump_offset(0x300);
+        assert_eq!(0x304, cpu.pc);
+        cpu.registers[0x0] = 0xFF;
+        cpu.jump_offset(0xFFF);
+        assert_eq!(0x0FE, cpu.pc);
+    }
+
     #[test]
     fn call_and_return() {
         let mut cpu = get_cpu();
         cpu.pc = 0x1337;
         cpu.call(0x6666);
         assert_eq!(1, cpu.sp);
-        assert_eq!(0x1337, cpu.stack[cpu.sp as usize]);
+        assert_eq!(0x1339, cpu.stack[cpu.sp as usize]);
         assert_eq!(0x6666, cpu.pc);
         cpu.ret();
         assert_eq!(0, cpu.sp);
-        assert_eq!(0x1337, cpu.pc);
+        assert_eq!(0x1339, cpu.pc);
     }
 
     #[test]
@@ -493,7 +1290,7 @@ mod tests {
         cpu.skip_equal(0x0, 0x42);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 2), cpu.pc);
     }
-    
+
     #[test]
     fn skip_equal_value_not_equals_reg() {
         let mut cpu = get_cpu();
@@ -511,7 +1308,7 @@ mod tests {
         cpu.skip_not_equal(0x0, 0x42);
         assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
     }
-    
+
     #[test]
     fn skip_not_equal_value_not_equals_reg() {
         let mut cpu = get_cpu();
@@ -541,6 +1338,26 @@ mod tests {
         assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
     }
 
+    #[test]
+    fn skip_reg_not_equal_registers_equal() {
+        let mut cpu = get_cpu();
+        let initial_pc = cpu.pc;
+        cpu.registers[0] = 0x42;
+        cpu.registers[1] = 0x42;
+        cpu.skip_reg_not_equal(0x0, 0x1);
+        assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
+    }
+
+    #[test]
+    fn skip_reg_not_equal_registers_not_equal() {
+        let mut cpu = get_cpu();
+        let initial_pc = cpu.pc;
+        cpu.registers[0] = 0x42;
+        cpu.registers[1] = 0x00;
+        cpu.skip_reg_not_equal(0x0, 0x1);
+        assert_eq!(initial_pc + (INSTRUCTION_SIZE * 2), cpu.pc);
+    }
+
     #[test]
     fn add_val() {
         let mut cpu = get_cpu();
@@ -557,7 +1374,7 @@ mod tests {
         let initial_pc = cpu.pc;
         cpu.registers[0] = 0x00;
         cpu.registers[1] = 0x42;
-        cpu.load_reg(0x0, 0x1);
+        cpu.alu(Operation::Load, 0x0, 0x1);
         assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
         assert_eq!(0x42, cpu.registers[0]);
     }
@@ -569,7 +1386,7 @@ mod tests {
 
         cpu.registers[0] = 0x00;
         cpu.registers[1] = 0x00;
-        
+
         cpu.registers[2] = 0x00;
         cpu.registers[3] = 0x01;
 
@@ -579,19 +1396,19 @@ mod tests {
         cpu.registers[6] = 0x01;
         cpu.registers[7] = 0x01;
 
-        cpu.or(0x0, 0x1);
+        cpu.alu(Operation::Or, 0x0, 0x1);
         assert_eq!(0x00, cpu.registers[0]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 1), cpu.pc);
 
-        cpu.or(0x2, 0x3);
+        cpu.alu(Operation::Or, 0x2, 0x3);
         assert_eq!(0x01, cpu.registers[2]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 2), cpu.pc);
 
-        cpu.or(0x4, 0x5);
+        cpu.alu(Operation::Or, 0x4, 0x5);
         assert_eq!(0x01, cpu.registers[4]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 3), cpu.pc);
 
-        cpu.or(0x6, 0x7);
+        cpu.alu(Operation::Or, 0x6, 0x7);
         assert_eq!(0x01, cpu.registers[6]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 4), cpu.pc);
     }
@@ -603,7 +1420,7 @@ mod tests {
 
         cpu.registers[0] = 0x00;
         cpu.registers[1] = 0x00;
-        
+
         cpu.registers[2] = 0x00;
         cpu.registers[3] = 0x01;
 
@@ -613,19 +1430,19 @@ mod tests {
         cpu.registers[6] = 0x01;
         cpu.registers[7] = 0x01;
 
-        cpu.and(0x0, 0x1);
+        cpu.alu(Operation::And, 0x0, 0x1);
         assert_eq!(0x00, cpu.registers[0]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 1), cpu.pc);
 
-        cpu.and(0x2, 0x3);
+        cpu.alu(Operation::And, 0x2, 0x3);
         assert_eq!(0x00, cpu.registers[2]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 2), cpu.pc);
 
-        cpu.and(0x4, 0x5);
+        cpu.alu(Operation::And, 0x4, 0x5);
         assert_eq!(0x00, cpu.registers[4]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 3), cpu.pc);
 
-        cpu.and(0x6, 0x7);
+        cpu.alu(Operation::And, 0x6, 0x7);
         assert_eq!(0x01, cpu.registers[6]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 4), cpu.pc);
     }
@@ -637,7 +1454,7 @@ mod tests {
 
         cpu.registers[0] = 0x00;
         cpu.registers[1] = 0x00;
-        
+
         cpu.registers[2] = 0x00;
         cpu.registers[3] = 0x01;
 
@@ -647,19 +1464,19 @@ mod tests {
         cpu.registers[6] = 0x01;
         cpu.registers[7] = 0x01;
 
-        cpu.xor(0x0, 0x1);
+        cpu.alu(Operation::Xor, 0x0, 0x1);
         assert_eq!(0x00, cpu.registers[0]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 1), cpu.pc);
 
-        cpu.xor(0x2, 0x3);
+        cpu.alu(Operation::Xor, 0x2, 0x3);
         assert_eq!(0x01, cpu.registers[2]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 2), cpu.pc);
 
-        cpu.xor(0x4, 0x5);
+        cpu.alu(Operation::Xor, 0x4, 0x5);
         assert_eq!(0x01, cpu.registers[4]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 3), cpu.pc);
 
-        cpu.xor(0x6, 0x7);
+        cpu.alu(Operation::Xor, 0x6, 0x7);
         assert_eq!(0x00, cpu.registers[6]);
         assert_eq!(initial_pc + (INSTRUCTION_SIZE * 4), cpu.pc);
     }
@@ -670,7 +1487,7 @@ mod tests {
         let initial_pc = cpu.pc;
         cpu.registers[0] = 0x42;
         cpu.registers[1] = 0x01;
-        cpu.add_reg(0x0, 0x1);
+        cpu.alu(Operation::Add, 0x0, 0x1);
         assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
         assert_eq!(0x43, cpu.registers[0]);
     }
@@ -681,17 +1498,44 @@ mod tests {
         let initial_pc = cpu.pc;
         cpu.registers[0] = 0x42;
         cpu.registers[1] = 0x01;
-        cpu.sub_reg(0x0, 0x1);
+        cpu.alu(Operation::Sub, 0x0, 0x1);
         assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
         assert_eq!(0x41, cpu.registers[0]);
     }
 
+    #[test]
+    fn sub_reg_wraps() {
+        let mut cpu = get_cpu();
+        cpu.registers[0] = 0x00;
+        cpu.registers[1] = 0x80;
+        cpu.alu(Operation::Sub, 0x0, 0x1);
+        assert_eq!(0x80, cpu.registers[0]);
+        assert_eq!(0x00, cpu.registers[0xF]);
+    }
+
+    #[test]
+    fn flag_is_written_after_the_result() {
+        let mut cpu = get_cpu();
+        cpu.registers[0xF] = 0xFF;
+        cpu.registers[1] = 0x01;
+        cpu.alu(Operation::Add, 0xF, 0x1);
+        assert_eq!(0x01, cpu.registers[0xF]);
+
+        cpu.registers[0xF] = 0x10;
+        cpu.alu(Operation::Sub, 0xF, 0x1);
+        assert_eq!(0x01, cpu.registers[0xF]);
+
+        cpu.registers[0xF] = 0x02;
+        cpu.alu(Operation::ShiftRight, 0xF, 0xF);
+        assert_eq!(0x00, cpu.registers[0xF]);
+    }
+
     #[test]
     fn shift_right() {
         let mut cpu = get_cpu();
         let initial_pc = cpu.pc;
         cpu.registers[0] = 0b10110101;
-        cpu.shift_right(0x0);
+        cpu.alu(Operation::ShiftRight, 0x0, 0x0);
         assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
         assert_eq!(0b01011010, cpu.registers[0]);
     }
@@ -701,9 +1545,48 @@ mod tests {
         let mut cpu = get_cpu();
         let initial_pc = cpu.pc;
         cpu.registers[0] = 0b10110101;
-        cpu.shift_left(0x0);
+        cpu.alu(Operation::ShiftLeft, 0x0, 0x0);
         assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
         assert_eq!(0b01101010, cpu.registers[0]);
+        assert_eq!(0x01, cpu.registers[0xF]);
+    }
+
+    #[test]
+    fn sub_reg_reverse() {
+        let mut cpu = get_cpu();
+        let initial_pc = cpu.pc;
+        cpu.registers[0] = 0x01;
+        cpu.registers[1] = 0x42;
+        cpu.alu(Operation::SubReverse, 0x0, 0x1);
+        assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
+        assert_eq!(0x41, cpu.registers[0]);
+        assert_eq!(0x01, cpu.registers[0xF]);
+    }
+
+    #[test]
+    fn shift_right_uses_vy_quirk() {
+        let mut cpu = get_cpu();
+        cpu.set_quirks(Quirks {
+            shift_uses_vy: true,
+            ..Quirks::default()
+        });
+        cpu.registers[0] = 0x00;
+        cpu.registers[1] = 0b10110101;
+        cpu.alu(Operation::ShiftRight, 0x0, 0x1);
+        assert_eq!(0b01011010, cpu.registers[0]);
+        assert_eq!(0b10110101, cpu.registers[1]);
+    }
+
+    #[test]
+    fn or_resets_vf_quirk() {
+        let mut cpu = get_cpu();
+        cpu.set_quirks(Quirks {
+            logic_resets_vf: true,
+            ..Quirks::default()
+        });
+        cpu.registers[0xF] = 0x01;
+        cpu.alu(Operation::Or, 0x0, 0x1);
+        assert_eq!(0x00, cpu.registers[0xF]);
     }
 
     #[test]
@@ -726,6 +1609,32 @@ mod tests {
         // not asserting value difference due to lack of determinism
     }
 
+    #[test]
+    fn load_and_set_timers() {
+        let mut cpu = get_cpu();
+        let initial_pc = cpu.pc;
+        cpu.registers[0] = 0x42;
+        cpu.set_delay(0x0);
+        cpu.set_sound(0x0);
+        cpu.load_delay(0x1);
+        assert_eq!(initial_pc + (INSTRUCTION_SIZE * 3), cpu.pc);
+        assert_eq!(0x42, cpu.sound_timer);
+        assert_eq!(0x42, cpu.registers[1]);
+    }
+
+    #[test]
+    fn wait_key_repeats_until_pressed() {
+        let mut cpu = get_cpu();
+        let initial_pc = cpu.pc;
+        cpu.wait_key(0x0);
+        assert_eq!(initial_pc, cpu.pc);
+        cpu.keys[0xA] = true;
+        cpu.keys[0xC] = true;
+        cpu.wait_key(0x0);
+        assert_eq!(initial_pc + INSTRUCTION_SIZE, cpu.pc);
+        assert_eq!(0xA, cpu.registers[0]);
+    }
+
     #[test]
     fn skip_key_pressed() {
         let mut cpu = get_cpu();
@@ -813,6 +1722,18 @@ mod tests {
         assert_eq!(0x33, cpu.memory[(cpu.index + 2) as usize])
     }
 
+    #[test]
+    fn store_index_increments_index_quirk() {
+        let mut cpu = get_cpu();
+        cpu.set_quirks(Quirks {
+            load_store_increments_index: true,
+            ..Quirks::default()
+        });
+        cpu.index = 0x300;
+        cpu.store_index(0x2);
+        assert_eq!(0x303, cpu.index);
+    }
+
     #[test]
     fn read_index() {
         let mut cpu = get_cpu();
@@ -855,4 +1776,270 @@ mod tests {
         assert_eq!(false, screen[0][6]);
         assert_eq!(false, screen[0][7]);
     }
+
+    #[test]
+    fn draw_wraps_without_clip_quirk() {
+        let mut cpu = get_cpu();
+        cpu.set_quirks(Quirks {
+            clip_sprites: false,
+            ..Quirks::default()
+        });
+        cpu.display.draw_sprite(&[0b11000000], 63, 31);
+        let screen = cpu.display.get_screen();
+        assert!(screen[31][63]);
+        assert!(screen[31][0]);
+    }
+
+    #[test]
+    fn vip_timing_charges_machine_cycles() {
+        // LD V0, 0; LD F, V0; ADD V1, 1; DRW V0, V0, 5; JP 0x204
+        let rom = vec![0x60, 0x00, 0xF0, 0x29, 0x71, 0x01, 0xD0, 0x05, 0x12, 0x04];
+        let mut cpu = Cpu::new(rom, 360, false);
+        cpu.set_timing(Timing::Vip);
+
+        cpu.cycle();
+        assert_eq!(6, cpu.frame_cycles);
+        assert_eq!(0, cpu.frame());
+
+        // Every draw waits for the next frame, so the loop runs once a frame
+        for _ in 0..1 + 3 * 10 {
+            cpu.cycle();
+        }
+        assert_eq!(10, cpu.frame());
+        assert_eq!(10, cpu.registers[1]);
+    }
+
+    #[test]
+    fn chip8x_colors_second_keypad_and_port() {
+        // BGC; LD V0, 0x10; LD V1, 0; LD V2, 4; COL V0, V2; SKP2 V2; CLS; OUT V2
+        let rom = vec![
+            0x02, 0xA0, 0x60, 0x10, 0x61, 0x00, 0x62, 0x04, 0xB0, 0x20, 0xE2, 0xF2, 0x00, 0xE0,
+            0xF2, 0xF8,
+        ];
+        assert!(Cpu::new(rom.clone(), 360, false).display.colors().is_none());
+
+        let mut cpu = Cpu::with_platform(rom, 360, false, Platform::Chip8X);
+        assert_eq!(0x02, cpu.memory[0x300]);
+        cpu.set_second_key(0x4, true);
+        for _ in 0..7 {
+            cpu.cycle();
+        }
+        assert_eq!(0x310, cpu.pc);
+        assert_eq!(4, cpu.port());
+
+        let colors = cpu.display.colors().unwrap();
+        assert_eq!([0x00, 0x00, 0x00], colors.background());
+        assert_eq!([0x00, 0xFF, 0x00], colors.foreground(0, 0));
+        assert_eq!([0x00, 0xFF, 0x00], colors.foreground(15, 3));
+        assert_eq!([0xFF, 0x00, 0x00], colors.foreground(16, 0));
+        assert_eq!([0xFF, 0x00, 0x00], colors.foreground(0, 4));
+    }
+
+    #[test]
+    fn megachip_draws_palette_sprites_on_the_big_screen() {
+        // MEGAON; LDHI I, 0x000300; LDPAL 1; SPRW 2; SPRH 1; LDHI I, 0x000304;
+        // LD V0, 5; LD V1, 3; DRW V0, V1, 0; CLS; MEGAOFF
+        let mut rom = vec![
+            0x00, 0x11, 0x01, 0x00, 0x03, 0x00, 0x02, 0x01, 0x03, 0x02, 0x04, 0x01, 0x01, 0x00,
+            0x03, 0x04, 0x60, 0x05, 0x61, 0x03, 0xD0, 0x10, 0x00, 0xE0, 0x00, 0x10,
+        ];
+        rom.resize(0x100, 0);
+        rom.extend_from_slice(&[0xFF, 0x00, 0xFF, 0x00, 0x01, 0x01]);
+        let mut cpu = Cpu::with_platform(rom, 360, false, Platform::MegaChip);
+
+        for _ in 0..10 {
+            cpu.cycle();
+        }
+        assert_eq!(0x304, cpu.index);
+        assert_eq!(0, cpu.registers[0xF]);
+        let frame = cpu.display.frame();
+        assert_eq!((256, 192), (frame.width, frame.height));
+        assert_eq!(0xFF00_FF00, frame.pixels[3 * 256 + 5]);
+        assert_eq!(0xFF00_FF00, frame.pixels[3 * 256 + 6]);
+        assert_eq!(0xFF00_0000, frame.pixels[3 * 256 + 7]);
+
+        cpu.cycle();
+        assert_eq!(64, cpu.display.frame().width);
+    }
+
+    #[test]
+    fn megachip_runs_code_past_the_first_4_kb() {
+        // ADD V0, 1 from 0x200 up to 0x1004
+        let mut rom = Vec::new();
+        for _ in (PROGRAM_START..0x1004).step_by(2) {
+            rom.extend_from_slice(&[0x70, 0x01]);
+        }
+        let mut cpu = Cpu::with_platform(rom, 360, false, Platform::MegaChip);
+
+        for _ in 0..0x702 {
+            cpu.cycle();
+        }
+        assert!(!cpu.faulted);
+        assert_eq!(0x1004, cpu.pc);
+        assert_eq!(0x02, cpu.registers[0x0]);
+    }
+
+    #[test]
+    fn sys_calls_run_registered_routines() {
+        // SYS 0x300; SYS 0x400
+        let mut cpu = Cpu::new(vec![0x03, 0x00, 0x04, 0x00], 360, false);
+        cpu.register_routine(
+            0x300,
+            Box::new(|cpu: &mut Cpu| {
+                let pc = cpu.pc();
+                cpu.display.draw_sprite(&[0x80], 0, 0);
+                assert_eq!(0x202, pc);
+            }),
+        );
+        cpu.cycle();
+        assert!(cpu.display.get_screen()[0][0]);
+
+        // Calls without a routine are skipped
+        cpu.cycle();
+        assert_eq!(0x204, cpu.pc);
+        assert!(cpu.remove_routine(0x300).is_some());
+    }
+
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl Observer for Recorder {
+        fn frame(&mut self, frame: u32) {
+            self.0.borrow_mut().push(format!("frame {}", frame));
+        }
+
+        fn draw(&mut self, x: usize, y: usize, collided: bool) {
+            self.0.borrow_mut().push(format!("draw {} {} {}", x, y, collided));
+        }
+
+        fn clear(&mut self) {
+            self.0.borrow_mut().push(String::from("clear"));
+        }
+
+        fn sound_started(&mut self) {
+            self.0.borrow_mut().push(String::from("sound started"));
+        }
+
+        fn sound_stopped(&mut self) {
+            self.0.borrow_mut().push(String::from("sound stopped"));
+        }
+
+        fn key_wait_started(&mut self, register: Register) {
+            self.0.borrow_mut().push(format!("wait V{:X}", register));
+        }
+
+        fn key_wait_ended(&mut self, register: Register, key: u8) {
+            self.0.borrow_mut().push(format!("key {:X} in V{:X}", key, register));
+        }
+
+        fn fault(&mut self, pc: Address, opcode: u16) {
+            self.0.borrow_mut().push(format!("fault {:04X} at {:03X}", opcode, pc));
+        }
+
+        fn memory_written(&mut self, address: usize, value: u8) {
+            self.0.borrow_mut().push(format!("write {:02X} to {:03X}", value, address));
+        }
+    }
+
+    #[test]
+    fn observers_are_told_about_events() {
+        // LD V0, 1; LD ST, V0; CLS; DRW V0, V0, 5; LD I, 0x300; LD [I], V0;
+        // LD V1, K; then an invalid opcode
+        let rom = vec![
+            0x60, 0x01, 0xF0, 0x18, 0x00, 0xE0, 0xD0, 0x05, 0xA3, 0x00, 0xF0, 0x55, 0xF1, 0x0A,
+            0xFF, 0xFF,
+        ];
+        let mut cpu = Cpu::new(rom, 360, false);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        cpu.add_observer(Box::new(Recorder(events.clone())));
+
+        for _ in 0..8 {
+            cpu.cycle();
+        }
+        cpu.set_key(0x5, true);
+        cpu.cycle();
+        cpu.cycle();
+        assert!(cpu.faulted);
+
+        let expected = vec![
+            "sound started",
+            "clear",
+            "draw 1 1 false",
+            "write 01 to 300",
+            "frame 1",
+            "wait V1",
+            "sound stopped",
+            "key 5 in V1",
+            "fault FFFF at 20E",
+        ];
+        assert_eq!(expected, *events.borrow());
+        assert_eq!(1, cpu.take_observers().len());
+    }
+
+    #[test]
+    fn hires_programs_draw_on_64_rows() {
+        // JP 0x260; then at 0x2C0: LD V0, 40; LD V1, 0; LD F, V1;
+        // DRW V0, V0, 5; SYS 0x230
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(0xC0, 0);
+        rom.extend_from_slice(&[0x60, 0x28, 0x61, 0x00, 0xF1, 0x29, 0xD0, 0x05, 0x02, 0x30]);
+        let mut cpu = Cpu::with_platform(rom, 360, false, Platform::HiresChip8);
+
+        for _ in 0..5 {
+            cpu.cycle();
+        }
+        assert_eq!(0x2C8, cpu.pc);
+        let screen = cpu.display.get_screen();
+        assert_eq!(HIRES_HEIGHT, screen.len());
+        assert!(screen[40][40]);
+
+        cpu.cycle();
+        assert!(cpu.display.get_screen().iter().all(|row| !row.contains(&true)));
+    }
+
+    #[test]
+    fn pokes_change_state_and_running_code() {
+        // CALL 0x204; JP 0x202; LD V0, 1; RET
+        let rom = vec![0x22, 0x04, 0x12, 0x02, 0x60, 0x01, 0x00, 0xEE];
+        let mut cpu = Cpu::new(rom, 360, false);
+        cpu.set_decode_cache(true);
+        cpu.cycle();
+        assert_eq!(&[0x202], cpu.stack());
+        cpu.cycle();
+        assert_eq!(1, cpu.registers()[0x0]);
+
+        // LD V0, 7 replaces the cached LD V0, 1
+        assert!(cpu.poke(0x205, &[0x07]));
+        assert!(cpu.code_modified(0x204, 0x206));
+        assert!(!cpu.poke(cpu.memory().len() - 1, &[0x00, 0x00]));
+        cpu.poke_pc(0x204);
+        cpu.cycle();
+        assert_eq!(7, cpu.registers()[0x0]);
+        assert_eq!(0x07, cpu.memory()[0x205]);
+
+        cpu.poke_register(0x3, 0x42);
+        cpu.poke_index(0x300);
+        cpu.poke_delay_timer(10);
+        cpu.poke_sound_timer(20);
+        assert_eq!(0x42, cpu.state().registers[0x3]);
+        assert_eq!(0x300, cpu.index());
+        assert_eq!((10, 20), (cpu.delay_timer(), cpu.sound_timer()));
+    }
+
+    #[test]
+    fn cycle_traces_register_changes() {
+        let path = temp_path("cpu-trace.txt");
+        let tracer = Tracer::create(&path, TraceFormat::Text, TraceFilter::default()).unwrap();
+        let mut cpu = Cpu::new(vec![0x6A, 0x02, 0xA3, 0x00], 360, false);
+        cpu.set_tracer(tracer);
+        cpu.cycle();
+        cpu.cycle();
+        cpu.take_tracer().unwrap().finish().unwrap();
+
+        let trace = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = trace.lines().collect();
+        assert_eq!(2, lines.len());
+        assert!(lines[0].ends_with("LD VA, 2             VA:00->02"));
+        assert!(lines[1].ends_with("LD I, 0x300          I:000->300"));
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,98 @@
+use instruction::{Address, Instruction};
+use platform::Platform;
+
+/// The program counter only reaches the first 64 KB of MEGA-CHIP memory
+const MAX_ENTRIES: usize = 0x10000;
+
+/// Instructions decoded a basic block at a time the first time execution
+/// reaches them, so that loops are fetched and decoded only once. Entries
+/// are dropped when the memory they were decoded from is written.
+pub struct DecodeCache {
+    /// The opcode and instruction starting at every address
+    entries: Vec<Option<(u16, Instruction)>>,
+    platform: Platform,
+}
+
+impl DecodeCache {
+    pub fn new(platform: Platform) -> DecodeCache {
+        DecodeCache {
+            entries: vec![None; platform.memory_size().min(MAX_ENTRIES)],
+            platform,
+        }
+    }
+
+    /// The opcode and instruction at `pc`, decoding the rest of its block
+    /// along with it. None if the instruction runs past the end of memory.
+    pub fn get(&mut self, memory: &[u8], pc: Address) -> Option<(u16, Instruction)> {
+        let pc = pc as usize;
+        if let Some(&Some(entry)) = self.entries.get(pc) {
+            return Some(entry);
+        }
+
+        let mut address = pc;
+        while address + 1 < memory.len()
+            && address < self.entries.len()
+            && self.entries[address].is_none()
+        {
+            let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+            let instruction = Instruction::parse_for(opcode, self.platform);
+            self.entries[address] = Some((opcode, instruction));
+            if instruction.is_branch() {
+                break;
+            }
+            address += 2;
+        }
+        self.entries.get(pc).cloned().unwrap_or(None)
+    }
+
+    /// Drops the instructions that include the byte at `address`
+    pub fn invalidate(&mut self, address: usize) {
+        // MEGA-CHIP memory goes on past the addresses that can be cached
+        if let Some(entries) = self.entries.get_mut(address.saturating_sub(1)..) {
+            for entry in entries.iter_mut().take(2) {
+                *entry = None;
+            }
+        }
+    }
+}
+
+impl Default for DecodeCache {
+    fn default() -> DecodeCache {
+        DecodeCache::new(Platform::Chip8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::MEMORY_SIZE;
+
+    #[test]
+    fn blocks_are_decoded_until_a_branch_and_invalidated_by_writes() {
+        let mut memory = [0; MEMORY_SIZE];
+        // LD V0, 1; ADD V0, 2; JP 0x200; LD V1, 3
+        memory[0x200..0x208].copy_from_slice(&[0x60, 0x01, 0x70, 0x02, 0x12, 0x00, 0x61, 0x03]);
+        let mut cache = DecodeCache::default();
+
+        assert_eq!(
+            Some((0x6001, Instruction::LoadVal(0, 1))),
+            cache.get(&memory, 0x200)
+        );
+        assert!(cache.entries[0x204].is_some());
+        assert!(cache.entries[0x206].is_none());
+
+        // Cached entries are returned even when memory changed behind its back
+        memory[0x203] = 0x05;
+        assert_eq!(
+            Some((0x7002, Instruction::AddVal(0, 2))),
+            cache.get(&memory, 0x202)
+        );
+        cache.invalidate(0x203);
+        assert_eq!(
+            Some((0x7005, Instruction::AddVal(0, 5))),
+            cache.get(&memory, 0x202)
+        );
+
+        assert_eq!(None, cache.get(&memory, (MEMORY_SIZE - 1) as Address));
+    }
+}
@@ -1,25 +1,208 @@
+use megachip::{MegaScreen, BLACK, MEGA_HEIGHT, MEGA_WIDTH};
+
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
+/// The two-page display of the hi-res CHIP-8 has twice as many rows
+pub const HIRES_HEIGHT: usize = 64;
+
+pub type Row = [bool; WIDTH];
+/// The rows of the screen, 32 or 64 of them
+pub type Screen = [Row];
+
+const WHITE: u32 = 0xFFFF_FFFF;
+
+/// The CHIP-8X colors foreground pixels in zones of 8x4 pixels
+pub const ZONE_WIDTH: usize = 8;
+pub const ZONE_HEIGHT: usize = 4;
+const ZONE_COLUMNS: usize = WIDTH / ZONE_WIDTH;
+const ZONE_ROWS: usize = HEIGHT / ZONE_HEIGHT;
 
-pub type Screen = [[bool; WIDTH]; HEIGHT];
+/// The CHIP-8X background colors in the order 02A0 steps through them:
+/// blue, black, green and red
+const BACKGROUND_COLORS: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x80],
+    [0x00, 0x00, 0x00],
+    [0x00, 0x80, 0x00],
+    [0x80, 0x00, 0x00],
+];
+/// The CHIP-8X foreground colors, where bit 0 is red, bit 1 blue and bit 2
+/// green
+const FOREGROUND_COLORS: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00],
+    [0xFF, 0x00, 0x00],
+    [0x00, 0x00, 0xFF],
+    [0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0x00],
+    [0xFF, 0xFF, 0x00],
+    [0x00, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0xFF],
+];
+/// Zones are red until a program colors them
+const DEFAULT_FOREGROUND: u8 = 1;
 
 pub struct Display {
-    screen: Screen,
+    screen: Vec<Row>,
+    colors: Option<Colors>,
+    mega: Option<MegaScreen>,
+    mega_mode: bool,
+    clipping: bool,
     debug_mode: bool,
 }
 
+/// A picture of the screen in whatever mode it is in, one ARGB color per
+/// pixel row by row
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+/// The state of the CHIP-8X color board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Colors {
+    background: usize,
+    zones: [[u8; ZONE_COLUMNS]; ZONE_ROWS],
+}
+
+impl Colors {
+    pub fn new() -> Colors {
+        Colors {
+            background: 0,
+            zones: [[DEFAULT_FOREGROUND; ZONE_COLUMNS]; ZONE_ROWS],
+        }
+    }
+
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLORS.len();
+    }
+
+    /// Colors the zones from `first` to `last` column and row, inclusive,
+    /// with one of the eight foreground colors
+    pub fn set_zones(&mut self, first: (usize, usize), last: (usize, usize), color: u8) {
+        for row in self.zones[first.1.min(ZONE_ROWS)..(last.1 + 1).min(ZONE_ROWS)].iter_mut() {
+            for zone in row[first.0.min(ZONE_COLUMNS)..(last.0 + 1).min(ZONE_COLUMNS)].iter_mut() {
+                *zone = color & 0x07;
+            }
+        }
+    }
+
+    pub fn background(&self) -> [u8; 3] {
+        BACKGROUND_COLORS[self.background]
+    }
+
+    /// The color of a lit pixel
+    pub fn foreground(&self, x: usize, y: usize) -> [u8; 3] {
+        let color = self.zones[(y / ZONE_HEIGHT) % ZONE_ROWS][(x / ZONE_WIDTH) % ZONE_COLUMNS];
+        FOREGROUND_COLORS[color as usize]
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors::new()
+    }
+}
+
 impl Display {
     pub fn new(debug_mode: bool) -> Display {
         Display {
-            screen: [[false; WIDTH]; HEIGHT],
+            screen: vec![[false; WIDTH]; HEIGHT],
+            colors: None,
+            mega: None,
+            mega_mode: false,
+            clipping: true,
             debug_mode: debug_mode,
         }
     }
 
+    /// Sets whether sprites are cut off at the edges of the screen or wrap around
+    pub fn set_clipping(&mut self, clipping: bool) {
+        self.clipping = clipping;
+    }
+
     pub fn get_screen(&mut self) -> &Screen {
         &self.screen
     }
 
+    /// Switches between 32 and 64 rows, clearing the screen
+    pub fn set_hires(&mut self, hires: bool) {
+        let height = if hires { HIRES_HEIGHT } else { HEIGHT };
+        self.screen = vec![[false; WIDTH]; height];
+    }
+
+    /// Rows of the screen in the mode it is in
+    pub fn height(&self) -> usize {
+        if self.mega_mode {
+            MEGA_HEIGHT
+        } else {
+            self.screen.len()
+        }
+    }
+
+    /// Adds the CHIP-8X color board, without it pixels are white on black
+    pub fn enable_colors(&mut self) {
+        self.colors = Some(Colors::new());
+    }
+
+    pub fn colors(&self) -> Option<&Colors> {
+        self.colors.as_ref()
+    }
+
+    pub fn colors_mut(&mut self) -> Option<&mut Colors> {
+        self.colors.as_mut()
+    }
+
+    /// Adds the MEGA-CHIP screen, which is shown while mega mode is on
+    pub fn enable_mega(&mut self) {
+        self.mega = Some(MegaScreen::new());
+    }
+
+    pub fn mega(&self) -> Option<&MegaScreen> {
+        self.mega.as_ref()
+    }
+
+    pub fn mega_mut(&mut self) -> Option<&mut MegaScreen> {
+        self.mega.as_mut()
+    }
+
+    pub fn set_mega_mode(&mut self, on: bool) {
+        self.mega_mode = on && self.mega.is_some();
+    }
+
+    pub fn mega_mode(&self) -> bool {
+        self.mega_mode
+    }
+
+    /// The picture to show: the MEGA-CHIP screen in mega mode, and otherwise
+    /// the CHIP-8 screen in white on black or in the CHIP-8X colors
+    pub fn frame(&self) -> Frame {
+        if let (true, Some(mega)) = (self.mega_mode, self.mega.as_ref()) {
+            return Frame {
+                width: MEGA_WIDTH,
+                height: MEGA_HEIGHT,
+                pixels: mega.pixels().to_vec(),
+            };
+        }
+
+        let mut pixels = Vec::with_capacity(WIDTH * self.screen.len());
+        for (y, row) in self.screen.iter().enumerate() {
+            for (x, &lit) in row.iter().enumerate() {
+                pixels.push(match (lit, self.colors.as_ref()) {
+                    (true, Some(colors)) => argb(colors.foreground(x, y)),
+                    (false, Some(colors)) => argb(colors.background()),
+                    (true, None) => WHITE,
+                    (false, None) => BLACK,
+                });
+            }
+        }
+        Frame {
+            width: WIDTH,
+            height: self.screen.len(),
+            pixels,
+        }
+    }
+
     // TODO: Please find a better way to do this...it hurts.
     pub fn draw_sprite(&mut self, sprite: &[u8], x: usize, y: usize) -> bool {
         let mut flipped = false;
@@ -33,20 +216,23 @@ impl Display {
             for j in 0..8 {
                 let pixel = (row >> j) & 0b1;
 
-                let y_pixel = y + i;
-                let x_pixel = x + (7 - j);
+                let mut y_pixel = y + i;
+                let mut x_pixel = x + (7 - j);
 
-                // TODO: Wrap this instead of refusing to draw the pixel
                 if self.coords_out_of_bounds(x_pixel, y_pixel) {
-                    continue;
+                    if self.clipping {
+                        continue;
+                    }
+                    x_pixel %= WIDTH;
+                    y_pixel %= self.screen.len();
                 }
 
-                let current_val = self.screen[y + i][x + (7 - j)];
+                let current_val = self.screen[y_pixel][x_pixel];
                 let pixel_val = pixel != 0;
 
                 let new_val = current_val ^ pixel_val;
 
-                self.screen[y + i][x + (7 - j)] = new_val;
+                self.screen[y_pixel][x_pixel] = new_val;
 
                 flipped = flipped || (current_val && !new_val);
             }
@@ -55,15 +241,28 @@ impl Display {
         flipped
     }
 
+    /// Clears the screen, or in mega mode shows what was drawn before
+    /// clearing it
     pub fn clear(&mut self) {
-        self.screen = [[false; WIDTH]; HEIGHT];
+        match self.mega {
+            Some(ref mut mega) if self.mega_mode => mega.show_and_clear(),
+            _ => {
+                for row in self.screen.iter_mut() {
+                    *row = [false; WIDTH];
+                }
+            }
+        }
     }
 
     fn coords_out_of_bounds(&self, x: usize, y: usize) -> bool {
-        return y >= HEIGHT || x >= WIDTH;
+        return y >= self.screen.len() || x >= WIDTH;
     }
 }
 
+fn argb(color: [u8; 3]) -> u32 {
+    BLACK | u32::from(color[0]) << 16 | u32::from(color[1]) << 8 | u32::from(color[2])
+}
+
 pub const SPRITES: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, /* 0 */
     0x20, 0x60, 0x20, 0x20, 0x70, /* 1 */
@@ -0,0 +1,309 @@
+use chip8::cpu::Cpu;
+use keyboard::Keyboard;
+use piston_window::{Button, Key};
+
+/// Bytes per row and rows of memory shown, which fill the debug panel
+const COLUMNS: usize = 8;
+const ROWS: usize = 16;
+/// Bytes PageUp and PageDown move the cursor by
+const PAGE_SIZE: usize = COLUMNS * ROWS;
+
+/// Edits memory of the running program a byte at a time, so the effect of a
+/// change shows up right away in the game
+pub struct HexEditor {
+    cursor: usize,
+    /// The high nibble typed for the byte under the cursor
+    pending: Option<u8>,
+    /// The search being typed, None when not searching
+    query: Option<String>,
+    matches: Vec<usize>,
+    /// The result of the last search
+    message: String,
+}
+
+impl HexEditor {
+    pub fn new() -> HexEditor {
+        HexEditor {
+            cursor: 0,
+            pending: None,
+            query: None,
+            matches: Vec::new(),
+            message: String::new(),
+        }
+    }
+
+    /// Handles a key press, writing to the CPU's memory when a byte was typed
+    pub fn handle_button(&mut self, button: Button, cpu: &mut Cpu) {
+        let key = match button {
+            Button::Keyboard(key) => key,
+            _ => return,
+        };
+        if self.query.is_some() {
+            self.handle_search_key(key, cpu.memory());
+            return;
+        }
+
+        let last = cpu.memory().len() - 1;
+        let previous = self.cursor;
+        match key {
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(last),
+            Key::Up => self.cursor = self.cursor.saturating_sub(COLUMNS),
+            Key::Down => self.cursor = (self.cursor + COLUMNS).min(last),
+            Key::PageUp => self.cursor = self.cursor.saturating_sub(PAGE_SIZE),
+            Key::PageDown => self.cursor = (self.cursor + PAGE_SIZE).min(last),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = last,
+            Key::I => self.cursor = (cpu.index() as usize).min(last),
+            Key::P => self.cursor = cpu.pc() as usize,
+            Key::N => self.next_match(),
+            Key::Backspace => self.pending = None,
+            Key::Slash => self.query = Some(String::new()),
+            _ => {
+                if let Some(digit) = hex_digit(key) {
+                    self.type_digit(digit, cpu);
+                }
+            }
+        }
+        if self.cursor != previous {
+            self.pending = None;
+        }
+    }
+
+    /// The text of the panel, one string per line
+    pub fn lines(&self, memory: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        let value = memory.get(self.cursor).cloned().unwrap_or(0);
+        lines.push(format!(
+            "MEMORY {:04X}  {:02X} {:3}",
+            self.cursor, value, value
+        ));
+
+        // Rows only show the last four digits of their address, the full
+        // address of the cursor is at the top
+        let first = ((self.cursor / COLUMNS).saturating_sub(ROWS / 2) * COLUMNS)
+            .min(memory.len().saturating_sub(PAGE_SIZE));
+        for row in 0..ROWS {
+            let start = first + row * COLUMNS;
+            let mut line = format!("{:04X}", start & 0xFFFF);
+            for (address, byte) in memory.iter().enumerate().skip(start).take(COLUMNS) {
+                line.push(if address == self.cursor { '>' } else { ' ' });
+                match self.pending {
+                    Some(high) if address == self.cursor => line.push_str(&format!("{:X}-", high)),
+                    _ => line.push_str(&format!("{:02X}", byte)),
+                }
+            }
+            lines.push(line);
+        }
+
+        lines.push(String::new());
+        match self.query {
+            Some(ref query) => lines.push(format!("FIND {}_", query)),
+            None => lines.push(self.message.clone()),
+        }
+        lines.push(String::from("/ FIND  N NEXT  I P GO TO"));
+        lines
+    }
+
+    /// Sets the high nibble of the byte under the cursor, or with the low
+    /// nibble writes the byte and moves on to the next one
+    fn type_digit(&mut self, digit: u8, cpu: &mut Cpu) {
+        match self.pending.take() {
+            None => self.pending = Some(digit),
+            Some(high) => {
+                cpu.poke(self.cursor, &[high << 4 | digit]);
+                self.cursor = (self.cursor + 1).min(cpu.memory().len() - 1);
+            }
+        }
+    }
+
+    fn handle_search_key(&mut self, key: Key, memory: &[u8]) {
+        let query = match self.query {
+            Some(ref mut query) => query,
+            None => return,
+        };
+        match key {
+            Key::Backspace => {
+                if query.pop().is_none() {
+                    self.query = None;
+                }
+            }
+            Key::Space => query.push(' '),
+            Key::Period => query.push('.'),
+            Key::Equals => query.push('='),
+            Key::Return => {
+                let query = self.query.take().unwrap_or_default();
+                self.search(&query, memory);
+            }
+            _ => {
+                if let Some(digit) = hex_digit(key) {
+                    query.push_str(&format!("{:X}", digit));
+                }
+            }
+        }
+    }
+
+    /// Finds every place the pattern occurs and moves to the first one from
+    /// the cursor on
+    fn search(&mut self, query: &str, memory: &[u8]) {
+        self.matches = match parse_pattern(query) {
+            Ok(pattern) => find_pattern(memory, &pattern),
+            Err(e) => {
+                self.matches.clear();
+                self.message = e;
+                return;
+            }
+        };
+        self.message = match self.matches.len() {
+            0 => String::from("NOT FOUND"),
+            1 => String::from("1 MATCH"),
+            count => format!("{} MATCHES", count),
+        };
+        if !self.matches.contains(&self.cursor) {
+            self.next_match();
+        }
+    }
+
+    /// Moves to the next match after the cursor, wrapping around to the first
+    fn next_match(&mut self) {
+        let cursor = self.cursor;
+        let next = self
+            .matches
+            .iter()
+            .find(|&&address| address > cursor)
+            .or_else(|| self.matches.first());
+        if let Some(&address) = next {
+            self.cursor = address;
+            self.pending = None;
+        }
+    }
+}
+
+impl Default for HexEditor {
+    fn default() -> HexEditor {
+        HexEditor::new()
+    }
+}
+
+/// Parses a search, either hex bytes like "A2 1E" where ".." matches any
+/// byte, or a decimal value like "=300", which is searched for as one byte
+/// or as two bytes with the high byte first
+pub fn parse_pattern(query: &str) -> Result<Vec<Option<u8>>, String> {
+    let query: String = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(value) = query.strip_prefix('=') {
+        let value = value
+            .parse::<u16>()
+            .map_err(|_| format!("BAD VALUE {}", value))?;
+        return Ok(if value > 0xFF {
+            vec![Some((value >> 8) as u8), Some(value as u8)]
+        } else {
+            vec![Some(value as u8)]
+        });
+    }
+
+    if query.is_empty() || query.len() % 2 == 1 {
+        return Err(String::from("TYPE WHOLE BYTES"));
+    }
+    (0..query.len())
+        .step_by(2)
+        .map(|start| match &query[start..start + 2] {
+            ".." => Ok(None),
+            byte => u8::from_str_radix(byte, 16)
+                .map(Some)
+                .map_err(|_| format!("BAD BYTE {}", byte)),
+        })
+        .collect()
+}
+
+/// Addresses where the pattern starts, None in the pattern matching any byte
+pub fn find_pattern(memory: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    memory
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|&(_, bytes)| {
+            bytes
+                .iter()
+                .zip(pattern.iter())
+                .all(|(&byte, expected)| expected.is_none() || *expected == Some(byte))
+        })
+        .map(|(address, _)| address)
+        .collect()
+}
+
+/// The value of a hex digit key
+fn hex_digit(key: Key) -> Option<u8> {
+    Keyboard::character(key)
+        .and_then(|character| character.to_digit(16))
+        .map(|digit| digit as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use overlay::PANEL_COLUMNS;
+
+    fn press(editor: &mut HexEditor, cpu: &mut Cpu, keys: &[Key]) {
+        for &key in keys {
+            editor.handle_button(Button::Keyboard(key), cpu);
+        }
+    }
+
+    #[test]
+    fn typed_bytes_are_written_to_memory() {
+        let mut cpu = Cpu::new(vec![0x60, 0x01, 0x12, 0x00], 360, false);
+        let mut editor = HexEditor::new();
+        press(&mut editor, &mut cpu, &[Key::P, Key::Right, Key::D0]);
+        let lines = editor.lines(cpu.memory());
+        assert_eq!("0200 60>0- 12 00 00 00 00 00", lines[9]);
+
+        press(&mut editor, &mut cpu, &[Key::D7]);
+        assert_eq!(0x07, cpu.memory()[0x201]);
+        assert_eq!(0x202, editor.cursor);
+        cpu.cycle();
+        assert_eq!(7, cpu.registers()[0x0]);
+
+        let lines = editor.lines(cpu.memory());
+        assert_eq!("MEMORY 0202  12  18", lines[0]);
+        assert!(lines.iter().all(|line| line.len() <= PANEL_COLUMNS));
+    }
+
+    #[test]
+    fn searches_find_patterns_and_values() {
+        assert_eq!(
+            Ok(vec![Some(0xA2), None, Some(0x1E)]),
+            parse_pattern("A2 .. 1E")
+        );
+        assert_eq!(Ok(vec![Some(0x01), Some(0x2C)]), parse_pattern("=300"));
+        assert_eq!(Ok(vec![Some(42)]), parse_pattern("=42"));
+        assert!(parse_pattern("A2 1").is_err());
+        assert!(parse_pattern("=70000").is_err());
+
+        let memory = [0xA2, 0x00, 0x1E, 0xA2, 0xFF, 0x1E, 0xA2];
+        assert_eq!(
+            vec![0, 3],
+            find_pattern(&memory, &[Some(0xA2), None, Some(0x1E)])
+        );
+
+        let mut cpu = Cpu::new(vec![0x00, 0xE0, 0x12, 0x00, 0x12, 0x00], 360, false);
+        let mut editor = HexEditor::new();
+        let keys = [
+            Key::Slash,
+            Key::D1,
+            Key::D2,
+            Key::Space,
+            Key::D0,
+            Key::D0,
+            Key::Return,
+        ];
+        press(&mut editor, &mut cpu, &keys);
+        assert_eq!(0x202, editor.cursor);
+        assert_eq!("2 MATCHES", editor.lines(cpu.memory())[18]);
+        press(&mut editor, &mut cpu, &[Key::N]);
+        assert_eq!(0x204, editor.cursor);
+        press(&mut editor, &mut cpu, &[Key::N]);
+        assert_eq!(0x202, editor.cursor);
+    }
+}
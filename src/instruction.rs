@@ -2,9 +2,10 @@ pub type Register = u8;
 pub type Address = u16;
 pub type Value = u8;
 
+use platform::Platform;
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Instruction {
     Clear,
     Return,
@@ -14,6 +15,7 @@ pub enum Instruction {
     SkipIfEqual(Register, Value),
     SkipIfNotEqual(Register, Value),
     SkipIfRegEqual(Register, Register),
+    SkipIfRegNotEqual(Register, Register),
     LoadVal(Register, Value),
     AddVal(Register, Value),
     LoadReg(Register, Register),
@@ -22,18 +24,57 @@ pub enum Instruction {
     Xor(Register, Register),
     AddReg(Register, Register),
     SubReg(Register, Register),
-    ShiftRight(Register),
-    ShiftLeft(Register),
+    ShiftRight(Register, Register),
+    SubRegReverse(Register, Register),
+    ShiftLeft(Register, Register),
     SetIndexRegister(Address),
+    JumpOffset(Address),
     Random(Register, Value),
     Draw(Register, Register, Value),
     SkipIfKey(Register),
     SkipIfNotKey(Register),
+    LoadDelay(Register),
+    WaitKey(Register),
+    SetDelay(Register),
+    SetSound(Register),
     AddIndex(Register),
     LoadDigit(Register),
     LoadBCD(Register),
     StoreIndex(Register),
     ReadIndex(Register),
+    /// CHIP-8X: 02A0 steps the background to its next color
+    CycleBackground,
+    /// CHIP-8X: BXY0 colors the zones given by VX and VX+1 with VY
+    SetZoneColor(Register, Register),
+    /// CHIP-8X: BXYN colors the zones under an 8xN sprite at VX, VX+1 with VY
+    SetSpriteColor(Register, Register, Value),
+    /// CHIP-8X: EXF2 and EXF5 check the second keypad
+    SkipIfKey2(Register),
+    SkipIfNotKey2(Register),
+    /// CHIP-8X: FXF8 sends VX to the sound output port
+    OutputPort(Register),
+    /// MEGA-CHIP: 0010 and 0011 turn mega mode off and on
+    MegaOff,
+    MegaOn,
+    /// MEGA-CHIP: 01NN loads I with NN followed by the 16 bits of the next
+    /// word, making it a four byte instruction
+    LoadLongIndex(Value),
+    /// MEGA-CHIP: 02NN loads NN ARGB colors from I into the palette,
+    /// starting at color 1
+    LoadPalette(Value),
+    /// MEGA-CHIP: 03NN and 04NN set the size of sprites, where 0 means 256
+    SetSpriteWidth(Value),
+    SetSpriteHeight(Value),
+    /// MEGA-CHIP: 05NN sets how opaque sprites are drawn
+    SetAlpha(Value),
+    /// MEGA-CHIP: 060N plays the sound sample at I, looping unless N is 1
+    PlaySample(Value),
+    /// MEGA-CHIP: 0700 stops the sample
+    StopSample,
+    /// MEGA-CHIP: 080N sets how sprites are blended with the screen
+    SetBlendMode(Value),
+    /// MEGA-CHIP: 09NN sets the color that makes sprites collide
+    SetCollisionColor(Value),
     InvalidOperation,
 }
 
@@ -59,11 +100,17 @@ impl Instruction {
                 0x0003 => Instruction::Xor(reg1(val), reg2(val)),
                 0x0004 => Instruction::AddReg(reg1(val), reg2(val)),
                 0x0005 => Instruction::SubReg(reg1(val), reg2(val)),
-                0x0006 => Instruction::ShiftRight(reg1(val)),
-                0x000E => Instruction::ShiftLeft(reg1(val)),
+                0x0006 => Instruction::ShiftRight(reg1(val), reg2(val)),
+                0x0007 => Instruction::SubRegReverse(reg1(val), reg2(val)),
+                0x000E => Instruction::ShiftLeft(reg1(val), reg2(val)),
+                _ => Instruction::InvalidOperation,
+            },
+            0x9000 => match val & 0x000F {
+                0x0000 => Instruction::SkipIfRegNotEqual(reg1(val), reg2(val)),
                 _ => Instruction::InvalidOperation,
             },
             0xA000 => Instruction::SetIndexRegister(addr(val)),
+            0xB000 => Instruction::JumpOffset(addr(val)),
             0xC000 => Instruction::Random(reg1(val), byte(val)),
             0xD000 => Instruction::Draw(reg1(val), reg2(val), nibble(val)),
             0xE000 => match val & 0x00FF {
@@ -72,6 +119,10 @@ impl Instruction {
                 _ => Instruction::InvalidOperation,
             },
             0xF000 => match val & 0x00FF {
+                0x0007 => Instruction::LoadDelay(reg1(val)),
+                0x000A => Instruction::WaitKey(reg1(val)),
+                0x0015 => Instruction::SetDelay(reg1(val)),
+                0x0018 => Instruction::SetSound(reg1(val)),
                 0x001E => Instruction::AddIndex(reg1(val)),
                 0x0029 => Instruction::LoadDigit(reg1(val)),
                 0x0033 => Instruction::LoadBCD(reg1(val)),
@@ -82,6 +133,131 @@ impl Instruction {
             _ => Instruction::InvalidOperation,
         }
     }
+
+    /// Decodes an opcode the way a platform's interpreter does. The CHIP-8X
+    /// gives new meanings to 02A0, BNNN, EXF2, EXF5 and FXF8, and the
+    /// MEGA-CHIP to 0010 up to 09NN.
+    pub fn parse_for(val: u16, platform: Platform) -> Instruction {
+        match platform {
+            Platform::Chip8 | Platform::HiresChip8 => Instruction::parse(val),
+            Platform::Chip8X => Instruction::parse_chip8x(val),
+            Platform::MegaChip => Instruction::parse_megachip(val),
+        }
+    }
+
+    fn parse_chip8x(val: u16) -> Instruction {
+        match (val & 0xF000, val & 0x00FF) {
+            _ if val == 0x02A0 => Instruction::CycleBackground,
+            (0xB000, _) if nibble(val) == 0 => Instruction::SetZoneColor(reg1(val), reg2(val)),
+            (0xB000, _) => Instruction::SetSpriteColor(reg1(val), reg2(val), nibble(val)),
+            (0xE000, 0x00F2) => Instruction::SkipIfKey2(reg1(val)),
+            (0xE000, 0x00F5) => Instruction::SkipIfNotKey2(reg1(val)),
+            (0xF000, 0x00F8) => Instruction::OutputPort(reg1(val)),
+            _ => Instruction::parse(val),
+        }
+    }
+
+    fn parse_megachip(val: u16) -> Instruction {
+        match val & 0xFF00 {
+            _ if val == 0x0010 => Instruction::MegaOff,
+            _ if val == 0x0011 => Instruction::MegaOn,
+            0x0100 => Instruction::LoadLongIndex(byte(val)),
+            0x0200 => Instruction::LoadPalette(byte(val)),
+            0x0300 => Instruction::SetSpriteWidth(byte(val)),
+            0x0400 => Instruction::SetSpriteHeight(byte(val)),
+            0x0500 => Instruction::SetAlpha(byte(val)),
+            0x0600 if reg2(val) == 0 => Instruction::PlaySample(nibble(val)),
+            _ if val == 0x0700 => Instruction::StopSample,
+            0x0800 if reg2(val) == 0 => Instruction::SetBlendMode(nibble(val)),
+            0x0900 => Instruction::SetCollisionColor(byte(val)),
+            _ => Instruction::parse(val),
+        }
+    }
+
+    /// Bytes the instruction takes up, four for 01NN NNNN and two otherwise
+    pub fn size(&self) -> u16 {
+        match *self {
+            Instruction::LoadLongIndex(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Whether execution can continue anywhere but the next instruction,
+    /// which ends a basic block. Waiting for a key repeats the instruction.
+    pub fn is_branch(&self) -> bool {
+        matches!(
+            *self,
+            Instruction::Return
+                | Instruction::Jump(_)
+                | Instruction::Call(_)
+                | Instruction::JumpOffset(_)
+                | Instruction::SkipIfEqual(_, _)
+                | Instruction::SkipIfNotEqual(_, _)
+                | Instruction::SkipIfRegEqual(_, _)
+                | Instruction::SkipIfRegNotEqual(_, _)
+                | Instruction::SkipIfKey(_)
+                | Instruction::SkipIfNotKey(_)
+                | Instruction::SkipIfKey2(_)
+                | Instruction::SkipIfNotKey2(_)
+                | Instruction::WaitKey(_)
+                | Instruction::InvalidOperation
+        )
+    }
+
+    /// The assembly mnemonic without operands, e.g. "DRW"
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            Instruction::Clear => "CLS",
+            Instruction::Return => "RET",
+            Instruction::ExRoutine(_) => "SYS",
+            Instruction::Jump(_) | Instruction::JumpOffset(_) => "JP",
+            Instruction::Call(_) => "CALL",
+            Instruction::SkipIfEqual(_, _) | Instruction::SkipIfRegEqual(_, _) => "SE",
+            Instruction::SkipIfNotEqual(_, _) | Instruction::SkipIfRegNotEqual(_, _) => "SNE",
+            Instruction::LoadVal(_, _)
+            | Instruction::LoadReg(_, _)
+            | Instruction::SetIndexRegister(_)
+            | Instruction::LoadDelay(_)
+            | Instruction::WaitKey(_)
+            | Instruction::SetDelay(_)
+            | Instruction::SetSound(_)
+            | Instruction::LoadDigit(_)
+            | Instruction::LoadBCD(_)
+            | Instruction::StoreIndex(_)
+            | Instruction::ReadIndex(_) => "LD",
+            Instruction::AddVal(_, _) | Instruction::AddReg(_, _) | Instruction::AddIndex(_) => {
+                "ADD"
+            }
+            Instruction::Or(_, _) => "OR",
+            Instruction::And(_, _) => "AND",
+            Instruction::Xor(_, _) => "XOR",
+            Instruction::SubReg(_, _) => "SUB",
+            Instruction::ShiftRight(_, _) => "SHR",
+            Instruction::SubRegReverse(_, _) => "SUBN",
+            Instruction::ShiftLeft(_, _) => "SHL",
+            Instruction::Random(_, _) => "RND",
+            Instruction::Draw(_, _, _) => "DRW",
+            Instruction::SkipIfKey(_) => "SKP",
+            Instruction::SkipIfNotKey(_) => "SKNP",
+            Instruction::CycleBackground => "BGC",
+            Instruction::SetZoneColor(_, _) | Instruction::SetSpriteColor(_, _, _) => "COL",
+            Instruction::SkipIfKey2(_) => "SKP2",
+            Instruction::SkipIfNotKey2(_) => "SKNP2",
+            Instruction::OutputPort(_) => "OUT",
+            Instruction::MegaOff => "MEGAOFF",
+            Instruction::MegaOn => "MEGAON",
+            Instruction::LoadLongIndex(_) => "LDHI",
+            Instruction::LoadPalette(_) => "LDPAL",
+            Instruction::SetSpriteWidth(_) => "SPRW",
+            Instruction::SetSpriteHeight(_) => "SPRH",
+            Instruction::SetAlpha(_) => "ALPHA",
+            Instruction::PlaySample(_) => "DIGISND",
+            Instruction::StopSample => "STOPSND",
+            Instruction::SetBlendMode(_) => "BMODE",
+            Instruction::SetCollisionColor(_) => "CCOL",
+            Instruction::InvalidOperation => "INVALID",
+        }
+    }
 }
 
 impl fmt::Display for Instruction {
@@ -95,6 +271,7 @@ impl fmt::Display for Instruction {
             Instruction::SkipIfEqual(r, v) => format!("SE V{:X}, {}", r, v),
             Instruction::SkipIfNotEqual(r, v) => format!("SNE V{:X}, {}", r, v),
             Instruction::SkipIfRegEqual(r1, r2) => format!("SE V{:X}, V{:X}", r1, r2),
+            Instruction::SkipIfRegNotEqual(r1, r2) => format!("SNE V{:X}, V{:X}", r1, r2),
             Instruction::LoadVal(r, v) => format!("LD V{:X}, {}", r, v),
             Instruction::AddVal(r, v) => format!("ADD V{:X}, {}", r, v),
             Instruction::LoadReg(r1, r2) => format!("LD V{:X}, V{:X}", r1, r2),
@@ -103,18 +280,41 @@ impl fmt::Display for Instruction {
             Instruction::Xor(r1, r2) => format!("XOR V{:X}, V{:X}", r1, r2),
             Instruction::AddReg(r1, r2) => format!("ADD V{:X}, V{:X}", r1, r2),
             Instruction::SubReg(r1, r2) => format!("SUB V{:X}, V{:X}", r1, r2),
-            Instruction::ShiftRight(r) => format!("SHR V{:X}", r),
-            Instruction::ShiftLeft(r) => format!("SHL V{:X}", r),
+            Instruction::ShiftRight(r1, r2) => format!("SHR V{:X}, V{:X}", r1, r2),
+            Instruction::SubRegReverse(r1, r2) => format!("SUBN V{:X}, V{:X}", r1, r2),
+            Instruction::ShiftLeft(r1, r2) => format!("SHL V{:X}, V{:X}", r1, r2),
             Instruction::SetIndexRegister(a) => format!("LD I, 0x{:X}", a),
+            Instruction::JumpOffset(a) => format!("JP V0, 0x{:X}", a),
             Instruction::Random(r, v) => format!("RND V{:X}, {}", r, v),
             Instruction::Draw(r1, r2, v) => format!("DRW V{:X}, V{:X}, {}", r1, r2, v),
             Instruction::SkipIfKey(r) => format!("SKP V{:X}", r),
             Instruction::SkipIfNotKey(r) => format!("SKNP V{:X}", r),
+            Instruction::LoadDelay(r) => format!("LD V{:X}, DT", r),
+            Instruction::WaitKey(r) => format!("LD V{:X}, K", r),
+            Instruction::SetDelay(r) => format!("LD DT, V{:X}", r),
+            Instruction::SetSound(r) => format!("LD ST, V{:X}", r),
             Instruction::AddIndex(r) => format!("ADD I, V{:X}", r),
             Instruction::LoadDigit(r) => format!("LD F, V{:X}", r),
             Instruction::LoadBCD(r) => format!("LD B, V{:X}", r),
             Instruction::StoreIndex(r) => format!("LD [I], V{:X}", r),
             Instruction::ReadIndex(r) => format!("LD V{:X} [I]", r),
+            Instruction::CycleBackground => String::from("BGC"),
+            Instruction::SetZoneColor(r1, r2) => format!("COL V{:X}, V{:X}", r1, r2),
+            Instruction::SetSpriteColor(r1, r2, v) => format!("COL V{:X}, V{:X}, {}", r1, r2, v),
+            Instruction::SkipIfKey2(r) => format!("SKP2 V{:X}", r),
+            Instruction::SkipIfNotKey2(r) => format!("SKNP2 V{:X}", r),
+            Instruction::OutputPort(r) => format!("OUT V{:X}", r),
+            Instruction::MegaOff => String::from("MEGAOFF"),
+            Instruction::MegaOn => String::from("MEGAON"),
+            Instruction::LoadLongIndex(v) => format!("LDHI I, 0x{:02X}", v),
+            Instruction::LoadPalette(v) => format!("LDPAL {}", v),
+            Instruction::SetSpriteWidth(v) => format!("SPRW {}", v),
+            Instruction::SetSpriteHeight(v) => format!("SPRH {}", v),
+            Instruction::SetAlpha(v) => format!("ALPHA {}", v),
+            Instruction::PlaySample(v) => format!("DIGISND {}", v),
+            Instruction::StopSample => String::from("STOPSND"),
+            Instruction::SetBlendMode(v) => format!("BMODE {}", v),
+            Instruction::SetCollisionColor(v) => format!("CCOL {}", v),
             Instruction::InvalidOperation => format!("INVALID OPERATION"),
         };
         write!(f, "{}", pretty_instruction)
@@ -140,3 +340,97 @@ fn nibble(val: u16) -> u8 {
 fn addr(val: u16) -> u16 {
     (val & 0x0FFF)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chip8x_opcodes_only_decode_on_chip8x() {
+        assert_eq!(
+            Instruction::JumpOffset(0x123),
+            Instruction::parse_for(0xB123, Platform::Chip8)
+        );
+        assert_eq!(
+            Instruction::SetSpriteColor(0x1, 0x2, 3),
+            Instruction::parse_for(0xB123, Platform::Chip8X)
+        );
+        assert_eq!(
+            Instruction::SetZoneColor(0x1, 0x2),
+            Instruction::parse_for(0xB120, Platform::Chip8X)
+        );
+        assert_eq!(
+            Instruction::CycleBackground,
+            Instruction::parse_for(0x02A0, Platform::Chip8X)
+        );
+        assert_eq!(
+            Instruction::ExRoutine(0x2A0),
+            Instruction::parse_for(0x02A0, Platform::Chip8)
+        );
+        assert_eq!(
+            Instruction::SkipIfKey2(0x4),
+            Instruction::parse_for(0xE4F2, Platform::Chip8X)
+        );
+        assert_eq!(
+            Instruction::SkipIfNotKey2(0x4),
+            Instruction::parse_for(0xE4F5, Platform::Chip8X)
+        );
+        assert_eq!(
+            Instruction::OutputPort(0x5),
+            Instruction::parse_for(0xF5F8, Platform::Chip8X)
+        );
+        assert_eq!(
+            Instruction::InvalidOperation,
+            Instruction::parse_for(0xF5F8, Platform::Chip8)
+        );
+        assert_eq!(
+            Instruction::Draw(0x1, 0x2, 5),
+            Instruction::parse_for(0xD125, Platform::Chip8X)
+        );
+    }
+
+    #[test]
+    fn megachip_opcodes_only_decode_on_megachip() {
+        assert_eq!(
+            Instruction::MegaOn,
+            Instruction::parse_for(0x0011, Platform::MegaChip)
+        );
+        assert_eq!(
+            Instruction::ExRoutine(0x011),
+            Instruction::parse_for(0x0011, Platform::Chip8)
+        );
+        assert_eq!(
+            Instruction::LoadLongIndex(0x12),
+            Instruction::parse_for(0x0112, Platform::MegaChip)
+        );
+        assert_eq!(4, Instruction::LoadLongIndex(0x12).size());
+        assert_eq!(
+            Instruction::LoadPalette(0xA0),
+            Instruction::parse_for(0x02A0, Platform::MegaChip)
+        );
+        assert_eq!(
+            Instruction::PlaySample(1),
+            Instruction::parse_for(0x0601, Platform::MegaChip)
+        );
+        assert_eq!(
+            Instruction::ExRoutine(0x611),
+            Instruction::parse_for(0x0611, Platform::MegaChip)
+        );
+        assert_eq!(
+            Instruction::StopSample,
+            Instruction::parse_for(0x0700, Platform::MegaChip)
+        );
+        assert_eq!(
+            Instruction::SetBlendMode(4),
+            Instruction::parse_for(0x0804, Platform::MegaChip)
+        );
+        assert_eq!(
+            Instruction::Clear,
+            Instruction::parse_for(0x00E0, Platform::MegaChip)
+        );
+        assert_eq!(
+            Instruction::JumpOffset(0x123),
+            Instruction::parse_for(0xB123, Platform::MegaChip)
+        );
+    }
+}
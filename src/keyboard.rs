@@ -10,6 +10,19 @@ pub enum KeyMapping {
     Improved,
 }
 
+/// Emulator functions bound to keys outside of the CHIP-8 keypad
+pub enum Hotkey {
+    Screenshot,
+    ToggleRecording,
+    ToggleFullscreen,
+    OpenBrowser,
+    TogglePause,
+    Reset,
+    ToggleOverlay,
+    ToggleHexEditor,
+    ToggleCheatFinder,
+}
+
 impl Keyboard {
     pub fn new(map: KeyMapping) -> Keyboard {
         Keyboard { mapping: map }
@@ -22,6 +35,63 @@ impl Keyboard {
         }
     }
 
+    /// The CHIP-8X's second keypad, laid out like the improved mapping on the
+    /// right side of the keyboard
+    pub fn map_second_key(&self, button: Button) -> Option<u8> {
+        match button {
+            Button::Keyboard(Key::D7) => Some(0x1),
+            Button::Keyboard(Key::D8) => Some(0x2),
+            Button::Keyboard(Key::D9) => Some(0x3),
+            Button::Keyboard(Key::D0) => Some(0xC),
+            Button::Keyboard(Key::U) => Some(0x4),
+            Button::Keyboard(Key::I) => Some(0x5),
+            Button::Keyboard(Key::O) => Some(0x6),
+            Button::Keyboard(Key::P) => Some(0xD),
+            Button::Keyboard(Key::J) => Some(0x7),
+            Button::Keyboard(Key::K) => Some(0x8),
+            Button::Keyboard(Key::L) => Some(0x9),
+            Button::Keyboard(Key::Semicolon) => Some(0xE),
+            Button::Keyboard(Key::M) => Some(0xA),
+            Button::Keyboard(Key::Comma) => Some(0x0),
+            Button::Keyboard(Key::Period) => Some(0xB),
+            Button::Keyboard(Key::Slash) => Some(0xF),
+            _ => None,
+        }
+    }
+
+    pub fn map_hotkey(&self, button: Button) -> Option<Hotkey> {
+        match button {
+            Button::Keyboard(Key::F12) => Some(Hotkey::Screenshot),
+            Button::Keyboard(Key::F9) => Some(Hotkey::ToggleRecording),
+            Button::Keyboard(Key::F11) => Some(Hotkey::ToggleFullscreen),
+            Button::Keyboard(Key::F2) => Some(Hotkey::OpenBrowser),
+            Button::Keyboard(Key::F5) => Some(Hotkey::TogglePause),
+            Button::Keyboard(Key::F6) => Some(Hotkey::Reset),
+            Button::Keyboard(Key::F3) => Some(Hotkey::ToggleOverlay),
+            Button::Keyboard(Key::F4) => Some(Hotkey::ToggleHexEditor),
+            Button::Keyboard(Key::F7) => Some(Hotkey::ToggleCheatFinder),
+            _ => None,
+        }
+    }
+
+    /// The name of the keyboard key bound to a CHIP-8 key
+    pub fn key_label(&self, key: u8) -> &'static str {
+        match self.mapping {
+            KeyMapping::Default => self.default_key_label(key),
+            KeyMapping::Improved => self.improved_key_label(key),
+        }
+    }
+
+    /// The uppercase letter or digit on a key, for typing into the panels.
+    /// Piston uses the same ASCII key codes as SDL for these.
+    pub fn character(key: Key) -> Option<char> {
+        match key as u32 {
+            code @ 0x30..=0x39 => Some(code as u8 as char),
+            code @ 0x61..=0x7A => Some((code as u8 as char).to_ascii_uppercase()),
+            _ => None,
+        }
+    }
+
     fn default_keymapping(&self, button: Button) -> Option<u8> {
         match button {
             Button::Keyboard(Key::D0) => Some(0x0),
@@ -65,4 +135,48 @@ impl Keyboard {
             _ => None,
         }
     }
+
+    fn default_key_label(&self, key: u8) -> &'static str {
+        match key {
+            0x0 => "0",
+            0x1 => "1",
+            0x2 => "2",
+            0x3 => "3",
+            0x4 => "4",
+            0x5 => "5",
+            0x6 => "6",
+            0x7 => "7",
+            0x8 => "8",
+            0x9 => "9",
+            0xA => "A",
+            0xB => "B",
+            0xC => "C",
+            0xD => "D",
+            0xE => "E",
+            0xF => "F",
+            _ => "?",
+        }
+    }
+
+    fn improved_key_label(&self, key: u8) -> &'static str {
+        match key {
+            0x1 => "1",
+            0x2 => "2",
+            0x3 => "3",
+            0xC => "4",
+            0x4 => "Q",
+            0x5 => "W",
+            0x6 => "E",
+            0xD => "R",
+            0x7 => "A",
+            0x8 => "S",
+            0x9 => "D",
+            0xE => "F",
+            0xA => "Z",
+            0x0 => "X",
+            0xB => "C",
+            0xF => "V",
+            _ => "?",
+        }
+    }
 }
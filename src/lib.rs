@@ -0,0 +1,33 @@
+//! The emulator core and the tools built on it, without a window, so that
+//! ROMs can also be run and inspected headless
+
+extern crate gif;
+extern crate rand;
+extern crate sha1;
+extern crate yaml_rust;
+extern crate zip;
+
+pub mod alu;
+pub mod analysis;
+pub mod cfg;
+pub mod cheats;
+pub mod coverage;
+pub mod cpu;
+pub mod decode;
+pub mod display;
+pub mod instruction;
+pub mod megachip;
+pub mod metadata;
+pub mod observer;
+pub mod octo;
+pub mod patch;
+pub mod platform;
+pub mod profiler;
+pub mod quirks;
+pub mod recompiler;
+pub mod rom;
+pub mod routines;
+#[cfg(test)]
+mod testing;
+pub mod timing;
+pub mod trace;
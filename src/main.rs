@@ -1,31 +1,63 @@
+extern crate chip8;
+extern crate gif;
 extern crate image;
 extern crate piston_window;
-extern crate rand;
 
 #[macro_use]
 extern crate clap;
 
-mod cpu;
-mod display;
-mod instruction;
+mod browser;
+mod capture;
+mod cheatfinder;
+mod hexeditor;
 mod keyboard;
+mod overlay;
+mod session;
+mod text;
+mod watcher;
+#[cfg(test)]
+mod testing;
 
-use clap::App;
+use analysis::Analyzer;
+use browser::Browser;
+use capture::Recorder;
+use cfg::{CfgFormat, ControlFlowGraph};
+use cheatfinder::CheatFinder;
+use chip8::{
+    analysis, cfg, cheats, coverage, cpu, display, instruction, megachip, metadata, platform,
+    profiler, recompiler, rom, timing, trace,
+};
+use clap::{App, ArgMatches};
+use coverage::Coverage;
 use cpu::Cpu;
-use keyboard::{KeyMapping, Keyboard};
+use display::Frame;
+use hexeditor::HexEditor;
+use keyboard::{Hotkey, KeyMapping, Keyboard};
+use megachip::MEGA_WIDTH;
+use metadata::{RomDatabase, RomInfo};
 use piston_window::*;
+use platform::Platform;
+use profiler::Profiler;
+use rom::RomError;
+use session::Session;
+use std::env;
+use std::fs;
 use std::fs::File;
-use std::io::Read;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::time::Instant;
+use timing::Timing;
+use trace::{TraceFilter, TraceFormat, Tracer};
 
 const ENLARGEMENT_FACTOR: u32 = 8;
 const WINDOW_WIDTH: u32 = 64;
 const WINDOW_HEIGHT: u32 = 32;
+/// The largest enlargement of the widest screen, the MEGA-CHIP's, that still
+/// fits the 16 bit size of a GIF
+const MAX_CAPTURE_SCALE: u32 = u16::MAX as u32 / MEGA_WIDTH as u32;
 
-const CLOCK_SPEED_HZ_HALF: u32 = 180;
 const CLOCK_SPEED_HZ_DEFAULT: u32 = 360;
-const CLOCK_SPEED_HZ_DOUBLE: u32 = 720;
+const FRAMES_PER_SECOND: u32 = 60;
 
 enum EmulatorSpeed {
     Half,
@@ -34,82 +66,585 @@ enum EmulatorSpeed {
 }
 
 struct Arguments {
-    rom: String,
+    rom: Option<String>,
     step: bool,
     debug: bool,
     speed: EmulatorSpeed,
+    timing: Timing,
+    platform: Option<Platform>,
+    screenshot: Option<String>,
+    record: Option<String>,
+    capture_scale: u32,
+    scale: u32,
+    fullscreen: bool,
+    rom_db: Option<String>,
+    patch: Option<String>,
+    cheats: Option<String>,
+    trace: Option<TraceOptions>,
+    trace_diff: Option<(String, String)>,
+    profile: Option<String>,
+    coverage: Option<String>,
+    analyze: bool,
+    cfg: Option<(String, CfgFormat)>,
+    recompile: Option<String>,
+}
+
+struct TraceOptions {
+    path: String,
+    format: TraceFormat,
+    filter: TraceFilter,
+}
+
+/// Directory listed by the ROM browser when no ROM is given
+const DEFAULT_ROM_DIRECTORY: &str = "roms";
+
+enum Mode {
+    Browsing(Browser),
+    Running(Box<Session>),
 }
 
 fn main() {
     let arguments = match parse_args() {
         Ok(args) => args,
-        Err(_) => {
+        Err(e) => {
+            println!("{}", e);
             println!("Usage: chip8 [rom] [--debug (optional)] [--step (optional)]");
-            process::exit(0);
+            process::exit(1);
         }
     };
 
-    let width = WINDOW_WIDTH * ENLARGEMENT_FACTOR;
-    let height = WINDOW_HEIGHT * ENLARGEMENT_FACTOR;
-
-    let mut window = create_window(width, height);
-
-    let mut file = File::open(arguments.rom).expect("Unable to open the ROM file.");
+    if let Some((ref left, ref right)) = arguments.trace_diff {
+        process::exit(compare_traces(left, right));
+    }
 
-    let mut game_data = Vec::new();
-    file.read_to_end(&mut game_data)
-        .expect("Unable to read the ROM file.");
+    let database = load_rom_database(&arguments);
+    let keyboard = Keyboard::new(KeyMapping::Improved);
 
-    let clock_speed = match arguments.speed {
-        EmulatorSpeed::Half => CLOCK_SPEED_HZ_HALF,
-        EmulatorSpeed::Normal => CLOCK_SPEED_HZ_DEFAULT,
-        EmulatorSpeed::Double => CLOCK_SPEED_HZ_DOUBLE,
+    let source = arguments
+        .rom
+        .clone()
+        .unwrap_or_else(|| String::from(DEFAULT_ROM_DIRECTORY));
+    if let Some((ref path, format)) = arguments.cfg {
+        process::exit(export_cfg(
+            &source,
+            Path::new(path),
+            format,
+            arguments.platform,
+            &database,
+        ));
+    }
+    if let Some(ref path) = arguments.recompile {
+        process::exit(export_recompiled(
+            &source,
+            Path::new(path),
+            arguments.platform,
+            &database,
+        ));
+    }
+    let mut mode = if source != "-" && Path::new(&source).is_dir() {
+        match Browser::open(Path::new(&source), &database) {
+            Ok(browser) => Mode::Browsing(browser),
+            Err(e) => {
+                println!("Unable to list ROMs in {}: {}", source, e);
+                process::exit(1);
+            }
+        }
+    } else {
+        match start_session(&source, &arguments, &database, &keyboard) {
+            Ok(session) => Mode::Running(Box::new(session)),
+            Err(e) => {
+                println!("Unable to load {}: {}", source, e);
+                process::exit(1);
+            }
+        }
     };
 
-    let mut cpu = Cpu::new(game_data, clock_speed, arguments.debug);
-    let keyboard = Keyboard::new(KeyMapping::Improved);
+    let mut window_size = [
+        WINDOW_WIDTH * arguments.scale,
+        WINDOW_HEIGHT * arguments.scale,
+    ];
+    let mut fullscreen = arguments.fullscreen;
+    let mut show_overlay = false;
+    let mut show_hex_editor = false;
+    let mut hex_editor = HexEditor::new();
+    let mut show_cheat_finder = false;
+    let mut cheat_finder = CheatFinder::new();
 
-    let cycle_time_millis: u128 = (1000 / clock_speed).into();
+    let mut window = create_window(&mode_title(&mode), window_size, fullscreen);
+
+    if let Mode::Running(ref mut session) = mode {
+        if let Some(ref path) = arguments.record {
+            session.recorder = start_recording(Path::new(path), arguments.capture_scale);
+        }
+    }
 
-    let mut clock = Instant::now();
     while let Some(e) = window.next() {
         let mut step_forward = false;
+        let mut next_mode = None;
+
+        // The whole window is redrawn every frame since resizing and the
+        // letterbox borders invalidate what was previously drawn.
+        if e.render_args().is_some() {
+            window.draw_2d(&e, |context, graphics| {
+                piston_window::clear(color::BLACK, graphics);
+                match mode {
+                    Mode::Browsing(ref browser) => browser.draw(&context, graphics),
+                    Mode::Running(ref mut session) => {
+                        // The debug panel takes the right side of the window and
+                        // the screen is fitted into what is left
+                        let mut view = context.get_view_size();
+                        if show_overlay || show_hex_editor || show_cheat_finder {
+                            let size = overlay::font_size(view[1]);
+                            view[0] = (view[0] - overlay::panel_width(size)).max(0.0);
+                        }
+                        if show_hex_editor {
+                            let lines = hex_editor.lines(session.cpu.memory());
+                            overlay::draw_panel(&lines, view[0], &context, graphics);
+                        } else if show_cheat_finder {
+                            let lines = cheat_finder.lines(session.cpu.memory());
+                            overlay::draw_panel(&lines, view[0], &context, graphics);
+                        } else if show_overlay {
+                            overlay::draw_overlay(
+                                &session.cpu.state(),
+                                view[0],
+                                &context,
+                                graphics,
+                            );
+                        }
+                        draw_screen(&session.cpu.display.frame(), view, &context, graphics);
+                        session.cpu.draw_flag = false;
+                    }
+                }
+            });
+        }
 
-        if let Some(_) = e.render_args() {
-            if cpu.draw_flag {
-                draw_screen(&e, &cpu.get_screen(), &mut window);
-                cpu.draw_flag = false
+        if let Some(size) = e.resize_args() {
+            if !fullscreen {
+                window_size = size;
             }
         }
 
         if let Some(button) = e.press_args() {
-            if button == Button::Keyboard(Key::Space) && arguments.step {
+            let typing = show_hex_editor || show_cheat_finder;
+            if button == Button::Keyboard(Key::Space) && arguments.step && !typing {
                 step_forward = true;
             }
 
-            if let Some(key_val) = keyboard.map_key(button) {
-                cpu.set_key(key_val, true);
+            let hotkey = keyboard.map_hotkey(button);
+            match hotkey {
+                Some(Hotkey::ToggleFullscreen) => {
+                    fullscreen = !fullscreen;
+                    window = create_window(&mode_title(&mode), window_size, fullscreen);
+                }
+                Some(Hotkey::ToggleOverlay) => show_overlay = !show_overlay,
+                Some(Hotkey::ToggleHexEditor) => show_hex_editor = !show_hex_editor,
+                Some(Hotkey::ToggleCheatFinder) => show_cheat_finder = !show_cheat_finder,
+                _ => {}
+            }
+
+            match mode {
+                Mode::Browsing(ref mut browser) => {
+                    if let Some(path) = browser.handle_button(button) {
+                        let source = path.to_string_lossy().into_owned();
+                        match start_session(&source, &arguments, &database, &keyboard) {
+                            Ok(session) => next_mode = Some(Mode::Running(Box::new(session))),
+                            Err(e) => browser.set_message(e.to_string()),
+                        }
+                    }
+                }
+                Mode::Running(ref mut session) => {
+                    match hotkey {
+                        Some(Hotkey::Screenshot) => {
+                            let path = capture_path(&session.rom.name, "png");
+                            save_screenshot(&session.cpu, &path, arguments.capture_scale);
+                        }
+                        Some(Hotkey::ToggleRecording) => {
+                            session.recorder = match session.recorder.take() {
+                                Some(active) => {
+                                    stop_recording(active);
+                                    None
+                                }
+                                None => {
+                                    let path = capture_path(&session.rom.name, "gif");
+                                    start_recording(&path, arguments.capture_scale)
+                                }
+                            };
+                        }
+                        Some(Hotkey::OpenBrowser) => {
+                            next_mode = return_to_browser(session, &database);
+                        }
+                        Some(Hotkey::TogglePause) => {
+                            session.paused = !session.paused;
+                            window.set_title(session.title());
+                        }
+                        Some(Hotkey::Reset) => {
+                            session.reset();
+                            println!("Reset {}", session.rom.name);
+                        }
+                        _ => {}
+                    }
+
+                    // The hex editor and cheat finder take the keys while they
+                    // are shown, as their keys overlap with the keypad
+                    if show_hex_editor {
+                        hex_editor.handle_button(button, &mut session.cpu);
+                    } else if show_cheat_finder {
+                        if let Some(cheat) = cheat_finder.handle_button(button, &mut session.cpu) {
+                            println!(
+                                "Froze {}, add it under [{}] in the cheat file to keep it",
+                                cheat,
+                                metadata::rom_hash(&session.rom.data)
+                            );
+                        }
+                    } else {
+                        if let Some(key_val) = keyboard.map_key(button) {
+                            session.cpu.set_key(key_val, true);
+                        }
+                        if session.cpu.platform() == Platform::Chip8X {
+                            if let Some(key_val) = keyboard.map_second_key(button) {
+                                session.cpu.set_second_key(key_val, true);
+                            }
+                        }
+                    }
+                }
             }
         }
 
         if let Some(button) = e.release_args() {
-            if let Some(key_val) = keyboard.map_key(button) {
-                cpu.set_key(key_val, false);
+            if let Mode::Running(ref mut session) = mode {
+                if let Some(key_val) = keyboard.map_key(button) {
+                    session.cpu.set_key(key_val, false);
+                }
+                if session.cpu.platform() == Platform::Chip8X {
+                    if let Some(key_val) = keyboard.map_second_key(button) {
+                        session.cpu.set_second_key(key_val, false);
+                    }
+                }
             }
         }
 
-        // If debugging is enabled, only cycle on space bar presses
-        if arguments.step {
-            if step_forward {
-                cpu.cycle();
+        if let Mode::Running(ref mut session) = mode {
+            if e.update_args().is_some() {
+                match session.reload_if_changed() {
+                    Some(Ok(_)) => println!("Reloaded {}", session.rom.name),
+                    Some(Err(e)) => println!("Unable to reload {}: {}", session.rom.name, e),
+                    None => {}
+                }
+            }
+
+            // If debugging is enabled, only cycle on space bar presses
+            if arguments.step {
+                if step_forward {
+                    session.step();
+                }
+            } else if e.update_args().is_some() {
+                session.run_frame();
             }
-        } else {
-            let elapsed = clock.elapsed().as_millis();
-            if elapsed >= cycle_time_millis {
-                cpu.cycle();
-                clock = Instant::now();
+        }
+
+        if let Some(next) = next_mode {
+            if let Mode::Running(session) = mode {
+                end_session(session, &arguments, false);
+            }
+            mode = next;
+            window.set_title(mode_title(&mode));
+        }
+    }
+
+    if let Mode::Running(session) = mode {
+        end_session(session, &arguments, true);
+    }
+}
+
+/// Loads a ROM and sets up the CPU for it with the speed and quirks from the
+/// ROM database
+fn start_session(
+    source: &str,
+    arguments: &Arguments,
+    database: &RomDatabase,
+    keyboard: &Keyboard,
+) -> Result<Session, RomError> {
+    // A patch given on the command line is for the ROM given there, not the
+    // ones launched from the browser
+    let patch = arguments
+        .patch
+        .as_ref()
+        .filter(|_| arguments.rom.as_deref() == Some(source))
+        .map(Path::new);
+    let rom = rom::load_patched(source, patch)?;
+    if let Some(ref patch) = rom.patch {
+        println!("Applied patch {}", patch.display());
+    }
+    for warning in rom.warnings.iter() {
+        println!("Warning: {}", warning);
+    }
+
+    let info = database.lookup(&rom.data).cloned().unwrap_or_default();
+    print_rom_info(&info, keyboard);
+
+    let platform = rom_platform(arguments.platform, &info, &rom.data);
+    rom.check_fits(platform)?;
+
+    let base_clock_speed = match info.instructions_per_frame {
+        Some(ipf) => ipf * FRAMES_PER_SECOND,
+        None => CLOCK_SPEED_HZ_DEFAULT,
+    };
+    let clock_speed = match arguments.speed {
+        EmulatorSpeed::Half => base_clock_speed / 2,
+        EmulatorSpeed::Normal => base_clock_speed,
+        EmulatorSpeed::Double => base_clock_speed * 2,
+    };
+
+    let path = if source == "-" {
+        None
+    } else {
+        Some(PathBuf::from(source))
+    };
+    let mut session = Session::new(
+        rom,
+        info,
+        path,
+        clock_speed,
+        platform,
+        FRAMES_PER_SECOND,
+        arguments.debug,
+    );
+    session.cpu.set_timing(arguments.timing);
+    session.patch = patch.map(Path::to_path_buf);
+
+    if let Some(ref options) = arguments.trace {
+        let path = Path::new(&options.path);
+        match Tracer::create(path, options.format, options.filter.clone()) {
+            Ok(tracer) => session.cpu.set_tracer(tracer),
+            Err(e) => println!("Unable to write trace to {}: {}", path.display(), e),
+        }
+    }
+
+    if arguments.profile.is_some() {
+        session.cpu.set_profiler(Profiler::new(platform));
+    }
+    if arguments.coverage.is_some() {
+        let rom_size = session.rom.data.len();
+        session.cpu.set_coverage(Coverage::new(rom_size, platform));
+    }
+    if arguments.analyze {
+        let rom_size = session.rom.data.len();
+        session.cpu.set_analyzer(Analyzer::new(rom_size, platform));
+    }
+
+    if let Some(cheats) = load_cheats(arguments, &session.rom.data) {
+        if !cheats.cheats().is_empty() {
+            println!("Applying {} cheats", cheats.cheats().len());
+            session.cpu.set_cheats(cheats);
+        }
+    }
+
+    Ok(session)
+}
+
+/// Finishes any recording, trace and profile, and when the emulator is exiting
+/// saves the final screenshot if one was asked for
+fn end_session(mut session: Box<Session>, arguments: &Arguments, exiting: bool) {
+    if exiting {
+        if let Some(ref path) = arguments.screenshot {
+            save_screenshot(&session.cpu, Path::new(path), arguments.capture_scale);
+        }
+    }
+    if let Some(active) = session.recorder.take() {
+        stop_recording(active);
+    }
+    if let Some(tracer) = session.cpu.take_tracer() {
+        if let Err(e) = tracer.finish() {
+            println!("Unable to write trace: {}", e);
+        }
+    }
+    if let (Some(profiler), Some(ref path)) = (session.cpu.take_profiler(), &arguments.profile) {
+        let path = Path::new(path);
+        match profiler.write_reports(path) {
+            Ok(_) => println!("Saved profile to {}", path.display()),
+            Err(e) => println!("Unable to save profile to {}: {}", path.display(), e),
+        }
+    }
+    if let (Some(coverage), Some(ref path)) = (session.cpu.take_coverage(), &arguments.coverage) {
+        let path = Path::new(path);
+        let summary = coverage.summary();
+        match coverage.write_report_file(session.cpu.state().memory, path) {
+            Ok(_) => println!(
+                "Saved coverage to {}, {} of {} ROM bytes covered",
+                path.display(),
+                summary.covered,
+                summary.rom_size
+            ),
+            Err(e) => println!("Unable to save coverage to {}: {}", path.display(), e),
+        }
+    }
+    if let Some(analyzer) = session.cpu.take_analyzer() {
+        print_findings(&session.rom.name, analyzer.findings());
+    }
+}
+
+fn print_findings(name: &str, findings: &[analysis::Finding]) {
+    if findings.is_empty() {
+        println!("Analysis of {} found nothing suspicious", name);
+        return;
+    }
+    println!(
+        "Analysis of {} found {} suspicious things:",
+        name,
+        findings.len()
+    );
+    for finding in findings {
+        println!("  {}", finding);
+    }
+}
+
+/// Prints where two trace files first differ, returning the exit code: 0 when
+/// they are the same, 1 when they differ and 2 when they could not be read
+fn compare_traces(left: &str, right: &str) -> i32 {
+    let open = |path: &str| File::open(path).map(BufReader::new);
+    let (left_file, right_file) = match (open(left), open(right)) {
+        (Ok(left_file), Ok(right_file)) => (left_file, right_file),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Unable to read trace: {}", e);
+            return 2;
+        }
+    };
+
+    match trace::diff_traces(left_file, right_file) {
+        Ok(None) => {
+            println!("The traces are identical");
+            0
+        }
+        Ok(Some(divergence)) => {
+            println!("The traces diverge at line {}", divergence.line);
+            for line in divergence.context.iter() {
+                println!("  {}", line);
             }
+            let missing = String::from("(end of trace)");
+            println!("< {}", divergence.left.as_ref().unwrap_or(&missing));
+            println!("> {}", divergence.right.as_ref().unwrap_or(&missing));
+            1
+        }
+        Err(e) => {
+            println!("Unable to read trace: {}", e);
+            2
+        }
+    }
+}
+
+/// The platform given on the command line wins over the one in the ROM
+/// database, which wins over guessing from the ROM itself
+fn rom_platform(platform: Option<Platform>, info: &RomInfo, data: &[u8]) -> Platform {
+    platform
+        .or_else(|| {
+            info.platform
+                .as_ref()
+                .and_then(|name| Platform::from_name(name))
+        })
+        .unwrap_or_else(|| Platform::detect(data))
+}
+
+/// Writes the control-flow graph of a ROM, returning the exit code
+fn export_cfg(
+    source: &str,
+    path: &Path,
+    format: CfgFormat,
+    platform: Option<Platform>,
+    database: &RomDatabase,
+) -> i32 {
+    let rom = match rom::load(source) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Unable to load {}: {}", source, e);
+            return 1;
+        }
+    };
+
+    let info = database.lookup(&rom.data).cloned().unwrap_or_default();
+    let platform = rom_platform(platform, &info, &rom.data);
+    let graph = ControlFlowGraph::build(&rom.data, platform);
+    match graph.write_file(path, format) {
+        Ok(_) => {
+            println!(
+                "Saved control-flow graph of {} to {}, {} blocks and {} edges",
+                rom.name,
+                path.display(),
+                graph.blocks.len(),
+                graph.edges.len()
+            );
+            0
         }
+        Err(e) => {
+            println!(
+                "Unable to save control-flow graph to {}: {}",
+                path.display(),
+                e
+            );
+            1
+        }
+    }
+}
+
+/// Writes a ROM recompiled to Rust source, returning the exit code
+fn export_recompiled(
+    source: &str,
+    path: &Path,
+    platform: Option<Platform>,
+    database: &RomDatabase,
+) -> i32 {
+    let rom = match rom::load(source) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Unable to load {}: {}", source, e);
+            return 1;
+        }
+    };
+
+    let name = Path::new(source).file_name().map_or_else(
+        || rom.name.clone(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let info = database.lookup(&rom.data).cloned().unwrap_or_default();
+    let platform = rom_platform(platform, &info, &rom.data);
+    match fs::write(path, recompiler::recompile(&rom.data, &name, platform)) {
+        Ok(_) => {
+            println!("Saved recompiled {} to {}", rom.name, path.display());
+            0
+        }
+        Err(e) => {
+            println!("Unable to save recompiled ROM to {}: {}", path.display(), e);
+            1
+        }
+    }
+}
+
+/// The browser for the directory the running ROM came from, with the ROM selected
+fn return_to_browser(session: &Session, database: &RomDatabase) -> Option<Mode> {
+    let directory = session
+        .path
+        .as_ref()
+        .and_then(|path| path.parent())
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new(DEFAULT_ROM_DIRECTORY))
+        .to_path_buf();
+
+    let mut browser = match Browser::open(&directory, database) {
+        Ok(browser) => browser,
+        Err(e) => {
+            println!("Unable to list ROMs in {}: {}", directory.display(), e);
+            return None;
+        }
+    };
+    if let Some(ref path) = session.path {
+        browser.select_path(path);
+    }
+    Some(Mode::Browsing(browser))
+}
+
+fn mode_title(mode: &Mode) -> String {
+    match *mode {
+        Mode::Browsing(_) => String::from("chip8"),
+        Mode::Running(ref session) => session.title(),
     }
 }
 
@@ -117,7 +652,7 @@ fn parse_args() -> Result<Arguments, String> {
     let yaml = load_yaml!("../cli.yml");
     let matches = App::from_yaml(yaml).get_matches();
 
-    let rom = String::from(matches.value_of("ROM").unwrap());
+    let rom = matches.value_of("ROM").map(String::from);
     let debug = matches.is_present("debug");
     let step = matches.is_present("step");
     let speed_arg = matches.value_of("speed").unwrap_or("1");
@@ -126,53 +661,284 @@ fn parse_args() -> Result<Arguments, String> {
         "2" => EmulatorSpeed::Double,
         _ => EmulatorSpeed::Normal,
     };
+    let timing_name = matches.value_of("timing").unwrap_or("simple");
+    let timing = Timing::from_name(timing_name)
+        .ok_or_else(|| format!("Unknown timing '{}'", timing_name))?;
+    let platform = match matches.value_of("platform") {
+        Some(name) => {
+            Some(Platform::from_name(name).ok_or_else(|| format!("Unknown platform '{}'", name))?)
+        }
+        None => None,
+    };
+    let screenshot = matches.value_of("screenshot").map(String::from);
+    let record = matches.value_of("record").map(String::from);
+    let capture_scale = if matches.is_present("capture-scale") {
+        value_t!(matches, "capture-scale", u32).unwrap_or_else(|e| e.exit())
+    } else {
+        ENLARGEMENT_FACTOR
+    };
+    if capture_scale == 0 || capture_scale > MAX_CAPTURE_SCALE {
+        return Err(format!(
+            "The capture scale must be between 1 and {}",
+            MAX_CAPTURE_SCALE
+        ));
+    }
+    let scale = if matches.is_present("scale") {
+        value_t!(matches, "scale", u32).unwrap_or_else(|e| e.exit())
+    } else {
+        ENLARGEMENT_FACTOR
+    };
+    let fullscreen = matches.is_present("fullscreen");
+    let rom_db = matches.value_of("rom-db").map(String::from);
+    let trace = match matches.value_of("trace") {
+        Some(path) => Some(parse_trace_options(&matches, path)?),
+        None => None,
+    };
+    let trace_diff = matches.values_of("trace-diff").map(|mut paths| {
+        let left = String::from(paths.next().unwrap_or_default());
+        let right = String::from(paths.next().unwrap_or_default());
+        (left, right)
+    });
+    let cfg = match matches.value_of("cfg") {
+        Some(path) => {
+            let format_name = matches.value_of("cfg-format").unwrap_or("dot");
+            let format = CfgFormat::from_name(format_name)
+                .ok_or_else(|| format!("Unknown control-flow graph format '{}'", format_name))?;
+            Some((String::from(path), format))
+        }
+        None => None,
+    };
 
     let args = Arguments {
         rom,
         step,
         debug,
         speed,
+        timing,
+        platform,
+        screenshot,
+        record,
+        capture_scale,
+        scale: scale.max(1),
+        fullscreen,
+        rom_db,
+        patch: matches.value_of("patch").map(String::from),
+        cheats: matches.value_of("cheats").map(String::from),
+        trace,
+        trace_diff,
+        profile: matches.value_of("profile").map(String::from),
+        coverage: matches.value_of("coverage").map(String::from),
+        analyze: matches.is_present("analyze"),
+        cfg,
+        recompile: matches.value_of("recompile").map(String::from),
     };
     return Ok(args);
 }
 
-fn create_window(width: u32, height: u32) -> PistonWindow {
+fn parse_trace_options(matches: &ArgMatches, path: &str) -> Result<TraceOptions, String> {
+    let format_name = matches.value_of("trace-format").unwrap_or("text");
+    let format = TraceFormat::from_name(format_name)
+        .ok_or_else(|| format!("Unknown trace format '{}'", format_name))?;
+    let addresses = match matches.value_of("trace-range") {
+        Some(range) => Some(TraceFilter::parse_range(range)?),
+        None => None,
+    };
+    let mnemonics = matches
+        .value_of("trace-only")
+        .map(TraceFilter::parse_mnemonics)
+        .unwrap_or_default();
+
+    Ok(TraceOptions {
+        path: String::from(path),
+        format,
+        filter: TraceFilter {
+            addresses,
+            mnemonics,
+        },
+    })
+}
+
+fn create_window(title: &str, size: [u32; 2], fullscreen: bool) -> PistonWindow {
     let opengl = OpenGL::V3_2;
 
-    let mut window: PistonWindow = WindowSettings::new("chip8", (width, height))
+    let mut window: PistonWindow = WindowSettings::new(title, size)
         .exit_on_esc(true)
+        .resizable(true)
+        .fullscreen(fullscreen)
         .opengl(opengl)
         .build()
         .unwrap();
 
     window.set_max_fps(60);
+    window.set_ups(FRAMES_PER_SECOND as u64);
 
     window
 }
 
-fn draw_screen(event: &Event, screen: &display::Screen, window: &mut PistonWindow) {
-    window.draw_2d(event, |context, graphics| {
-        piston_window::clear(color::BLACK, graphics);
-
-        for (i, row) in screen.iter().enumerate() {
-            for (j, val) in row.iter().enumerate() {
-                if *val {
-                    let dimensions = [
-                        (j * ENLARGEMENT_FACTOR as usize) as f64,
-                        (i * ENLARGEMENT_FACTOR as usize) as f64,
-                        ENLARGEMENT_FACTOR as f64,
-                        ENLARGEMENT_FACTOR as f64,
-                    ];
-                    Rectangle::new(color::WHITE).draw(
-                        dimensions,
-                        &context.draw_state,
-                        context.transform,
-                        graphics,
-                    );
-                }
+/// The embedded ROM database with the user's database merged over it. The user
+/// database is the one given with --rom-db, or ~/.config/chip8/roms.yml if it exists.
+fn load_rom_database(arguments: &Arguments) -> RomDatabase {
+    let mut database = RomDatabase::embedded();
+
+    let user_database = match arguments.rom_db {
+        Some(ref path) => Some(PathBuf::from(path)),
+        None => default_rom_database_path().filter(|path| path.exists()),
+    };
+
+    if let Some(path) = user_database {
+        if let Err(e) = database.merge_file(&path) {
+            println!("Ignoring ROM database: {}", e);
+        }
+    }
+
+    database
+}
+
+fn default_rom_database_path() -> Option<PathBuf> {
+    config_path("roms.yml")
+}
+
+/// The cheats for a ROM from the file given with --cheats, or
+/// ~/.config/chip8/cheats.txt if it exists
+fn load_cheats(arguments: &Arguments, rom: &[u8]) -> Option<cheats::Cheats> {
+    let path = match arguments.cheats {
+        Some(ref path) => PathBuf::from(path),
+        None => config_path("cheats.txt").filter(|path| path.exists())?,
+    };
+    match cheats::load_cheats(&path, &metadata::rom_hash(rom)) {
+        Ok(cheats) => Some(cheats),
+        Err(e) => {
+            println!("Ignoring cheats: {}", e);
+            None
+        }
+    }
+}
+
+/// A file in the emulator's directory in the user's configuration
+fn config_path(name: &str) -> Option<PathBuf> {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config) => PathBuf::from(config),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("chip8").join(name))
+}
+
+fn print_rom_info(info: &RomInfo, keyboard: &Keyboard) {
+    if let Some(ref title) = info.title {
+        match info.author {
+            Some(ref author) => println!("{} by {}", title, author),
+            None => println!("{}", title),
+        }
+    }
+
+    if let Some(ref platform) = info.platform {
+        if Platform::from_name(platform).is_none() {
+            println!("This ROM was written for {} and may not run correctly.", platform);
+        }
+    }
+
+    if !info.keys.is_empty() {
+        println!("Controls:");
+        let mut keys = info.keys.clone();
+        keys.sort();
+        for (key, description) in keys {
+            println!("  {} ({:X}): {}", keyboard.key_label(key), key, description);
+        }
+    }
+}
+
+/// Finds an unused file name in the working directory based on the ROM name,
+/// e.g. "pong-1.png"
+fn capture_path(name: &str, extension: &str) -> PathBuf {
+    let mut number = 1;
+    loop {
+        let path = PathBuf::from(format!("{}-{}.{}", name, number, extension));
+        if !path.exists() {
+            return path;
+        }
+        number += 1;
+    }
+}
+
+fn save_screenshot(cpu: &Cpu, path: &Path, scale: u32) {
+    match capture::save_screenshot(&cpu.display.frame(), path, scale) {
+        Ok(_) => println!("Saved screenshot to {}", path.display()),
+        Err(e) => println!("Unable to save screenshot to {}: {}", path.display(), e),
+    }
+}
+
+fn start_recording(path: &Path, scale: u32) -> Option<Recorder> {
+    match Recorder::new(path, scale) {
+        Ok(recorder) => {
+            println!("Recording to {}", path.display());
+            Some(recorder)
+        }
+        Err(e) => {
+            println!("Unable to start recording to {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn stop_recording(recorder: Recorder) {
+    let path = recorder.path().to_path_buf();
+    match recorder.finish() {
+        Ok(_) => println!("Saved recording to {}", path.display()),
+        Err(e) => println!("Unable to save recording to {}: {}", path.display(), e),
+    }
+}
+
+/// Largest whole number scale at which the screen fits in the view, along
+/// with the offsets that center it. Keeping the scale a whole number keeps every
+/// CHIP-8 pixel the same size, and whatever space is left over is letterboxed.
+fn screen_layout(view: [f64; 2], columns: usize, rows: usize) -> (f64, f64, f64) {
+    let horizontal = (view[0] / columns as f64).floor();
+    let vertical = (view[1] / rows as f64).floor();
+    let scale = horizontal.min(vertical).max(1.0);
+
+    let x = ((view[0] - columns as f64 * scale) / 2.0).max(0.0).floor();
+    let y = ((view[1] - rows as f64 * scale) / 2.0).max(0.0).floor();
+
+    (scale, x, y)
+}
+
+/// Draws the screen letterboxed into the area of the given size at the top
+/// left, leaving the black pixels to the cleared window
+fn draw_screen(frame: &Frame, view: [f64; 2], context: &Context, graphics: &mut G2d) {
+    // Sized from the frame so that every resolution scales the same way
+    if frame.width == 0 || frame.height == 0 {
+        return;
+    }
+
+    let (scale, offset_x, offset_y) = screen_layout(view, frame.width, frame.height);
+
+    for (i, row) in frame.pixels.chunks(frame.width).enumerate() {
+        for (j, &pixel) in row.iter().enumerate() {
+            if pixel & 0x00FF_FFFF != 0 {
+                let dimensions = [
+                    offset_x + j as f64 * scale,
+                    offset_y + i as f64 * scale,
+                    scale,
+                    scale,
+                ];
+                Rectangle::new(rgb(pixel)).draw(
+                    dimensions,
+                    &context.draw_state,
+                    context.transform,
+                    graphics,
+                );
             }
         }
-    });
+    }
+}
+
+fn rgb(pixel: u32) -> [f32; 4] {
+    [
+        ((pixel >> 16) & 0xFF) as f32 / 255.0,
+        ((pixel >> 8) & 0xFF) as f32 / 255.0,
+        (pixel & 0xFF) as f32 / 255.0,
+        1.0,
+    ]
 }
 
 // TODO:
@@ -0,0 +1,274 @@
+pub const MEGA_WIDTH: usize = 256;
+pub const MEGA_HEIGHT: usize = 192;
+
+/// Opaque black, which the screen is cleared to
+pub const BLACK: u32 = 0xFF00_0000;
+/// Bytes of the header in front of a sound sample: the sample rate in two
+/// bytes, the length in three and one reserved byte
+const SAMPLE_HEADER_SIZE: usize = 6;
+
+/// How the colors of a sprite are combined with the screen under it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    /// The sprite covers 25% or 50% of the screen
+    Quarter,
+    Half,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn from_value(value: u8) -> Option<BlendMode> {
+        match value {
+            0 => Some(BlendMode::Normal),
+            1 => Some(BlendMode::Quarter),
+            2 => Some(BlendMode::Half),
+            3 => Some(BlendMode::Add),
+            4 => Some(BlendMode::Multiply),
+            _ => None,
+        }
+    }
+
+    fn blend(&self, screen: u8, sprite: u8) -> u8 {
+        let (screen, sprite) = (u32::from(screen), u32::from(sprite));
+        let blended = match *self {
+            BlendMode::Normal => sprite,
+            BlendMode::Quarter => (screen * 3 + sprite) / 4,
+            BlendMode::Half => (screen + sprite) / 2,
+            BlendMode::Add => (screen + sprite).min(0xFF),
+            BlendMode::Multiply => screen * sprite / 0xFF,
+        };
+        blended as u8
+    }
+}
+
+/// A sound sample the program started, which frontends can play
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    /// Where the 8 bit unsigned samples start in memory
+    pub address: usize,
+    pub rate: u32,
+    pub length: usize,
+    pub looping: bool,
+}
+
+impl Sample {
+    /// Reads the header of the sample at `address`, None when it does not
+    /// fit in memory
+    pub fn at(memory: &[u8], address: usize, looping: bool) -> Option<Sample> {
+        let header = memory.get(address..address + SAMPLE_HEADER_SIZE)?;
+        let rate = u32::from(header[0]) << 8 | u32::from(header[1]);
+        let length = (header[2] as usize) << 16 | (header[3] as usize) << 8 | header[4] as usize;
+        Some(Sample {
+            address: address + SAMPLE_HEADER_SIZE,
+            rate,
+            length: length.min(memory.len() - address - SAMPLE_HEADER_SIZE),
+            looping,
+        })
+    }
+}
+
+/// The 256x192 screen of the MEGA-CHIP, drawn with sprites of one palette
+/// color per byte. Drawing goes to a buffer that 00E0 shows before clearing
+/// it, so the picture is replaced a whole frame at a time.
+pub struct MegaScreen {
+    /// ARGB colors, where color 0 is transparent
+    palette: [u32; 256],
+    sprite_width: usize,
+    sprite_height: usize,
+    alpha: u8,
+    blend_mode: BlendMode,
+    collision_color: u8,
+    buffer: Vec<u32>,
+    /// The palette color last drawn at each pixel of the buffer
+    colors: Vec<u8>,
+    shown: Vec<u32>,
+}
+
+impl MegaScreen {
+    pub fn new() -> MegaScreen {
+        MegaScreen {
+            palette: [BLACK; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_color: 0,
+            buffer: vec![BLACK; MEGA_WIDTH * MEGA_HEIGHT],
+            colors: vec![0; MEGA_WIDTH * MEGA_HEIGHT],
+            shown: vec![BLACK; MEGA_WIDTH * MEGA_HEIGHT],
+        }
+    }
+
+    /// Sets palette colors from 1 on from four bytes of ARGB each
+    pub fn load_palette(&mut self, colors: &[u8]) {
+        for (color, bytes) in self.palette[1..].iter_mut().zip(colors.chunks(4)) {
+            if bytes.len() == 4 {
+                *color = bytes
+                    .iter()
+                    .fold(0, |color, &byte| color << 8 | u32::from(byte));
+            }
+        }
+    }
+
+    /// The width and height of sprites in pixels
+    pub fn sprite_size(&self) -> (usize, usize) {
+        (self.sprite_width, self.sprite_height)
+    }
+
+    /// Sets the width of sprites, where 0 means 256
+    pub fn set_sprite_width(&mut self, width: u8) {
+        self.sprite_width = if width == 0 { 256 } else { width as usize };
+    }
+
+    pub fn set_sprite_height(&mut self, height: u8) {
+        self.sprite_height = if height == 0 { 256 } else { height as usize };
+    }
+
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    pub fn set_collision_color(&mut self, color: u8) {
+        self.collision_color = color;
+    }
+
+    /// Draws a sprite of palette colors row by row, skipping color 0 and
+    /// cutting it off at the edges. Returns whether it drew over a pixel of
+    /// the collision color, where empty pixels never collide.
+    pub fn draw_sprite(&mut self, sprite: &[u8], x: usize, y: usize) -> bool {
+        let mut collided = false;
+        if self.sprite_width == 0 {
+            return collided;
+        }
+        for (row, colors) in sprite.chunks(self.sprite_width).enumerate() {
+            for (column, &color) in colors.iter().enumerate() {
+                let (pixel_x, pixel_y) = (x + column, y + row);
+                if color == 0 || pixel_x >= MEGA_WIDTH || pixel_y >= MEGA_HEIGHT {
+                    continue;
+                }
+                let pixel = pixel_y * MEGA_WIDTH + pixel_x;
+                let under = self.colors[pixel];
+                collided = collided || (under != 0 && under == self.collision_color);
+                self.colors[pixel] = color;
+                self.buffer[pixel] = self.blend(self.buffer[pixel], self.palette[color as usize]);
+            }
+        }
+        collided
+    }
+
+    /// Shows what was drawn and starts a new picture
+    pub fn show_and_clear(&mut self) {
+        self.shown.copy_from_slice(&self.buffer);
+        for pixel in self.buffer.iter_mut() {
+            *pixel = BLACK;
+        }
+        for color in self.colors.iter_mut() {
+            *color = 0;
+        }
+    }
+
+    /// The picture last shown, one ARGB color per pixel row by row
+    pub fn pixels(&self) -> &[u32] {
+        &self.shown
+    }
+
+    /// Blends every channel of a sprite color into the screen, and then
+    /// covers the screen with the result as far as the sprite and screen
+    /// alpha make it opaque
+    fn blend(&self, screen: u32, sprite: u32) -> u32 {
+        let opacity = (sprite >> 24) * u32::from(self.alpha) / 0xFF;
+        let mut color = BLACK;
+        for shift in [16, 8, 0].iter() {
+            let under = (screen >> shift) as u8;
+            let blended = u32::from(self.blend_mode.blend(under, (sprite >> shift) as u8));
+            let under = u32::from(under);
+            let mixed = (under * (0xFF - opacity) + blended * opacity) / 0xFF;
+            color |= mixed << shift;
+        }
+        color
+    }
+}
+
+impl Default for MegaScreen {
+    fn default() -> MegaScreen {
+        MegaScreen::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_with_palette() -> MegaScreen {
+        let mut screen = MegaScreen::new();
+        screen.load_palette(&[0xFF, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF]);
+        screen.set_sprite_width(2);
+        screen.set_sprite_height(2);
+        screen
+    }
+
+    #[test]
+    fn sprites_show_after_clearing() {
+        let mut screen = screen_with_palette();
+        assert!(!screen.draw_sprite(&[1, 0, 2, 1], 10, 20));
+        assert_eq!(BLACK, screen.pixels()[20 * MEGA_WIDTH + 10]);
+
+        screen.show_and_clear();
+        let pixels = screen.pixels();
+        assert_eq!(0xFFFF_0000, pixels[20 * MEGA_WIDTH + 10]);
+        assert_eq!(BLACK, pixels[20 * MEGA_WIDTH + 11]);
+        assert_eq!(0xFF00_00FF, pixels[21 * MEGA_WIDTH + 10]);
+    }
+
+    #[test]
+    fn sprites_collide_with_the_collision_color() {
+        let mut screen = screen_with_palette();
+        screen.set_collision_color(2);
+        assert!(!screen.draw_sprite(&[1, 2, 2, 2], 0, 0));
+        assert!(!screen.draw_sprite(&[2, 0, 0, 0], 0, 0));
+        assert!(screen.draw_sprite(&[1, 0, 0, 0], 0, 0));
+        // Sprites are cut off at the edges
+        assert!(!screen.draw_sprite(&[2, 2, 2, 2], MEGA_WIDTH - 1, MEGA_HEIGHT - 1));
+    }
+
+    #[test]
+    fn blend_modes_mix_with_the_screen() {
+        let mut screen = screen_with_palette();
+        screen.draw_sprite(&[1, 1, 1, 1], 0, 0);
+        screen.set_blend_mode(BlendMode::Half);
+        screen.draw_sprite(&[2], 0, 0);
+        screen.set_blend_mode(BlendMode::Add);
+        screen.draw_sprite(&[0, 2], 0, 0);
+        screen.set_blend_mode(BlendMode::Normal);
+        screen.set_alpha(0);
+        screen.draw_sprite(&[0, 0, 2], 0, 0);
+        screen.show_and_clear();
+
+        let pixels = screen.pixels();
+        assert_eq!(0xFF7F_007F, pixels[0]);
+        assert_eq!(0xFFFF_00FF, pixels[1]);
+        assert_eq!(0xFFFF_0000, pixels[MEGA_WIDTH]);
+    }
+
+    #[test]
+    fn samples_are_read_from_their_header() {
+        let mut memory = vec![0; 16];
+        memory[2..8].copy_from_slice(&[0x1F, 0x40, 0x00, 0x00, 0x20, 0x00]);
+        assert_eq!(
+            Some(Sample {
+                address: 8,
+                rate: 8000,
+                length: 8,
+                looping: true,
+            }),
+            Sample::at(&memory, 2, true)
+        );
+        assert_eq!(None, Sample::at(&memory, 12, false));
+    }
+}
@@ -0,0 +1,187 @@
+use quirks::Quirks;
+use sha1::Sha1;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+const EMBEDDED_DATABASE: &str = include_str!("../roms/metadata.yml");
+
+/// Everything known about a ROM, see roms/metadata.yml for the file format
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub platform: Option<String>,
+    pub instructions_per_frame: Option<u32>,
+    pub quirks: Quirks,
+    pub keys: Vec<(u8, String)>,
+}
+
+pub struct RomDatabase {
+    entries: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// The database built into the emulator
+    pub fn embedded() -> RomDatabase {
+        let mut database = RomDatabase {
+            entries: HashMap::new(),
+        };
+        database
+            .merge_str(EMBEDDED_DATABASE)
+            .expect("The embedded ROM database is invalid.");
+        database
+    }
+
+    /// Merges a user database over this one. Fields present in the file
+    /// replace the existing ones, everything else is kept.
+    pub fn merge_file(&mut self, path: &Path) -> Result<(), String> {
+        let mut source = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut source))
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+        self.merge_str(&source)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn merge_str(&mut self, source: &str) -> Result<(), String> {
+        let documents = YamlLoader::load_from_str(source).map_err(|e| e.to_string())?;
+        let root = match documents.into_iter().next() {
+            Some(root) => root,
+            None => return Ok(()),
+        };
+        let entries = match root {
+            Yaml::Hash(entries) => entries,
+            Yaml::Null => return Ok(()),
+            _ => return Err(String::from("Expected a map of ROM hashes")),
+        };
+
+        for (hash, fields) in entries {
+            let hash = match hash.as_str() {
+                Some(hash) => hash.to_lowercase(),
+                None => return Err(format!("Invalid ROM hash {:?}", hash)),
+            };
+            let info = self.entries.entry(hash.clone()).or_default();
+            merge_entry(info, &fields).map_err(|e| format!("{}: {}", hash, e))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.entries.get(&rom_hash(rom))
+    }
+}
+
+/// The key ROMs are stored under: the SHA-1 of their contents as lowercase hex
+pub fn rom_hash(rom: &[u8]) -> String {
+    Sha1::from(rom).digest().to_string()
+}
+
+fn merge_entry(info: &mut RomInfo, fields: &Yaml) -> Result<(), String> {
+    if let Some(title) = fields["title"].as_str() {
+        info.title = Some(String::from(title));
+    }
+    if let Some(author) = fields["author"].as_str() {
+        info.author = Some(String::from(author));
+    }
+    if let Some(platform) = fields["platform"].as_str() {
+        info.platform = Some(String::from(platform));
+    }
+
+    match fields["ipf"] {
+        Yaml::BadValue => {}
+        Yaml::Integer(ipf) if ipf > 0 => info.instructions_per_frame = Some(ipf as u32),
+        ref other => return Err(format!("Invalid ipf {:?}", other)),
+    }
+
+    if let Some(quirks) = fields["quirks"].as_hash() {
+        for (name, enabled) in quirks {
+            let name = name.as_str().unwrap_or("");
+            let enabled = enabled
+                .as_bool()
+                .ok_or_else(|| format!("Quirk '{}' must be true or false", name))?;
+            info.quirks.set(name, enabled)?;
+        }
+    }
+
+    if let Some(keys) = fields["keys"].as_hash() {
+        for (key, description) in keys {
+            let key = parse_key(key).ok_or_else(|| format!("Invalid key {:?}", key))?;
+            let description = description.as_str().unwrap_or("").to_string();
+            info.keys.retain(|&(existing, _)| existing != key);
+            info.keys.push((key, description));
+        }
+    }
+
+    Ok(())
+}
+
+/// A key of the hex keypad, written as a hex digit or a number from 0 to 15
+pub fn parse_key(key: &Yaml) -> Option<u8> {
+    let key = match *key {
+        Yaml::String(ref key) => u8::from_str_radix(key, 16).ok(),
+        Yaml::Integer(key) => u8::try_from(key).ok(),
+        _ => None,
+    };
+    key.filter(|&key| key <= 0xF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLINKY: &[u8] = include_bytes!("../roms/blinky.rom");
+
+    #[test]
+    fn embedded_database_knows_bundled_roms() {
+        let database = RomDatabase::embedded();
+        let info = database.lookup(BLINKY).unwrap();
+        assert_eq!(Some(String::from("Blinky")), info.title);
+        assert_eq!(Some(15), info.instructions_per_frame);
+        assert!(info.keys.contains(&(0x3, String::from("Up"))));
+    }
+
+    #[test]
+    fn user_entries_override_individual_fields() {
+        let mut database = RomDatabase::embedded();
+        let hash = rom_hash(BLINKY);
+        let user = format!(
+            "\"{}\":\n  ipf: 30\n  quirks:\n    load-store: true\n  keys:\n    \"3\": Climb\n",
+            hash
+        );
+        database.merge_str(&user).unwrap();
+
+        let info = database.lookup(BLINKY).unwrap();
+        assert_eq!(Some(String::from("Blinky")), info.title);
+        assert_eq!(Some(30), info.instructions_per_frame);
+        assert!(info.quirks.load_store_increments_index);
+        assert!(info.keys.contains(&(0x3, String::from("Climb"))));
+        assert!(info.keys.contains(&(0x6, String::from("Down"))));
+    }
+
+    #[test]
+    fn unknown_rom_has_no_entry() {
+        let database = RomDatabase::embedded();
+        assert_eq!(None, database.lookup(&[0x12, 0x00]));
+    }
+
+    #[test]
+    fn invalid_quirk_is_rejected() {
+        let mut database = RomDatabase::embedded();
+        let result = database.merge_str("\"abc\":\n  quirks:\n    warp-speed: true\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn out_of_range_keys_are_rejected() {
+        let mut database = RomDatabase::embedded();
+        assert!(database.merge_str("\"abc\":\n  keys:\n    256: Wrapped\n").is_err());
+        assert!(database.merge_str("\"abc\":\n  keys:\n    -255: Negative\n").is_err());
+        assert!(database.merge_str("\"abc\":\n  keys:\n    15: Fire\n").is_ok());
+    }
+}
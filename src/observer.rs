@@ -0,0 +1,32 @@
+use instruction::{Address, Register};
+
+/// Is told about what happens in the CPU as it happens, so that frontends and
+/// tools don't have to poll it. Every method does nothing by default, so an
+/// observer only implements the events it cares about.
+pub trait Observer {
+    /// A 60 Hz frame was completed, `frame` is the number of frames so far
+    fn frame(&mut self, _frame: u32) {}
+
+    /// A sprite was drawn at the given position, `collided` is whether it
+    /// turned off or covered a pixel
+    fn draw(&mut self, _x: usize, _y: usize, _collided: bool) {}
+
+    fn clear(&mut self) {}
+
+    /// The sound timer was set and the beep starts
+    fn sound_started(&mut self) {}
+
+    /// The sound timer ran out or was set to 0
+    fn sound_stopped(&mut self) {}
+
+    /// FX0A found no key held and waits for one to go into the register
+    fn key_wait_started(&mut self, _register: Register) {}
+
+    fn key_wait_ended(&mut self, _register: Register, _key: u8) {}
+
+    /// The CPU stopped at an opcode it does not support
+    fn fault(&mut self, _pc: Address, _opcode: u16) {}
+
+    /// The program wrote a byte of memory
+    fn memory_written(&mut self, _address: usize, _value: u8) {}
+}
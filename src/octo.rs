@@ -0,0 +1,495 @@
+//! An assembler for the core of the Octo language, enough to run the programs
+//! stored in Octo cartridges. Macros, `:calc`, `:next`, `:unpack` and the
+//! SCHIP and XO-CHIP instructions are not supported.
+
+use cpu::PROGRAM_START;
+use std::collections::HashMap;
+
+/// Assembles Octo source code into a ROM that starts with a jump to `main`
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let tokens = source
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split_whitespace())
+        .collect();
+
+    let mut assembler = Assembler {
+        tokens,
+        position: 0,
+        rom: Vec::new(),
+        here: PROGRAM_START,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+    };
+    assembler.jump_to_label(0x1, "main");
+    while assembler.position < assembler.tokens.len() {
+        assembler.statement()?;
+    }
+    assembler.finish()
+}
+
+enum Block {
+    /// `if ... begin`, with the address of the jump past the block
+    If(usize),
+    /// `else`, with the address of the jump past the else block
+    Else(usize),
+    /// `loop`, with its start and the addresses of the jumps out of its `while`s
+    Loop(usize, Vec<usize>),
+}
+
+/// The two ways of skipping over the instruction after a condition
+struct Condition {
+    skip_unless: u16,
+    skip_if: u16,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+    rom: Vec<u8>,
+    /// Address the next byte is written to
+    here: usize,
+    labels: HashMap<&'a str, usize>,
+    constants: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u8>,
+    /// Instructions waiting for the address of a label defined after them
+    fixups: Vec<(usize, &'a str)>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+        match token {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name, self.here).is_some() {
+                    return Err(format!("The label '{}' is defined twice", name));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                let value = self.value(value)?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":org" => {
+                let address = self.next()?;
+                self.here = self.address(address)?;
+            }
+            ":byte" => {
+                let value = self.next()?;
+                let value = self.byte(value)?;
+                self.emit(value);
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "clear" => self.instruction(0x00E0),
+            "return" | ";" => self.instruction(0x00EE),
+            "jump" => self.jump(0x1)?,
+            "jump0" => self.jump(0xB)?,
+            "native" => self.jump(0x0)?,
+            "i" => self.index()?,
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let low = if token == "delay" { 0x15 } else { 0x18 };
+                self.instruction(0xF000 | u16::from(x) << 8 | low);
+            }
+            "bcd" | "save" | "load" => {
+                let x = self.register()?;
+                let low = match token {
+                    "bcd" => 0x33,
+                    "save" => 0x55,
+                    _ => 0x65,
+                };
+                self.instruction(0xF000 | u16::from(x) << 8 | low);
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let rows = self.next()?;
+                let rows = self.value(rows)?;
+                if !(0..=0xF).contains(&rows) {
+                    return Err(format!("A sprite cannot be {} rows high", rows));
+                }
+                self.instruction(0xD000 | u16::from(x) << 8 | u16::from(y) << 4 | rows as u16);
+            }
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()? {
+                    "then" => self.instruction(condition.skip_unless),
+                    "begin" => {
+                        self.instruction(condition.skip_if);
+                        let jump = self.here;
+                        self.instruction(0x1000);
+                        self.blocks.push(Block::If(jump));
+                    }
+                    other => return Err(format!("Expected then or begin, found '{}'", other)),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If(jump)) => {
+                    let past_else = self.here;
+                    self.instruction(0x1000);
+                    let here = self.here;
+                    self.patch(jump, here);
+                    self.blocks.push(Block::Else(past_else));
+                }
+                _ => return Err(String::from("'else' without 'if ... begin'")),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If(jump)) | Some(Block::Else(jump)) => {
+                    let here = self.here;
+                    self.patch(jump, here);
+                }
+                _ => return Err(String::from("'end' without 'if ... begin'")),
+            },
+            "loop" => self.blocks.push(Block::Loop(self.here, Vec::new())),
+            "while" => {
+                let condition = self.condition()?;
+                self.instruction(condition.skip_if);
+                let jump = self.here;
+                self.instruction(0x1000);
+                match self.blocks.iter_mut().rev().find_map(|block| match *block {
+                    Block::Loop(_, ref mut exits) => Some(exits),
+                    _ => None,
+                }) {
+                    Some(exits) => exits.push(jump),
+                    None => return Err(String::from("'while' outside of a loop")),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop(start, exits)) => {
+                    self.instruction(0x1000 | start as u16);
+                    let here = self.here;
+                    for exit in exits {
+                        self.patch(exit, here);
+                    }
+                }
+                _ => return Err(String::from("'again' without 'loop'")),
+            },
+            _ if parse_register(token, &self.aliases).is_some() => {
+                self.position -= 1;
+                self.assignment()?;
+            }
+            _ if parse_number(token).is_some() || self.constants.contains_key(token) => {
+                let value = self.byte(token)?;
+                self.emit(value);
+            }
+            _ if token.starts_with(':') => {
+                return Err(format!("'{}' is not supported", token));
+            }
+            _ => self.jump_to_label(0x2, token),
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self) -> Result<(), String> {
+        let x = u16::from(self.register()?) << 8;
+        let operator = self.next()?;
+        let operand = self.next()?;
+
+        if let Some(y) = parse_register(operand, &self.aliases) {
+            let y = u16::from(y) << 4;
+            let low = match operator {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(format!("Unknown operator '{}'", operator)),
+            };
+            self.instruction(0x8000 | x | y | low);
+            return Ok(());
+        }
+
+        match (operator, operand) {
+            (":=", "delay") => self.instruction(0xF007 | x),
+            (":=", "key") => self.instruction(0xF00A | x),
+            (":=", "random") => {
+                let mask = self.next()?;
+                let mask = self.byte(mask)?;
+                self.instruction(0xC000 | x | u16::from(mask));
+            }
+            (":=", _) => {
+                let value = self.byte(operand)?;
+                self.instruction(0x6000 | x | u16::from(value));
+            }
+            ("+=", _) => {
+                let value = self.byte(operand)?;
+                self.instruction(0x7000 | x | u16::from(value));
+            }
+            ("-=", _) => {
+                let value = self.byte(operand)?.wrapping_neg();
+                self.instruction(0x7000 | x | u16::from(value));
+            }
+            _ => return Err(format!("'{}' cannot be used with '{}'", operator, operand)),
+        }
+        Ok(())
+    }
+
+    fn index(&mut self) -> Result<(), String> {
+        match self.next()? {
+            ":=" => {
+                let operand = self.next()?;
+                if operand == "hex" {
+                    let x = self.register()?;
+                    self.instruction(0xF029 | u16::from(x) << 8);
+                    Ok(())
+                } else {
+                    self.position -= 1;
+                    self.jump(0xA)
+                }
+            }
+            "+=" => {
+                let x = self.register()?;
+                self.instruction(0xF01E | u16::from(x) << 8);
+                Ok(())
+            }
+            other => Err(format!("Unknown operator '{}' for i", other)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = u16::from(self.register()?) << 8;
+        let operator = self.next()?;
+        let (skip_unless, skip_if) = match operator {
+            "key" => (0xE0A1 | x, 0xE09E | x),
+            "-key" => (0xE09E | x, 0xE0A1 | x),
+            "==" | "!=" => {
+                let operand = self.next()?;
+                let (unequal, equal) = match parse_register(operand, &self.aliases) {
+                    Some(y) => (
+                        0x9000 | x | u16::from(y) << 4,
+                        0x5000 | x | u16::from(y) << 4,
+                    ),
+                    None => {
+                        let value = u16::from(self.byte(operand)?);
+                        (0x4000 | x | value, 0x3000 | x | value)
+                    }
+                };
+                if operator == "==" {
+                    (unequal, equal)
+                } else {
+                    (equal, unequal)
+                }
+            }
+            _ => return Err(format!("The comparison '{}' is not supported", operator)),
+        };
+        Ok(Condition {
+            skip_unless,
+            skip_if,
+        })
+    }
+
+    /// An instruction taking a 12 bit address, which may be a label defined later
+    fn jump(&mut self, opcode: u16) -> Result<(), String> {
+        let target = self.next()?;
+        if parse_number(target).is_some() || self.constants.contains_key(target) {
+            let address = self.address(target)?;
+            self.instruction(opcode << 12 | address as u16);
+        } else {
+            self.jump_to_label(opcode, target);
+        }
+        Ok(())
+    }
+
+    fn jump_to_label(&mut self, opcode: u16, label: &'a str) {
+        self.fixups.push((self.here, label));
+        self.instruction(opcode << 12);
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, String> {
+        if !self.blocks.is_empty() {
+            return Err(String::from("A 'begin' or 'loop' is never closed"));
+        }
+        for (address, label) in self.fixups.clone() {
+            let target = *self
+                .labels
+                .get(label)
+                .ok_or_else(|| format!("The label '{}' is not defined", label))?;
+            self.patch(address, target);
+        }
+        Ok(self.rom)
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| String::from("Unexpected end of the program"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("Expected '{}', found '{}'", expected, token)),
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        parse_register(token, &self.aliases)
+            .ok_or_else(|| format!("Expected a register, found '{}'", token))
+    }
+
+    fn value(&self, token: &str) -> Result<i32, String> {
+        parse_number(token)
+            .or_else(|| self.constants.get(token).cloned())
+            .or_else(|| self.labels.get(token).map(|&address| address as i32))
+            .ok_or_else(|| format!("Expected a number, found '{}'", token))
+    }
+
+    fn byte(&self, token: &str) -> Result<u8, String> {
+        match self.value(token)? {
+            value @ -128..=255 => Ok(value as u8),
+            value => Err(format!("{} does not fit in a byte", value)),
+        }
+    }
+
+    fn address(&self, token: &str) -> Result<usize, String> {
+        match self.value(token)? {
+            value @ 0..=0xFFF if value as usize >= PROGRAM_START => Ok(value as usize),
+            value => Err(format!("0x{:X} is not an address in the program", value)),
+        }
+    }
+
+    fn instruction(&mut self, opcode: u16) {
+        self.emit((opcode >> 8) as u8);
+        self.emit(opcode as u8);
+    }
+
+    fn emit(&mut self, byte: u8) {
+        let offset = self.here - PROGRAM_START;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.here += 1;
+    }
+
+    /// Fills in the address of the instruction at `at`
+    fn patch(&mut self, at: usize, target: usize) {
+        let offset = at - PROGRAM_START;
+        self.rom[offset] |= (target >> 8) as u8 & 0xF;
+        self.rom[offset + 1] = target as u8;
+    }
+}
+
+fn parse_register(token: &str, aliases: &HashMap<&str, u8>) -> Option<u8> {
+    if let Some(&register) = aliases.get(token) {
+        return Some(register);
+    }
+    match token.strip_prefix('v').or_else(|| token.strip_prefix('V')) {
+        Some(digit) if digit.len() == 1 => u8::from_str_radix(digit, 16).ok(),
+        _ => None,
+    }
+}
+
+fn parse_number(token: &str) -> Option<i32> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()
+    } else {
+        digits.parse().ok()
+    }?;
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_starts_with_a_jump_to_main() {
+        let rom = assemble(": main\n  v0 := 5\n  loop again").unwrap();
+        assert_eq!(vec![0x12, 0x02, 0x60, 0x05, 0x12, 0x04], rom);
+    }
+
+    #[test]
+    fn labels_can_be_used_before_they_are_defined() {
+        let source = "
+            : main
+              i := digits   # data after the code
+              draw
+            : draw
+              sprite v0 v1 5
+              return
+            : digits
+              0x3C 0x42 -1
+        ";
+        let rom = assemble(source).unwrap();
+        assert_eq!(
+            vec![0x12, 0x02, 0xA2, 0x0A, 0x22, 0x06, 0xD0, 0x15, 0x00, 0xEE, 0x3C, 0x42, 0xFF],
+            rom
+        );
+    }
+
+    #[test]
+    fn conditions_skip_the_instruction_after_them() {
+        let source = "
+            : main
+              if v1 == 3 then v2 += 1
+              if v1 != v2 begin
+                v3 -= 1
+              else
+                v3 := key
+              end
+              loop
+                while vA -key
+                v4 <<= v4
+              again
+        ";
+        let rom = assemble(source).unwrap();
+        assert_eq!(
+            vec![
+                0x12, 0x02, // jump main
+                0x41, 0x03, 0x72, 0x01, // if v1 == 3 then v2 += 1
+                0x91, 0x20, 0x12, 0x0E, 0x73, 0xFF, 0x12,
+                0x10, // if v1 != v2 begin v3 -= 1 else
+                0xF3, 0x0A, // v3 := key end
+                0xEA, 0xA1, 0x12, 0x18, 0x84, 0x4E, 0x12,
+                0x10, // loop while vA -key v4 <<= v4 again
+            ],
+            rom
+        );
+    }
+
+    #[test]
+    fn constants_and_aliases_are_substituted() {
+        let rom = assemble(":const SPEED 3 :alias x vE : main x := SPEED").unwrap();
+        assert_eq!(vec![0x12, 0x02, 0x6E, 0x03], rom);
+    }
+
+    #[test]
+    fn undefined_label_is_an_error() {
+        assert!(assemble(": main jump nowhere").is_err());
+        assert!(assemble("clear").is_err());
+    }
+}
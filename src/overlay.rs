@@ -0,0 +1,149 @@
+use cpu::CpuState;
+use instruction::Instruction;
+use piston_window::{color, Context, G2d, Rectangle};
+use text::{self, ADVANCE, LINE_HEIGHT};
+
+/// Widest line of the panel in characters, a row of the memory view
+pub const PANEL_COLUMNS: usize = 28;
+/// Number of lines in the panel
+const PANEL_LINES: usize = 22;
+
+/// Return addresses shown from the top of the stack
+const STACK_ENTRIES: usize = 5;
+/// Instructions shown after the current one
+const NEXT_INSTRUCTIONS: u16 = 5;
+/// Bytes per row and rows of the memory view around I
+const MEMORY_COLUMNS: usize = 8;
+const MEMORY_ROWS: usize = 4;
+
+const BACKGROUND: [f32; 4] = [0.1, 0.1, 0.2, 1.0];
+
+/// Size of a font pixel that fits the panel in the given height
+pub fn font_size(height: f64) -> f64 {
+    (height / (PANEL_LINES as f64 * LINE_HEIGHT))
+        .floor()
+        .max(1.0)
+}
+
+/// Width of the panel in pixels for a font size
+pub fn panel_width(size: f64) -> f64 {
+    (PANEL_COLUMNS as f64 * ADVANCE + 4.0) * size
+}
+
+/// Draws the panel with its top left corner at `x` covering the full height
+pub fn draw_overlay(state: &CpuState, x: f64, context: &Context, graphics: &mut G2d) {
+    draw_panel(&overlay_lines(state), x, context, graphics);
+}
+
+/// Draws lines of at most `PANEL_COLUMNS` characters in the panel
+pub fn draw_panel(lines: &[String], x: f64, context: &Context, graphics: &mut G2d) {
+    let view = context.get_view_size();
+    let size = font_size(view[1]);
+    let background = [x, 0.0, panel_width(size), view[1]];
+    Rectangle::new(BACKGROUND).draw(background, &context.draw_state, context.transform, graphics);
+
+    for (line, content) in lines.iter().enumerate() {
+        let position = [
+            x + 2.0 * size,
+            2.0 * size + line as f64 * LINE_HEIGHT * size,
+        ];
+        text::draw_text(content, position, size, color::WHITE, context, graphics);
+    }
+}
+
+/// The text of the panel, one string per line
+pub fn overlay_lines(state: &CpuState) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    lines.push(format!(
+        "PC {:04X}  I {:04X}  SP {:X}",
+        state.pc, state.index, state.sp
+    ));
+    lines.push(format!(
+        "DT {:02X}  ST {:02X}  CYC {}",
+        state.delay_timer, state.sound_timer, state.tick
+    ));
+    for (row, values) in state.registers.chunks(4).enumerate() {
+        let registers: Vec<_> = values
+            .iter()
+            .enumerate()
+            .map(|(column, value)| format!("V{:X} {:02X}", row * 4 + column, value))
+            .collect();
+        lines.push(registers.join("  "));
+    }
+
+    // Only the innermost return addresses fit on the line
+    let depth = state.sp as usize;
+    let stack: Vec<_> = (1..=depth)
+        .skip(depth.saturating_sub(STACK_ENTRIES))
+        .filter_map(|level| state.stack.get(level))
+        .map(|address| format!("{:03X}", address))
+        .collect();
+    lines.push(format!("STACK {}", stack.join(" ")));
+
+    let keys: Vec<_> = (0..16)
+        .filter(|&key| state.keys[key])
+        .map(|key| format!("{:X}", key))
+        .collect();
+    lines.push(format!("KEYS {}", keys.join(" ")));
+
+    lines.push(String::new());
+    for offset in 0..=NEXT_INSTRUCTIONS {
+        let address = state.pc as usize + offset as usize * 2;
+        if address + 1 >= state.memory.len() {
+            break;
+        }
+        let raw = (state.memory[address] as u16) << 8 | state.memory[address + 1] as u16;
+        let marker = if offset == 0 { ">" } else { " " };
+        lines.push(format!(
+            "{}{:03X} {:04X} {}",
+            marker,
+            address,
+            raw,
+            Instruction::parse_for(raw, state.platform)
+        ));
+    }
+
+    lines.push(String::new());
+    let first = (state.index as usize & !(MEMORY_COLUMNS - 1))
+        .saturating_sub(MEMORY_COLUMNS)
+        .min(state.memory.len() - MEMORY_COLUMNS * MEMORY_ROWS);
+    for row in 0..MEMORY_ROWS {
+        let start = first + row * MEMORY_COLUMNS;
+        let bytes: Vec<_> = state.memory[start..start + MEMORY_COLUMNS]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        lines.push(format!("{:03X} {}", start, bytes.join(" ")));
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::Cpu;
+    use platform::Platform;
+
+    #[test]
+    fn panel_shows_registers_and_next_instructions() {
+        let mut cpu = Cpu::new(vec![0x6A, 0x02, 0xA3, 0x00, 0x12, 0x00], 360, false);
+        cpu.cycle();
+        let lines = overlay_lines(&cpu.state());
+
+        assert_eq!("PC 0202  I 0000  SP 0", lines[0]);
+        assert_eq!("V8 00  V9 00  VA 02  VB 00", lines[4]);
+        assert_eq!(">202 A300 LD I, 0x300", lines[9]);
+        assert_eq!(" 204 1200 JP 0x200", lines[10]);
+        assert!(lines.iter().all(|line| line.len() <= PANEL_COLUMNS));
+    }
+
+    #[test]
+    fn chip8x_instructions_are_shown_as_decoded_there() {
+        // LD V0, 2; COL V0, V1, 1
+        let cpu = Cpu::with_platform(vec![0x60, 0x02, 0xB0, 0x11], 360, false, Platform::Chip8X);
+        let lines = overlay_lines(&cpu.state());
+        assert_eq!(" 302 B011 COL V0, V1, 1", lines[10]);
+    }
+}
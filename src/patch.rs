@@ -0,0 +1,397 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+const BPS_HEADER: &[u8] = b"BPS1";
+/// The CRC32 of the source, the target and the patch itself
+const BPS_FOOTER_SIZE: usize = 12;
+
+/// Extensions of the patch files looked for next to a ROM
+pub const PATCH_EXTENSIONS: [&str; 2] = ["ips", "bps"];
+
+#[derive(Debug, PartialEq)]
+pub enum PatchError {
+    /// Neither an IPS nor a BPS patch
+    UnknownFormat,
+    /// The patch ends in the middle of a record
+    Truncated,
+    /// A BPS patch made for a ROM of another size, the expected and actual size
+    SourceSize(usize, usize),
+    /// The CRC32 of the ROM, the patched ROM or the patch itself is not the
+    /// one stored in a BPS patch, the expected and actual checksum
+    SourceChecksum(u32, u32),
+    TargetChecksum(u32, u32),
+    PatchChecksum(u32, u32),
+    /// A BPS patch reads or writes outside of the ROM
+    OutOfBounds,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::UnknownFormat => write!(f, "The file is not an IPS or BPS patch"),
+            PatchError::Truncated => write!(f, "The patch is truncated"),
+            PatchError::SourceSize(expected, actual) => write!(
+                f,
+                "The patch is for a ROM of {} bytes but the ROM is {} bytes",
+                expected, actual
+            ),
+            PatchError::SourceChecksum(expected, actual) => write!(
+                f,
+                "The patch is for a ROM with CRC32 {:08x} but the ROM has {:08x}",
+                expected, actual
+            ),
+            PatchError::TargetChecksum(expected, actual) => write!(
+                f,
+                "The patched ROM should have CRC32 {:08x} but has {:08x}",
+                expected, actual
+            ),
+            PatchError::PatchChecksum(expected, actual) => write!(
+                f,
+                "The patch should have CRC32 {:08x} but has {:08x}, it may be corrupted",
+                expected, actual
+            ),
+            PatchError::OutOfBounds => write!(f, "The patch refers to data outside of the ROM"),
+        }
+    }
+}
+
+/// Applies an IPS or BPS patch to a ROM, telling them apart by their header
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(IPS_HEADER) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_HEADER) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+/// The patch file next to a ROM with the same name, e.g. "pong.ips" for
+/// "pong.ch8"
+pub fn find_patch(rom: &Path) -> Option<PathBuf> {
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| rom.with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+/// IPS patches are records of a 3 byte offset and 2 byte size followed by
+/// as many bytes, or with a size of 0 by a 2 byte count and a byte to repeat,
+/// up to "EOF" and an optional 3 byte size to truncate the ROM to
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut target = rom.to_vec();
+    let mut reader = Reader::new(&patch[IPS_HEADER.len()..]);
+    loop {
+        if reader.remaining().starts_with(IPS_FOOTER) {
+            reader.take(IPS_FOOTER.len())?;
+            break;
+        }
+        let offset = reader.big_endian(3)?;
+        let size = reader.big_endian(2)?;
+        let (size, bytes) = if size == 0 {
+            let count = reader.big_endian(2)?;
+            (count, vec![reader.byte()?; count])
+        } else {
+            (size, reader.take(size)?.to_vec())
+        };
+        if target.len() < offset + size {
+            target.resize(offset + size, 0);
+        }
+        target[offset..offset + size].copy_from_slice(&bytes);
+    }
+    if reader.remaining().len() >= 3 {
+        let size = reader.big_endian(3)?;
+        target.truncate(size);
+    }
+    Ok(target)
+}
+
+/// BPS patches describe the patched ROM as a list of actions copying from
+/// the ROM, the patch or what was already written, and carry the CRC32 of
+/// all three
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.len() < BPS_HEADER.len() + BPS_FOOTER_SIZE {
+        return Err(PatchError::Truncated);
+    }
+    let footer = &patch[patch.len() - BPS_FOOTER_SIZE..];
+    let checksum = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .rev()
+            .fold(0u32, |value, &byte| value << 8 | u32::from(byte))
+    };
+    let (source_crc, target_crc, patch_crc) = (
+        checksum(&footer[0..4]),
+        checksum(&footer[4..8]),
+        checksum(&footer[8..12]),
+    );
+    let actual = crc32(&patch[..patch.len() - 4]);
+    if actual != patch_crc {
+        return Err(PatchError::PatchChecksum(patch_crc, actual));
+    }
+
+    let mut reader = Reader::new(&patch[BPS_HEADER.len()..patch.len() - BPS_FOOTER_SIZE]);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.take(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(PatchError::SourceSize(source_size, rom.len()));
+    }
+    let actual = crc32(rom);
+    if actual != source_crc {
+        return Err(PatchError::SourceChecksum(source_crc, actual));
+    }
+
+    let mut target = Vec::new();
+    let (mut source_offset, mut target_offset) = (0i64, 0i64);
+    while !reader.remaining().is_empty() {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        if length > target_size - target.len() {
+            return Err(PatchError::OutOfBounds);
+        }
+        match action & 3 {
+            // Source read, the ROM's bytes at the same place
+            0 => {
+                let start = target.len();
+                let bytes = rom
+                    .get(start..start + length)
+                    .ok_or(PatchError::OutOfBounds)?;
+                target.extend_from_slice(bytes);
+            }
+            // Target read, bytes stored in the patch
+            1 => target.extend_from_slice(reader.take(length)?),
+            // Source copy, the ROM's bytes at a relative offset
+            2 => {
+                source_offset = source_offset
+                    .checked_add(reader.offset()?)
+                    .ok_or(PatchError::OutOfBounds)?;
+                let start = usize_offset(source_offset)?;
+                let bytes = rom
+                    .get(start..start + length)
+                    .ok_or(PatchError::OutOfBounds)?;
+                target.extend_from_slice(bytes);
+                source_offset = source_offset
+                    .checked_add(length as i64)
+                    .ok_or(PatchError::OutOfBounds)?;
+            }
+            // Target copy, bytes already written, which can overlap with the
+            // ones being written to repeat a pattern
+            _ => {
+                target_offset = target_offset
+                    .checked_add(reader.offset()?)
+                    .ok_or(PatchError::OutOfBounds)?;
+                let start = usize_offset(target_offset)?;
+                if start >= target.len() {
+                    return Err(PatchError::OutOfBounds);
+                }
+                for index in start..start + length {
+                    let byte = target[index];
+                    target.push(byte);
+                }
+                target_offset = target_offset
+                    .checked_add(length as i64)
+                    .ok_or(PatchError::OutOfBounds)?;
+            }
+        }
+    }
+
+    let actual = crc32(&target);
+    if target.len() != target_size || actual != target_crc {
+        return Err(PatchError::TargetChecksum(target_crc, actual));
+    }
+    Ok(target)
+}
+
+fn usize_offset(offset: i64) -> Result<usize, PatchError> {
+    if offset < 0 {
+        return Err(PatchError::OutOfBounds);
+    }
+    Ok(offset as usize)
+}
+
+/// The CRC32 used by zip and BPS, computed a bit at a time as ROMs are small
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], PatchError> {
+        if count > self.bytes.len() {
+            return Err(PatchError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn big_endian(&mut self, size: usize) -> Result<usize, PatchError> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0, |value, &byte| value << 8 | byte as usize))
+    }
+
+    /// A BPS number, 7 bits per byte with the last byte marked by the high
+    /// bit, where every byte but the first also adds one to skip encodings
+    /// of the same number
+    fn number(&mut self) -> Result<usize, PatchError> {
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.byte()?;
+            value = value
+                .checked_add((byte & 0x7F) as usize * shift)
+                .ok_or(PatchError::OutOfBounds)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::OutOfBounds)?;
+            value = value.checked_add(shift).ok_or(PatchError::OutOfBounds)?;
+        }
+    }
+
+    /// A BPS relative offset, a number with the sign in its lowest bit
+    fn offset(&mut self) -> Result<i64, PatchError> {
+        let number = self.number()?;
+        let magnitude = (number >> 1) as i64;
+        Ok(if number & 1 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes a BPS number, the inverse of `Reader::number`
+    fn bps_number(mut value: usize, patch: &mut Vec<u8>) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                patch.push(byte | 0x80);
+                return;
+            }
+            patch.push(byte);
+            value -= 1;
+        }
+    }
+
+    /// A BPS patch turning the source into the target with the given actions,
+    /// which are encoded as they are
+    fn bps_patch(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = BPS_HEADER.to_vec();
+        bps_number(source.len(), &mut patch);
+        bps_number(target.len(), &mut patch);
+        bps_number(0, &mut patch);
+        patch.extend_from_slice(actions);
+        for &checksum in [crc32(source), crc32(target)].iter() {
+            patch.extend_from_slice(&checksum.to_le_bytes());
+        }
+        let checksum = crc32(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn checksums_match_zip() {
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+        assert_eq!(0, crc32(&[]));
+    }
+
+    #[test]
+    fn ips_records_are_written_over_the_rom() {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        let mut patch = b"PATCH".to_vec();
+        // Two bytes at 1, then four copies of 0xAA at 3
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0x11, 0x22]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0xAA]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(
+            Ok(vec![0x00, 0x11, 0x22, 0xAA, 0xAA, 0xAA, 0xAA]),
+            apply(&rom, &patch)
+        );
+
+        // Truncated to 2 bytes after the end
+        patch.extend_from_slice(&[0x00, 0x00, 0x02]);
+        assert_eq!(Ok(vec![0x00, 0x11]), apply(&rom, &patch));
+
+        assert_eq!(
+            Err(PatchError::Truncated),
+            apply(&rom, &patch[..patch.len() - 8])
+        );
+        assert_eq!(Err(PatchError::UnknownFormat), apply(&rom, b"PK"));
+    }
+
+    #[test]
+    fn bps_actions_build_the_target() {
+        let source = [0x60, 0x01, 0x12, 0x00];
+        let target = [0x60, 0x07, 0x60, 0x07, 0x60, 0x07, 0x12, 0x00];
+        // Each action is its length - 1 shifted left by 2 and its command,
+        // with the high bit marking the last byte of a number
+        let actions = [
+            // Source read of 1 byte, target read of 1 byte
+            0x80, 0x81, 0x07,
+            // Target copy of 4 bytes from 0, source copy of 2 bytes from +2
+            0x8F, 0x80, 0x86, 0x84,
+        ];
+        let patch = bps_patch(&source, &target, &actions);
+        assert_eq!(Ok(target.to_vec()), apply(&source, &patch));
+
+        let mut other = source;
+        other[3] = 0xFF;
+        match apply(&other, &patch) {
+            Err(PatchError::SourceChecksum(expected, _)) => assert_eq!(crc32(&source), expected),
+            other => panic!("Expected a source checksum error, got {:?}", other),
+        }
+        assert_eq!(
+            Err(PatchError::SourceSize(4, 3)),
+            apply(&source[..3], &patch)
+        );
+
+        let mut corrupted = patch.clone();
+        corrupted[8] ^= 0xFF;
+        match apply(&source, &corrupted) {
+            Err(PatchError::PatchChecksum(_, _)) => {}
+            other => panic!("Expected a patch checksum error, got {:?}", other),
+        }
+
+        // Source copies of 1 byte from 0, then from as far ahead as an
+        // offset goes
+        let mut actions = vec![0x82, 0x80, 0x82];
+        bps_number((i64::MAX as usize) << 1, &mut actions);
+        let patch = bps_patch(&source, &source[..2], &actions);
+        assert_eq!(Err(PatchError::OutOfBounds), apply(&source, &patch));
+    }
+}
@@ -0,0 +1,86 @@
+use cpu::{MEMORY_SIZE, PROGRAM_START};
+
+/// Where programs for the CHIP-8X start, after its larger interpreter
+const CHIP8X_PROGRAM_START: usize = 0x300;
+/// The MEGA-CHIP reaches 32 MB through its 24 bit index register
+const MEGACHIP_MEMORY_SIZE: usize = 32 * 1024 * 1024;
+/// Hi-res programs start with a jump to the extension of the interpreter
+/// at 0x260
+const HIRES_SIGNATURE: [u8; 2] = [0x12, 0x60];
+
+/// The CHIP-8 variant a ROM is written for, which decides where it is loaded
+/// and what some opcodes mean
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    Chip8,
+    /// The hi-res CHIP-8 for the COSMAC VIP, with a two-page display of
+    /// 64x64 pixels
+    HiresChip8,
+    /// The COSMAC VIP CHIP-8X, with a color board, a second keypad and a
+    /// sound output port
+    Chip8X,
+    /// The MEGA-CHIP, which can switch to a 256x192 screen of 8 bit color
+    /// sprites and play sound samples
+    MegaChip,
+}
+
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match name {
+            "chip8" => Some(Platform::Chip8),
+            "hires" => Some(Platform::HiresChip8),
+            "chip8x" => Some(Platform::Chip8X),
+            "megachip" => Some(Platform::MegaChip),
+            _ => None,
+        }
+    }
+
+    /// The address the ROM is loaded at and execution starts from
+    pub fn program_start(&self) -> usize {
+        match *self {
+            Platform::Chip8 | Platform::HiresChip8 | Platform::MegaChip => PROGRAM_START,
+            Platform::Chip8X => CHIP8X_PROGRAM_START,
+        }
+    }
+
+    /// The platform a ROM looks like it is written for, which can only tell
+    /// hi-res programs from others
+    pub fn detect(rom: &[u8]) -> Platform {
+        if rom.starts_with(&HIRES_SIGNATURE) {
+            Platform::HiresChip8
+        } else {
+            Platform::Chip8
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match *self {
+            Platform::Chip8 | Platform::HiresChip8 | Platform::Chip8X => MEMORY_SIZE,
+            Platform::MegaChip => MEGACHIP_MEMORY_SIZE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platforms_are_named_and_start_at_their_own_address() {
+        assert_eq!(Some(Platform::Chip8X), Platform::from_name("chip8x"));
+        assert_eq!(None, Platform::from_name("schip"));
+        assert_eq!(0x200, Platform::Chip8.program_start());
+        assert_eq!(0x300, Platform::Chip8X.program_start());
+        assert_eq!(Some(Platform::MegaChip), Platform::from_name("megachip"));
+        assert_eq!(0x200, Platform::MegaChip.program_start());
+        assert_eq!(0x2000000, Platform::MegaChip.memory_size());
+        assert_eq!(Some(Platform::HiresChip8), Platform::from_name("hires"));
+    }
+
+    #[test]
+    fn hires_programs_are_detected() {
+        assert_eq!(Platform::HiresChip8, Platform::detect(&[0x12, 0x60, 0x00]));
+        assert_eq!(Platform::Chip8, Platform::detect(&[0x12, 0x06]));
+        assert_eq!(Platform::Chip8, Platform::detect(&[]));
+    }
+}
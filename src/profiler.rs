@@ -0,0 +1,309 @@
+use instruction::{Address, Instruction};
+use platform::Platform;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::mem::Discriminant;
+use std::path::Path;
+
+/// Rows of the hotspot table in the report
+const HOTSPOTS: usize = 20;
+
+/// Counts where cycles are spent: per address, per instruction and per
+/// subroutine, following CALL and RET to know which subroutine is running
+pub struct Profiler {
+    cycles: u64,
+    /// Executions and the last opcode seen at every address
+    addresses: Vec<(u64, u16)>,
+    /// Executions per instruction variant, with an example to name it by
+    instructions: HashMap<Discriminant<Instruction>, (u64, Instruction)>,
+    /// Entry points of the subroutines currently being run, outermost first.
+    /// The program itself counts as the outermost subroutine.
+    stack: Vec<Address>,
+    /// The cycle each active subroutine was called at
+    call_cycles: Vec<u64>,
+    /// Cycles spent with each distinct stack, the key into this being kept
+    /// for the current stack so that it is only looked up on calls and returns
+    stacks: HashMap<Vec<Address>, usize>,
+    stack_cycles: Vec<u64>,
+    current_stack: usize,
+    subroutines: HashMap<Address, SubroutineTiming>,
+    platform: Platform,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SubroutineTiming {
+    pub calls: u64,
+    /// Cycles from the call until the return, including nested calls
+    pub inclusive_cycles: u64,
+}
+
+impl Profiler {
+    /// A profiler for a ROM loaded and decoded as on `platform`
+    pub fn new(platform: Platform) -> Profiler {
+        let mut profiler = Profiler {
+            cycles: 0,
+            // The program counter only reaches the first 64 KB of memory
+            addresses: vec![(0, 0); platform.memory_size().min(Address::MAX as usize + 1)],
+            instructions: HashMap::new(),
+            stack: Vec::new(),
+            call_cycles: Vec::new(),
+            stacks: HashMap::new(),
+            stack_cycles: Vec::new(),
+            current_stack: 0,
+            subroutines: HashMap::new(),
+            platform,
+        };
+        profiler.restart();
+        profiler
+    }
+
+    /// Starts over at the beginning of the program, e.g. after a reset.
+    /// Calls that were in progress are dropped, the counts are kept.
+    pub fn restart(&mut self) {
+        self.stack = vec![self.platform.program_start() as Address];
+        self.call_cycles = vec![self.cycles];
+        self.enter_stack();
+    }
+
+    /// Counts an instruction about to be executed at `pc`, taking `cost` cycles
+    pub fn record(&mut self, pc: Address, opcode: u16, instruction: &Instruction, cost: u64) {
+        self.cycles += cost;
+        if let Some(address) = self.addresses.get_mut(pc as usize) {
+            address.0 += 1;
+            address.1 = opcode;
+        }
+        self.instructions
+            .entry(mem::discriminant(instruction))
+            .or_insert((0, *instruction))
+            .0 += 1;
+        self.stack_cycles[self.current_stack] += cost;
+
+        match *instruction {
+            Instruction::Call(address) => {
+                self.stack.push(address);
+                self.call_cycles.push(self.cycles);
+                self.enter_stack();
+            }
+            // Returning from the program itself is left to the CPU to fault on
+            Instruction::Return if self.stack.len() > 1 => {
+                let address = self.stack.pop().unwrap_or_default();
+                let called = self.call_cycles.pop().unwrap_or_default();
+                let timing = self.subroutines.entry(address).or_default();
+                timing.calls += 1;
+                timing.inclusive_cycles += self.cycles - called;
+                self.enter_stack();
+            }
+            _ => {}
+        }
+    }
+
+    fn enter_stack(&mut self) {
+        let next = self.stack_cycles.len();
+        let index = *self.stacks.entry(self.stack.clone()).or_insert(next);
+        if index == next {
+            self.stack_cycles.push(0);
+        }
+        self.current_stack = index;
+    }
+
+    /// Cycles spent in each subroutine itself, not counting the ones it called
+    fn self_cycles(&self) -> HashMap<Address, u64> {
+        let mut cycles = HashMap::new();
+        for (stack, &index) in self.stacks.iter() {
+            if let Some(&address) = stack.last() {
+                *cycles.entry(address).or_insert(0) += self.stack_cycles[index];
+            }
+        }
+        cycles
+    }
+
+    /// Writes the text report to `path` and the folded stacks next to it with
+    /// ".folded" appended to the name
+    pub fn write_reports(&self, path: &Path) -> io::Result<()> {
+        let mut report = BufWriter::new(File::create(path)?);
+        self.write_report(&mut report)?;
+        report.flush()?;
+
+        let mut folded_path = path.as_os_str().to_os_string();
+        folded_path.push(".folded");
+        let mut folded = BufWriter::new(File::create(folded_path)?);
+        self.write_folded(&mut folded)?;
+        folded.flush()
+    }
+
+    pub fn write_report<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let total = self.cycles.max(1) as f64;
+        writeln!(out, "Total cycles: {}", self.cycles)?;
+
+        writeln!(out)?;
+        writeln!(out, "Hottest addresses")?;
+        writeln!(out, "  ADDR        COUNT       %  OPCODE INSTRUCTION")?;
+        let mut addresses: Vec<_> = self
+            .addresses
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(count, _))| count > 0)
+            .collect();
+        addresses.sort_by_key(|&(address, &(count, _))| (Reverse(count), address));
+        for (address, &(count, opcode)) in addresses.into_iter().take(HOTSPOTS) {
+            writeln!(
+                out,
+                "{:>6} {:>12} {:>6.2}%  {:04X}   {}",
+                format!("{:03X}", address),
+                count,
+                count as f64 * 100.0 / total,
+                opcode,
+                Instruction::parse_for(opcode, self.platform)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "Instructions")?;
+        writeln!(out, "INSTRUCTION               COUNT       %")?;
+        let mut instructions: Vec<_> = self.instructions.values().collect();
+        instructions.sort_by_key(|&&(count, _)| Reverse(count));
+        for &(count, ref example) in instructions {
+            writeln!(
+                out,
+                "{:<18} {:>12} {:>6.2}%",
+                variant_name(example),
+                count,
+                count as f64 * 100.0 / total
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "Subroutines")?;
+        writeln!(out, "  ADDR    CALLS    INCLUSIVE         SELF  SELF %")?;
+        let self_cycles = self.self_cycles();
+        let mut subroutines: Vec<_> = self_cycles.keys().cloned().collect();
+        subroutines.sort_by_key(|address| {
+            let timing = self.subroutines.get(address).cloned().unwrap_or_default();
+            (Reverse(timing.inclusive_cycles), *address)
+        });
+        for address in subroutines {
+            let timing = self.subroutines.get(&address).cloned().unwrap_or_default();
+            let own = self_cycles[&address];
+            let name = if address as usize == self.platform.program_start() {
+                String::from("main")
+            } else {
+                format!("{:03X}", address)
+            };
+            writeln!(
+                out,
+                "{:>6} {:>8} {:>12} {:>12} {:>6.2}%",
+                name,
+                timing.calls,
+                timing.inclusive_cycles,
+                own,
+                own as f64 * 100.0 / total
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes one line per call stack with the cycles spent in it, the format
+    /// read by flamegraph.pl and compatible tools
+    pub fn write_folded<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .filter(|&(_, &index)| self.stack_cycles[index] > 0)
+            .map(|(stack, &index)| {
+                let frames: Vec<_> = stack
+                    .iter()
+                    .enumerate()
+                    .map(|(depth, address)| {
+                        if depth == 0 {
+                            String::from("main")
+                        } else {
+                            format!("sub_{:03X}", address)
+                        }
+                    })
+                    .collect();
+                (frames.join(";"), self.stack_cycles[index])
+            })
+            .collect();
+        lines.sort();
+        for (frames, cycles) in lines {
+            writeln!(out, "{} {}", frames, cycles)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new(Platform::Chip8)
+    }
+}
+
+/// The name of an instruction's enum variant, e.g. "Draw"
+fn variant_name(instruction: &Instruction) -> String {
+    let debug = format!("{:?}", instruction);
+    debug.split('(').next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(profiler: &mut Profiler, pc: Address, opcode: u16) {
+        profiler.record(pc, opcode, &Instruction::parse(opcode), 1);
+    }
+
+    /// main calls 0x300 which calls 0x400
+    fn nested_calls() -> Profiler {
+        let mut profiler = Profiler::default();
+        record(&mut profiler, 0x200, 0x6001);
+        record(&mut profiler, 0x202, 0x2300);
+        record(&mut profiler, 0x300, 0x7001);
+        record(&mut profiler, 0x302, 0x2400);
+        record(&mut profiler, 0x400, 0x7001);
+        record(&mut profiler, 0x402, 0x00EE);
+        record(&mut profiler, 0x304, 0x00EE);
+        record(&mut profiler, 0x204, 0x1204);
+        profiler
+    }
+
+    #[test]
+    fn cycles_are_attributed_to_subroutines() {
+        let profiler = nested_calls();
+
+        let outer = profiler.subroutines[&0x300];
+        assert_eq!(1, outer.calls);
+        assert_eq!(5, outer.inclusive_cycles);
+        let inner = profiler.subroutines[&0x400];
+        assert_eq!(2, inner.inclusive_cycles);
+
+        let self_cycles = profiler.self_cycles();
+        assert_eq!(3, self_cycles[&0x200]);
+        assert_eq!(3, self_cycles[&0x300]);
+        assert_eq!(2, self_cycles[&0x400]);
+    }
+
+    #[test]
+    fn folded_stacks_are_written() {
+        let mut folded = Vec::new();
+        nested_calls().write_folded(&mut folded).unwrap();
+        assert_eq!(
+            "main 3\nmain;sub_300 3\nmain;sub_300;sub_400 2\n",
+            String::from_utf8(folded).unwrap()
+        );
+    }
+
+    #[test]
+    fn report_lists_hotspots_and_instructions() {
+        let mut report = Vec::new();
+        nested_calls().write_report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.starts_with("Total cycles: 8\n"));
+        assert!(report.contains("AddVal"));
+        assert!(report.contains("   202            1  12.50%  2300   CALL 0x300"));
+    }
+}
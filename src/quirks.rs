@@ -0,0 +1,39 @@
+/// Behaviours that differ between CHIP-8 interpreters. Many ROMs were written
+/// against a particular interpreter and only run correctly with its quirks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY and store the result in VX, like the original
+    /// COSMAC VIP interpreter, instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    /// FX55 and FX65 leave I pointing after the last register accessed
+    pub load_store_increments_index: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub logic_resets_vf: bool,
+    /// Sprites are cut off at the edges of the screen instead of wrapping around
+    pub clip_sprites: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_index: false,
+            logic_resets_vf: false,
+            clip_sprites: true,
+        }
+    }
+}
+
+impl Quirks {
+    /// Enables or disables a quirk by the name used in ROM metadata files
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match name {
+            "shift" => self.shift_uses_vy = enabled,
+            "load-store" => self.load_store_increments_index = enabled,
+            "vf-reset" => self.logic_resets_vf = enabled,
+            "clip" => self.clip_sprites = enabled,
+            _ => return Err(format!("Unknown quirk '{}'", name)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+use cfg::{Block, ControlFlowGraph};
+use display::HEIGHT;
+use instruction::{Address, Instruction, Register};
+use platform::Platform;
+use std::fmt::Write;
+use timing;
+
+const INSTRUCTION_SIZE: Address = 2;
+
+/// Translates the blocks of a ROM's control-flow graph into a Rust source
+/// file with a `run` function that executes them directly on a `Cpu`,
+/// without fetching or decoding. Code that was not found statically, such as
+/// the targets of computed jumps, and blocks that the program wrote to are
+/// left to the interpreter. The code runs on a `Cpu` of the same platform.
+pub fn recompile(rom: &[u8], name: &str, platform: Platform) -> String {
+    let graph = ControlFlowGraph::build(rom, platform);
+    let mut source = String::new();
+
+    // Writing to a String cannot fail
+    let _ = writeln!(
+        source,
+        "// Recompiled from {} by `chip8 --recompile`, do not edit",
+        name
+    );
+    source.push_str(
+        "
+use chip8::cpu::Cpu;
+use chip8::instruction::Instruction::*;
+
+/// Runs up to `budget` instructions and returns how many were run. Code that
+/// was not found ahead of time or was modified since is left to the
+/// interpreter.
+pub fn run(cpu: &mut Cpu, budget: u32) -> u32 {
+    let mut executed = 0;
+    while executed < budget && !cpu.faulted {
+        let remaining = budget - executed;
+        let ran = match cpu.pc() {
+",
+    );
+    for &start in graph.blocks.keys() {
+        let _ = writeln!(
+            source,
+            "            0x{:03X} => block_{:03x}(cpu, remaining),",
+            start, start
+        );
+    }
+    source.push_str(
+        "            _ => 0,
+        };
+        if ran > 0 {
+            executed += ran;
+        } else {
+            cpu.cycle();
+            executed += 1;
+        }
+    }
+    executed
+}
+",
+    );
+
+    for block in graph.blocks.values() {
+        let count = block.instructions.len();
+        let _ = write!(
+            source,
+            "
+fn block_{:03x}(cpu: &mut Cpu, remaining: u32) -> u32 {{
+    if remaining < {} || cpu.code_modified(0x{:03X}, 0x{:03X}) {{
+        return 0;
+    }}
+",
+            block.start,
+            count,
+            block.start,
+            block.end()
+        );
+        recompile_block(&mut source, block, platform);
+        let _ = writeln!(source, "    {}\n}}", count);
+    }
+
+    source
+}
+
+/// Writes the body of a block's function. Instructions that only change the
+/// registers, the index or the program counter are written out in Rust, the
+/// others are handed to `Cpu::execute`. The program counter and the timers
+/// are only brought up to date before those, and at the end of the block.
+fn recompile_block(source: &mut String, block: &Block, platform: Platform) {
+    // Instructions run since the timers were last brought up to date, and
+    // the VIP machine cycles they took
+    let mut unretired = 0;
+    let mut unretired_cycles = 0;
+    // Whether the program counter still points at an earlier instruction
+    let mut stale_pc = false;
+
+    for (position, &(address, opcode)) in block.instructions.iter().enumerate() {
+        let instruction = Instruction::parse_for(opcode, platform);
+        let next = address + INSTRUCTION_SIZE;
+
+        if let Some(code) = translate(&instruction, address) {
+            let _ = writeln!(source, "    // {}", instruction);
+            source.push_str(&code);
+            unretired += 1;
+            // None of the instructions written in Rust take a time that
+            // depends on the registers or the screen
+            unretired_cycles += timing::vip_cycles(&instruction, &[0; 16], HEIGHT, true);
+            stale_pc = !sets_pc(&instruction);
+            continue;
+        }
+
+        if stale_pc {
+            let _ = writeln!(source, "    cpu.poke_pc(0x{:03X});", address);
+            stale_pc = false;
+        }
+        if unretired > 0 {
+            let _ = writeln!(
+                source,
+                "    cpu.retire({}, {});",
+                unretired, unretired_cycles
+            );
+            unretired = 0;
+            unretired_cycles = 0;
+        }
+        // The Debug form of an instruction is also the Rust expression
+        // that constructs it
+        let _ = writeln!(
+            source,
+            "    cpu.execute(0x{:04X}, {:?});",
+            opcode, instruction
+        );
+        // The interpreter stops here when the instruction faults or waits
+        if position + 1 < block.instructions.len() {
+            let _ = writeln!(
+                source,
+                "    if cpu.pc() != 0x{:03X} {{\n        return {};\n    }}",
+                next,
+                position + 1
+            );
+        }
+    }
+
+    if stale_pc {
+        let _ = writeln!(source, "    cpu.poke_pc(0x{:03X});", block.end());
+    }
+    if unretired > 0 {
+        let _ = writeln!(
+            source,
+            "    cpu.retire({}, {});",
+            unretired, unretired_cycles
+        );
+    }
+}
+
+/// Rust statements doing the work of an instruction directly on the `Cpu`,
+/// None for instructions left to the interpreter
+fn translate(instruction: &Instruction, address: Address) -> Option<String> {
+    let register = |register: Register| format!("cpu.registers()[0x{:X}]", register);
+    let skip_if = |condition: String| {
+        format!(
+            "    cpu.poke_pc(if {} {{ 0x{:03X} }} else {{ 0x{:03X} }});\n",
+            condition,
+            address + 2 * INSTRUCTION_SIZE,
+            address + INSTRUCTION_SIZE
+        )
+    };
+    let logic = |x: Register, y: Register, operator: &str| {
+        format!(
+            "    cpu.poke_register(0x{:X}, {} {} {});
+    if cpu.quirks().logic_resets_vf {{
+        cpu.poke_register(0xF, 0);
+    }}
+",
+            x,
+            register(x),
+            operator,
+            register(y)
+        )
+    };
+    // The flag is written after the result, like the interpreter's ALU does
+    let shift = |x: Register, y: Register, result: &str, flag: &str| {
+        format!(
+            "    let value = if cpu.quirks().shift_uses_vy {{ {} }} else {{ {} }};
+    cpu.poke_register(0x{:X}, {});
+    cpu.poke_register(0xF, {});
+",
+            register(y),
+            register(x),
+            x,
+            result,
+            flag
+        )
+    };
+
+    let code = match *instruction {
+        Instruction::LoadVal(x, value) => {
+            format!("    cpu.poke_register(0x{:X}, 0x{:02X});\n", x, value)
+        }
+        Instruction::LoadReg(x, y) => {
+            format!("    cpu.poke_register(0x{:X}, {});\n", x, register(y))
+        }
+        Instruction::AddVal(x, value) => format!(
+            "    cpu.poke_register(0x{:X}, {}.wrapping_add(0x{:02X}));\n",
+            x,
+            register(x),
+            value
+        ),
+        Instruction::Or(x, y) => logic(x, y, "|"),
+        Instruction::And(x, y) => logic(x, y, "&"),
+        Instruction::Xor(x, y) => logic(x, y, "^"),
+        Instruction::AddReg(x, y) => format!(
+            "    let (sum, carry) = {}.overflowing_add({});
+    cpu.poke_register(0x{:X}, sum);
+    cpu.poke_register(0xF, carry as u8);
+",
+            register(x),
+            register(y),
+            x
+        ),
+        Instruction::SubReg(x, y) => format!(
+            "    let (difference, borrow) = {}.overflowing_sub({});
+    cpu.poke_register(0x{:X}, difference);
+    cpu.poke_register(0xF, !borrow as u8);
+",
+            register(x),
+            register(y),
+            x
+        ),
+        Instruction::SubRegReverse(x, y) => format!(
+            "    let (difference, borrow) = {}.overflowing_sub({});
+    cpu.poke_register(0x{:X}, difference);
+    cpu.poke_register(0xF, !borrow as u8);
+",
+            register(y),
+            register(x),
+            x
+        ),
+        Instruction::ShiftRight(x, y) => shift(x, y, "value >> 1", "value & 0x01"),
+        Instruction::ShiftLeft(x, y) => shift(x, y, "value << 1", "value >> 7"),
+        Instruction::SetIndexRegister(target) => {
+            format!("    cpu.poke_index(0x{:03X});\n", target)
+        }
+        Instruction::AddIndex(x) => format!(
+            "    cpu.poke_index(cpu.index() + {} as u32);\n",
+            register(x)
+        ),
+        Instruction::Jump(target) => format!("    cpu.poke_pc(0x{:03X});\n", target),
+        Instruction::JumpOffset(target) => format!(
+            "    cpu.poke_pc((0x{:03X} + {} as u16) & 0xFFF);\n",
+            target,
+            register(0x0)
+        ),
+        Instruction::SkipIfEqual(x, value) => {
+            skip_if(format!("{} == 0x{:02X}", register(x), value))
+        }
+        Instruction::SkipIfNotEqual(x, value) => {
+            skip_if(format!("{} != 0x{:02X}", register(x), value))
+        }
+        Instruction::SkipIfRegEqual(x, y) => skip_if(format!("{} == {}", register(x), register(y))),
+        Instruction::SkipIfRegNotEqual(x, y) => {
+            skip_if(format!("{} != {}", register(x), register(y)))
+        }
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Whether the translation of an instruction leaves the program counter at
+/// the next instruction to run
+fn sets_pc(instruction: &Instruction) -> bool {
+    matches!(
+        *instruction,
+        Instruction::Jump(_)
+            | Instruction::JumpOffset(_)
+            | Instruction::SkipIfEqual(_, _)
+            | Instruction::SkipIfNotEqual(_, _)
+            | Instruction::SkipIfRegEqual(_, _)
+            | Instruction::SkipIfRegNotEqual(_, _)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_become_functions() {
+        // LD V0, 1; JP V0, 0x300
+        let source = recompile(&[0x60, 0x01, 0xB3, 0x00], "test.ch8", Platform::Chip8);
+        assert!(source.starts_with("// Recompiled from test.ch8 by"));
+        assert!(source.contains("            0x200 => block_200(cpu, remaining),\n"));
+        assert!(source.contains(
+            "
+fn block_200(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x200, 0x204) {
+        return 0;
+    }
+    // LD V0, 1
+    cpu.poke_register(0x0, 0x01);
+    // JP V0, 0x300
+    cpu.poke_pc((0x300 + cpu.registers()[0x0] as u16) & 0xFFF);
+    cpu.retire(2, 29);
+    2
+}
+"
+        ));
+    }
+
+    #[test]
+    fn other_instructions_are_left_to_the_interpreter() {
+        // LD V0, 1; DRW V0, V0, 1; ADD V0, 1; JP 0x200
+        let source = recompile(
+            &[0x60, 0x01, 0xD0, 0x01, 0x70, 0x01, 0x12, 0x00],
+            "test.ch8",
+            Platform::Chip8,
+        );
+        assert!(source.contains(
+            "
+    // LD V0, 1
+    cpu.poke_register(0x0, 0x01);
+    cpu.poke_pc(0x202);
+    cpu.retire(1, 6);
+    cpu.execute(0xD001, Draw(0, 0, 1));
+    if cpu.pc() != 0x204 {
+        return 2;
+    }
+    // ADD V0, 1
+    cpu.poke_register(0x0, cpu.registers()[0x0].wrapping_add(0x01));
+    // JP 0x200
+    cpu.poke_pc(0x200);
+    cpu.retire(2, 33);
+    4
+}
+"
+        ));
+    }
+
+    #[test]
+    fn checked_in_recompiled_rom_is_up_to_date() {
+        // Regenerate it with:
+        // cargo run -- roms/tictac.rom --recompile tests/recompiled/tictac.rs
+        assert_eq!(
+            include_str!("../tests/recompiled/tictac.rs"),
+            recompile(
+                include_bytes!("../roms/tictac.rom"),
+                "tictac.rom",
+                Platform::Chip8
+            )
+        );
+    }
+
+    #[test]
+    fn chip8x_roms_are_recompiled_from_their_start() {
+        // LD V0, 1; COL V0, V1, 1
+        let source = recompile(&[0x60, 0x01, 0xB0, 0x11], "test.ch8", Platform::Chip8X);
+        assert!(source.contains("            0x300 => block_300(cpu, remaining),\n"));
+        assert!(source.contains("    cpu.execute(0xB011, SetSpriteColor(0, 1, 1));\n"));
+    }
+}
@@ -0,0 +1,469 @@
+use gif;
+use instruction::Instruction;
+use octo;
+use patch;
+use patch::PatchError;
+use platform::Platform;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "rom", "bin"];
+const GIF_SIGNATURE: &[u8] = b"GIF8";
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+
+pub struct Rom {
+    /// A short name for the ROM, used for file names of screenshots and recordings
+    pub name: String,
+    pub data: Vec<u8>,
+    /// Things about the ROM that look wrong but don't prevent it from running
+    pub warnings: Vec<String>,
+    /// The IPS or BPS patch applied to the ROM, if any
+    pub patch: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(String, io::Error),
+    Empty,
+    /// The size of the ROM and the most that fits
+    TooLarge(usize, usize),
+    Archive(String),
+    NoRomInArchive,
+    MultipleRomsInArchive(Vec<String>),
+    Cartridge(String),
+    /// The patch file and why it could not be applied
+    Patch(String, PatchError),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::Io(ref source, ref e) => write!(f, "Unable to read {}: {}", source, e),
+            RomError::Patch(ref source, ref e) => {
+                write!(f, "Unable to apply the patch {}: {}", source, e)
+            }
+            RomError::Empty => write!(f, "The ROM is empty"),
+            RomError::TooLarge(size, limit) => write!(
+                f,
+                "The ROM is {} bytes but at most {} bytes fit in memory",
+                size, limit
+            ),
+            RomError::Archive(ref e) => write!(f, "Unable to read the archive: {}", e),
+            RomError::NoRomInArchive => write!(f, "The archive does not contain a ROM"),
+            RomError::MultipleRomsInArchive(ref names) => write!(
+                f,
+                "The archive contains more than one ROM ({}), extract the one you want to run",
+                names.join(", ")
+            ),
+            RomError::Cartridge(ref e) => write!(f, "Unable to read the Octo cartridge: {}", e),
+        }
+    }
+}
+
+impl Rom {
+    /// Checks that the ROM fits in the memory of a platform, as a ROM that
+    /// fits on the MEGA-CHIP can be too large for the others
+    pub fn check_fits(&self, platform: Platform) -> Result<(), RomError> {
+        let limit = max_size(platform);
+        if self.data.len() > limit {
+            return Err(RomError::TooLarge(self.data.len(), limit));
+        }
+        Ok(())
+    }
+}
+
+/// Largest ROM that fits between the start of the program and the end of
+/// memory
+pub fn max_size(platform: Platform) -> usize {
+    platform.memory_size() - platform.program_start()
+}
+
+/// Loads a ROM from a file, a zip archive containing a single ROM, or stdin
+/// when the source is "-"
+pub fn load(source: &str) -> Result<Rom, RomError> {
+    let mut data = Vec::new();
+    let name = if source == "-" {
+        io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| RomError::Io(String::from("stdin"), e))?;
+        String::from("stdin")
+    } else {
+        let path = Path::new(source);
+        let io_error = |e| RomError::Io(path.display().to_string(), e);
+        let mut file = File::open(path).map_err(&io_error)?;
+        file.read_to_end(&mut data).map_err(&io_error)?;
+        file_stem(source)
+    };
+
+    if data.starts_with(ZIP_SIGNATURE) {
+        from_zip(io::Cursor::new(data))
+    } else {
+        from_bytes(&name, data)
+    }
+}
+
+/// Loads a ROM like `load` and applies a patch to it, either the one given
+/// or an IPS or BPS file with the same name next to the ROM
+pub fn load_patched(source: &str, patch: Option<&Path>) -> Result<Rom, RomError> {
+    let rom = load(source)?;
+    let patch = match patch {
+        Some(patch) => patch.to_path_buf(),
+        None if source != "-" => match patch::find_patch(Path::new(source)) {
+            Some(patch) => patch,
+            None => return Ok(rom),
+        },
+        None => return Ok(rom),
+    };
+    apply_patch(rom, &patch)
+}
+
+/// Applies an IPS or BPS patch file to a ROM, checking the result like a
+/// freshly loaded ROM
+pub fn apply_patch(rom: Rom, path: &Path) -> Result<Rom, RomError> {
+    let source = path.display().to_string();
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| RomError::Io(source.clone(), e))?;
+
+    let data = patch::apply(&rom.data, &bytes).map_err(|e| RomError::Patch(source, e))?;
+    let mut patched = from_bytes(&rom.name, data)?;
+    patched.patch = Some(path.to_path_buf());
+    Ok(patched)
+}
+
+/// Validates the contents of a ROM, which has to fit in memory on at least
+/// one platform, assembling the program first when it is an Octo cartridge
+pub fn from_bytes(name: &str, data: Vec<u8>) -> Result<Rom, RomError> {
+    if data.is_empty() {
+        return Err(RomError::Empty);
+    }
+    if data.starts_with(GIF_SIGNATURE) {
+        let program = cartridge_program(&data).map_err(RomError::Cartridge)?;
+        let data = octo::assemble(&program).map_err(RomError::Cartridge)?;
+        return from_bytes(name, data);
+    }
+    let limit = max_size(Platform::MegaChip);
+    if data.len() > limit {
+        return Err(RomError::TooLarge(data.len(), limit));
+    }
+
+    let warnings = check_rom(&data);
+    Ok(Rom {
+        name: String::from(name),
+        data,
+        warnings,
+        patch: None,
+    })
+}
+
+/// Loads the only ROM in a zip archive. Files with a known ROM extension are
+/// preferred, otherwise the archive must contain exactly one file.
+pub fn from_zip<R: Read + Seek>(reader: R) -> Result<Rom, RomError> {
+    let archive_error = |e: ::zip::result::ZipError| RomError::Archive(e.to_string());
+    let mut archive = ZipArchive::new(reader).map_err(&archive_error)?;
+
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(&archive_error)?;
+        if file.is_file() {
+            files.push((i, String::from(file.name())));
+        }
+    }
+
+    let roms: Vec<_> = files
+        .iter()
+        .filter(|&(_, name)| has_rom_extension(name))
+        .cloned()
+        .collect();
+    let candidates = if roms.is_empty() { files } else { roms };
+
+    let (index, name) = match candidates.len() {
+        0 => return Err(RomError::NoRomInArchive),
+        1 => candidates[0].clone(),
+        _ => {
+            let names = candidates.into_iter().map(|(_, name)| name).collect();
+            return Err(RomError::MultipleRomsInArchive(names));
+        }
+    };
+
+    let mut data = Vec::new();
+    archive
+        .by_index(index)
+        .map_err(&archive_error)?
+        .read_to_end(&mut data)
+        .map_err(|e| RomError::Io(name.clone(), e))?;
+
+    from_bytes(&file_stem(&name), data)
+}
+
+/// Octo cartridges are GIFs hiding the program's source code in the two low
+/// bits of each pixel's color index, four pixels to a byte. The bytes are a 32
+/// bit big endian length followed by JSON holding the source as "program".
+fn cartridge_program(data: &[u8]) -> Result<String, String> {
+    let mut reader = gif::Decoder::new(data)
+        .read_info()
+        .map_err(|e| e.to_string())?;
+    let mut payload = Vec::new();
+    while let Some(frame) = reader.read_next_frame().map_err(|e| e.to_string())? {
+        payload.extend(frame.buffer.chunks(4).map(|pixels| {
+            pixels
+                .iter()
+                .fold(0, |byte, &pixel| byte << 2 | (pixel & 0x3))
+        }));
+    }
+
+    if payload.len() < 4 {
+        return Err(String::from("The image does not contain a program"));
+    }
+    let length = payload[..4]
+        .iter()
+        .fold(0, |length, &byte| length << 8 | byte as usize);
+    let json = payload
+        .get(4..4 + length)
+        .ok_or_else(|| String::from("The program is longer than the image"))?;
+    json_string(&String::from_utf8_lossy(json), "program")
+        .ok_or_else(|| String::from("The image does not contain a program"))
+}
+
+/// Finds the string stored under a key in a JSON object, without parsing the
+/// rest of the document
+fn json_string(json: &str, key: &str) -> Option<String> {
+    let mut chars = json.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let string = json_string_literal(&mut chars)?;
+        let rest = chars.as_str().trim_start();
+        if string == key && rest.starts_with(':') {
+            let rest = rest[1..].trim_start();
+            let mut value = rest.chars();
+            return match value.next() {
+                Some('"') => json_string_literal(&mut value),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+/// Reads the rest of a JSON string whose opening quote has been consumed
+fn json_string_literal(chars: &mut ::std::str::Chars) -> Option<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => match chars.next()? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&code, 16).ok()?;
+                    string.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                }
+                escaped => string.push(escaped),
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+fn check_rom(data: &[u8]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if data.len() >= 2 {
+        let first = (data[0] as u16) << 8 | data[1] as u16;
+        if Instruction::parse(first) == Instruction::InvalidOperation {
+            warnings.push(format!(
+                "The ROM does not start with a valid instruction (0x{:04X})",
+                first
+            ));
+        }
+    }
+
+    if data.iter().all(|&byte| byte == 0) {
+        warnings.push(String::from("The ROM only contains zeros"));
+    }
+
+    let text = data
+        .iter()
+        .all(|&byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace());
+    if text {
+        warnings.push(String::from(
+            "The ROM looks like a text file, it may be source code or a hex dump",
+        ));
+    }
+
+    warnings
+}
+
+pub fn has_rom_extension(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| ROM_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn file_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("rom")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use testing::temp_path;
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    fn zip_archive(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for &(name, data) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        archive
+    }
+
+    #[test]
+    fn empty_rom_is_rejected() {
+        match from_bytes("empty", Vec::new()) {
+            Err(RomError::Empty) => {}
+            _ => panic!("expected an empty ROM error"),
+        }
+    }
+
+    #[test]
+    fn rom_larger_than_memory_is_rejected() {
+        let largest = max_size(Platform::Chip8);
+        let rom = from_bytes("large", vec![0x12; largest + 1]).unwrap();
+        match rom.check_fits(Platform::Chip8) {
+            Err(RomError::TooLarge(size, limit)) => {
+                assert_eq!((largest + 1, largest), (size, limit))
+            }
+            _ => panic!("expected a ROM too large error"),
+        }
+        assert!(rom.check_fits(Platform::MegaChip).is_ok());
+        assert!(from_bytes("largest", vec![0x12; largest])
+            .unwrap()
+            .check_fits(Platform::Chip8)
+            .is_ok());
+        assert_eq!(0xD00, max_size(Platform::Chip8X));
+
+        match from_bytes("huge", vec![0x12; max_size(Platform::MegaChip) + 1]) {
+            Err(RomError::TooLarge(_, limit)) => assert_eq!(max_size(Platform::MegaChip), limit),
+            _ => panic!("expected a ROM too large error"),
+        }
+    }
+
+    #[test]
+    fn invalid_first_instruction_warns() {
+        let rom = from_bytes("invalid", vec![0xFF, 0xFF]).unwrap();
+        assert_eq!(1, rom.warnings.len());
+
+        let rom = from_bytes("valid", vec![0x12, 0x00]).unwrap();
+        assert!(rom.warnings.is_empty());
+    }
+
+    /// Builds a cartridge the way Octo does, hiding the JSON in the low bits
+    /// of a 128x64 image whose palette has four shades of each color
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend(json.as_bytes());
+        let mut pixels: Vec<u8> = payload
+            .iter()
+            .flat_map(|&byte| (0..4).rev().map(move |shift| byte >> (shift * 2) & 0x3))
+            .map(|bits| 0x4 | bits)
+            .collect();
+        pixels.resize(128 * 64, 0x4);
+
+        let palette: Vec<u8> = (0..16).flat_map(|i| vec![i * 16; 3]).collect();
+        let mut image = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut image, 128, 64, &palette).unwrap();
+            let frame = gif::Frame {
+                width: 128,
+                height: 64,
+                buffer: ::std::borrow::Cow::Owned(pixels),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).unwrap();
+        }
+        image
+    }
+
+    #[test]
+    fn octo_cartridge_is_assembled() {
+        let json = r##"{"program":"# \"Hello\"\n: main\n\tv0 := 0x2A\n\tloop again\n","options":{"tickrate":20,"fillColor":"#FFCC00"}}"##;
+        let rom = from_bytes("cartridge", cartridge(json)).unwrap();
+        assert_eq!(vec![0x12, 0x02, 0x60, 0x2A, 0x12, 0x04], rom.data);
+    }
+
+    #[test]
+    fn cartridge_without_program_is_rejected() {
+        match from_bytes("cartridge", cartridge(r#"{"options":{}}"#)) {
+            Err(RomError::Cartridge(_)) => {}
+            _ => panic!("expected a cartridge error"),
+        }
+    }
+
+    #[test]
+    fn rom_is_loaded_from_zip() {
+        let archive = zip_archive(&[("README.txt", b"Have fun"), ("games/pong.ch8", &[0x12, 0x00])]);
+        let rom = from_zip(archive).unwrap();
+        assert_eq!("pong", rom.name);
+        assert_eq!(vec![0x12, 0x00], rom.data);
+    }
+
+    #[test]
+    fn patches_next_to_the_rom_are_applied() {
+        let directory = temp_path("rom-patch");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("game.ch8");
+        fs::write(&path, [0x60, 0x01, 0x12, 0x00]).unwrap();
+        let source = path.to_string_lossy().into_owned();
+        assert_eq!(None, load_patched(&source, None).unwrap().patch);
+
+        // Writes 0x07 at 1
+        let patch = directory.join("game.ips");
+        fs::write(&patch, b"PATCH\x00\x00\x01\x00\x01\x07EOF").unwrap();
+        let rom = load_patched(&source, None).unwrap();
+        assert_eq!(vec![0x60, 0x07, 0x12, 0x00], rom.data);
+        assert_eq!(Some(patch.clone()), rom.patch);
+
+        let broken = directory.join("broken.ips");
+        fs::write(&broken, b"PATCH\x00\x00").unwrap();
+        match load_patched(&source, Some(&broken)) {
+            Err(RomError::Patch(_, PatchError::Truncated)) => {}
+            other => panic!("expected a truncated patch error, got {:?}", other.err()),
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn zip_with_several_roms_is_rejected() {
+        let archive = zip_archive(&[("a.ch8", &[0x12, 0x00]), ("b.ch8", &[0x12, 0x00])]);
+        match from_zip(archive) {
+            Err(RomError::MultipleRomsInArchive(names)) => assert_eq!(2, names.len()),
+            _ => panic!("expected a multiple ROMs error"),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use cpu::Cpu;
+use instruction::Address;
+
+/// A native stand-in for a machine code subroutine of the interpreter, run
+/// when the program calls its address with 0NNN
+pub type Routine = Box<dyn FnMut(&mut Cpu)>;
+
+/// The hi-res CHIP-8 clears its 64x64 screen by calling 0230
+pub const HIRES_CLEAR: Address = 0x230;
+/// The extension of the hi-res interpreter at 0x260 sets up the display and
+/// goes on to the program at 0x2C0, so emulators jump there directly
+pub const HIRES_ENTRY: [u8; 2] = [0x12, 0xC0];
+
+pub fn clear_hires(cpu: &mut Cpu) {
+    cpu.clear_screen();
+}
@@ -0,0 +1,155 @@
+use capture::Recorder;
+use cpu::Cpu;
+use metadata::RomInfo;
+use platform::Platform;
+use rom;
+use rom::Rom;
+use std::path::PathBuf;
+use timing::Timing;
+use watcher::FileWatcher;
+
+/// How often the ROM file is checked for changes, in frames
+const RELOAD_POLL_FRAMES: u32 = 15;
+
+/// A ROM being run, everything that is thrown away when going back to the browser
+pub struct Session {
+    pub cpu: Cpu,
+    pub rom: Rom,
+    pub info: RomInfo,
+    /// Where the ROM was loaded from, None when it was read from stdin
+    pub path: Option<PathBuf>,
+    /// The patch given with `--patch`, None when it is looked for next to
+    /// the ROM
+    pub patch: Option<PathBuf>,
+    pub recorder: Option<Recorder>,
+    pub paused: bool,
+    instructions_per_frame: u32,
+    last_frame: u32,
+    watcher: Option<FileWatcher>,
+    frames_since_poll: u32,
+}
+
+impl Session {
+    pub fn new(
+        rom: Rom,
+        info: RomInfo,
+        path: Option<PathBuf>,
+        clock_speed: u32,
+        platform: Platform,
+        frames_per_second: u32,
+        debug: bool,
+    ) -> Session {
+        let mut cpu = Cpu::with_platform(rom.data.clone(), clock_speed, debug, platform);
+        cpu.set_quirks(info.quirks);
+        let last_frame = cpu.frame();
+        let watcher = path.as_ref().map(|path| FileWatcher::new(path));
+
+        Session {
+            cpu,
+            rom,
+            info,
+            path,
+            patch: None,
+            recorder: None,
+            paused: false,
+            instructions_per_frame: (clock_speed / frames_per_second).max(1),
+            last_frame,
+            watcher,
+            frames_since_poll: 0,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let title = match self.info.title {
+            Some(ref title) => format!("chip8 - {}", title),
+            None => String::from("chip8"),
+        };
+        if self.paused {
+            format!("{} (paused)", title)
+        } else {
+            title
+        }
+    }
+
+    /// Starts the ROM over with a fresh CPU, keeping the speed and quirks
+    pub fn reset(&mut self) {
+        self.cpu.reset(self.rom.data.clone());
+        self.last_frame = self.cpu.frame();
+    }
+
+    /// Reloads the ROM, patching it again, and resets if its file changed on
+    /// disk since it was loaded. Returns the error when the new file could
+    /// not be loaded, in which case the old ROM keeps running.
+    pub fn reload_if_changed(&mut self) -> Option<Result<(), rom::RomError>> {
+        self.frames_since_poll += 1;
+        if self.frames_since_poll < RELOAD_POLL_FRAMES {
+            return None;
+        }
+        self.frames_since_poll = 0;
+
+        let changed = match self.watcher {
+            Some(ref mut watcher) => watcher.poll(),
+            None => false,
+        };
+        if !changed {
+            return None;
+        }
+
+        let path = self.path.as_ref()?.to_string_lossy().into_owned();
+        let platform = self.cpu.platform();
+        Some(
+            rom::load_patched(&path, self.patch.as_deref())
+                .and_then(|rom| rom.check_fits(platform).map(|_| rom))
+                .map(|rom| {
+                    self.rom = rom;
+                    self.reset();
+                }),
+        )
+    }
+
+    /// Runs the instructions for one 60 Hz frame unless paused, either a fixed
+    /// number or as many as fit in a frame of the COSMAC VIP
+    pub fn run_frame(&mut self) {
+        if self.paused {
+            return;
+        }
+        match self.cpu.timing() {
+            Timing::Simple => {
+                for _ in 0..self.instructions_per_frame {
+                    self.cpu.cycle();
+                }
+            }
+            Timing::Vip => {
+                let frame = self.cpu.frame();
+                while self.cpu.frame() == frame && !self.cpu.faulted {
+                    self.cpu.cycle();
+                }
+            }
+        }
+        self.record_frames();
+    }
+
+    /// Runs a single instruction
+    pub fn step(&mut self) {
+        self.cpu.cycle();
+        self.record_frames();
+    }
+
+    /// Hands the recorder a picture of the screen for every frame that passed
+    /// since the last call
+    fn record_frames(&mut self) {
+        let frame = self.cpu.frame();
+        if frame == self.last_frame {
+            return;
+        }
+        if let Some(ref mut recorder) = self.recorder {
+            let picture = self.cpu.display.frame();
+            for _ in self.last_frame..frame {
+                if let Err(e) = recorder.capture(&picture) {
+                    println!("Unable to record frame: {}", e);
+                }
+            }
+        }
+        self.last_frame = frame;
+    }
+}
@@ -0,0 +1,10 @@
+//! Helpers shared by the unit tests
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+/// A path in the temporary directory that no other test run uses
+pub fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("chip8-{}-{}", process::id(), name))
+}
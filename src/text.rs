@@ -0,0 +1,107 @@
+use piston_window::types::Color;
+use piston_window::{Context, G2d, Rectangle};
+
+/// Distance from the start of one character to the next, and from one line to
+/// the next, in font pixels
+pub const ADVANCE: f64 = 4.0;
+pub const LINE_HEIGHT: f64 = 7.0;
+
+/// Draws text in a tiny 3x5 pixel font where every font pixel is a square of
+/// `size` pixels. Lowercase letters are drawn as uppercase and characters
+/// without a glyph as a question mark.
+pub fn draw_text(
+    text: &str,
+    position: [f64; 2],
+    size: f64,
+    color: Color,
+    context: &Context,
+    graphics: &mut G2d,
+) {
+    let rectangle = Rectangle::new(color);
+    for (index, character) in text.chars().enumerate() {
+        let left = position[0] + index as f64 * ADVANCE * size;
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    let dimensions = [
+                        left + column as f64 * size,
+                        position[1] + row as f64 * size,
+                        size,
+                        size,
+                    ];
+                    rectangle.draw(dimensions, &context.draw_state, context.transform, graphics);
+                }
+            }
+        }
+    }
+}
+
+/// The rows of a character from top to bottom, the highest of the three bits
+/// being the leftmost pixel
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ';' => [0b000, 0b010, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+        ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '*' => [0b101, 0b010, 0b111, 0b010, 0b101],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '&' => [0b010, 0b101, 0b010, 0b101, 0b011],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '$' => [0b011, 0b110, 0b010, 0b011, 0b110],
+        '@' => [0b010, 0b101, 0b111, 0b100, 0b011],
+        '|' => [0b010, 0b010, 0b010, 0b010, 0b010],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
@@ -0,0 +1,181 @@
+use instruction::{Instruction, Value};
+
+/// Machine cycles in one 60 Hz frame of the COSMAC VIP, whose CDP1802 runs
+/// at 1.7609 MHz and takes 8 clock cycles per machine cycle
+const VIP_MACHINE_CYCLES_PER_FRAME: u32 = 3668;
+/// Machine cycles of every frame taken by the display DMA, one for each of
+/// the 8 bytes of the 128 scanlines
+const VIP_DISPLAY_CYCLES: u32 = 1024;
+/// Machine cycles of every frame left for the interpreter
+pub const VIP_CYCLES_PER_FRAME: u32 = VIP_MACHINE_CYCLES_PER_FRAME - VIP_DISPLAY_CYCLES;
+
+/// Machine cycles DXYN takes before its first row and for every row, and
+/// per bit each row is shifted by when the sprite is not byte aligned, which
+/// also makes every row cover a second byte
+const DRAW_SETUP_CYCLES: u32 = 26;
+const DRAW_ROW_CYCLES: u32 = 12;
+const DRAW_SHIFT_CYCLES: u32 = 4;
+const DRAW_SECOND_BYTE_CYCLES: u32 = 10;
+
+/// How long instructions take, which decides how many run per frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+    /// Every instruction takes the same time, and a fixed number of them run
+    /// per frame
+    Simple,
+    /// Every instruction takes as many machine cycles as on the COSMAC VIP,
+    /// and draws wait for the next frame like the original interpreter
+    Vip,
+}
+
+impl Timing {
+    pub fn from_name(name: &str) -> Option<Timing> {
+        match name {
+            "simple" => Some(Timing::Simple),
+            "vip" => Some(Timing::Vip),
+            _ => None,
+        }
+    }
+}
+
+/// Machine cycles the COSMAC VIP interpreter takes to fetch, decode and run
+/// an instruction, given the registers from before it runs, the height of the
+/// screen and whether sprites are clipped at its bottom. The wait of DXYN for
+/// the next frame is not included, as it depends on when it runs.
+pub fn vip_cycles(
+    instruction: &Instruction,
+    registers: &[Value; 16],
+    screen_height: usize,
+    clip: bool,
+) -> u32 {
+    match *instruction {
+        Instruction::LoadVal(_, _) => 6,
+        Instruction::AddVal(_, _)
+        | Instruction::LoadDelay(_)
+        | Instruction::SetDelay(_)
+        | Instruction::SetSound(_)
+        | Instruction::OutputPort(_) => 10,
+        Instruction::SkipIfEqual(_, _)
+        | Instruction::SkipIfNotEqual(_, _)
+        | Instruction::SetIndexRegister(_) => 12,
+        Instruction::SkipIfRegEqual(_, _)
+        | Instruction::SkipIfRegNotEqual(_, _)
+        | Instruction::SkipIfKey(_)
+        | Instruction::SkipIfNotKey(_)
+        | Instruction::SkipIfKey2(_)
+        | Instruction::SkipIfNotKey2(_)
+        | Instruction::WaitKey(_) => 16,
+        Instruction::AddIndex(_) => 19,
+        Instruction::LoadDigit(_) => 20,
+        // MEGA-CHIP instructions never ran on the VIP, they are charged like
+        // the machine code subroutines that the other 0NNN opcodes call
+        Instruction::ExRoutine(_)
+        | Instruction::MegaOff
+        | Instruction::MegaOn
+        | Instruction::LoadLongIndex(_)
+        | Instruction::LoadPalette(_)
+        | Instruction::SetSpriteWidth(_)
+        | Instruction::SetSpriteHeight(_)
+        | Instruction::SetAlpha(_)
+        | Instruction::PlaySample(_)
+        | Instruction::StopSample
+        | Instruction::SetBlendMode(_)
+        | Instruction::SetCollisionColor(_)
+        | Instruction::CycleBackground
+        | Instruction::Return
+        | Instruction::Jump(_)
+        | Instruction::Call(_)
+        | Instruction::JumpOffset(_) => 23,
+        Instruction::Clear => 24,
+        Instruction::Random(_, _) => 36,
+        Instruction::SetZoneColor(_, _) | Instruction::SetSpriteColor(_, _, _) => 44,
+        Instruction::LoadReg(_, _)
+        | Instruction::Or(_, _)
+        | Instruction::And(_, _)
+        | Instruction::Xor(_, _)
+        | Instruction::AddReg(_, _)
+        | Instruction::SubReg(_, _)
+        | Instruction::ShiftRight(_, _)
+        | Instruction::SubRegReverse(_, _)
+        | Instruction::ShiftLeft(_, _) => 44,
+        Instruction::StoreIndex(r) | Instruction::ReadIndex(r) => 18 + 14 * (r as u32 + 1),
+        Instruction::LoadBCD(_) => 204,
+        Instruction::Draw(r1, r2, height) => {
+            let x = registers[r1 as usize] as u32;
+            let y = registers[r2 as usize] as usize % screen_height;
+            draw_cycles(x, y, height as u32, screen_height, clip)
+        }
+        Instruction::InvalidOperation => 0,
+    }
+}
+
+/// Clipped rows below the bottom of the screen are not drawn, and every row
+/// is shifted into place one bit at a time
+fn draw_cycles(x: u32, y: usize, height: u32, screen_height: usize, clip: bool) -> u32 {
+    let rows = if clip {
+        height.min((screen_height - y) as u32)
+    } else {
+        height
+    };
+    let shift = x % 8;
+    let mut row_cycles = DRAW_ROW_CYCLES + shift * DRAW_SHIFT_CYCLES;
+    if shift != 0 {
+        row_cycles += DRAW_SECOND_BYTE_CYCLES;
+    }
+    DRAW_SETUP_CYCLES + rows * row_cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use display::HEIGHT;
+
+    fn cycles(instruction: &Instruction, registers: &[Value; 16]) -> u32 {
+        vip_cycles(instruction, registers, HEIGHT, true)
+    }
+
+    #[test]
+    fn draws_cost_more_when_taller_and_unaligned() {
+        let mut registers = [0; 16];
+        let draw = Instruction::Draw(0x0, 0x1, 5);
+        let aligned = cycles(&draw, &registers);
+        assert_eq!(DRAW_SETUP_CYCLES + 5 * DRAW_ROW_CYCLES, aligned);
+        assert!(cycles(&Instruction::Draw(0x0, 0x1, 10), &registers) > aligned);
+
+        registers[0x0] = 3;
+        let unaligned = cycles(&draw, &registers);
+        assert_eq!(
+            aligned + 5 * (3 * DRAW_SHIFT_CYCLES + DRAW_SECOND_BYTE_CYCLES),
+            unaligned
+        );
+
+        // Only the three rows above the bottom are drawn
+        registers[0x1] = 29;
+        let row = DRAW_ROW_CYCLES + 3 * DRAW_SHIFT_CYCLES + DRAW_SECOND_BYTE_CYCLES;
+        assert_eq!(unaligned - 2 * row, cycles(&draw, &registers));
+
+        // Wrapped rows are all drawn, and the screen can be taller
+        assert_eq!(unaligned, vip_cycles(&draw, &registers, HEIGHT, false));
+        assert_eq!(unaligned, vip_cycles(&draw, &registers, 64, true));
+    }
+
+    #[test]
+    fn loads_and_stores_cost_more_per_register() {
+        let registers = [0; 16];
+        assert!(
+            cycles(&Instruction::StoreIndex(0xF), &registers)
+                > cycles(&Instruction::StoreIndex(0x0), &registers)
+        );
+        assert_eq!(
+            cycles(&Instruction::StoreIndex(0x3), &registers),
+            cycles(&Instruction::ReadIndex(0x3), &registers)
+        );
+    }
+
+    #[test]
+    fn timing_names() {
+        assert_eq!(Some(Timing::Vip), Timing::from_name("vip"));
+        assert_eq!(Some(Timing::Simple), Timing::from_name("simple"));
+        assert_eq!(None, Timing::from_name("fast"));
+    }
+}
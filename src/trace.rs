@@ -0,0 +1,287 @@
+use instruction::{Address, Instruction};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    /// One aligned, human readable line per instruction
+    Text,
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl TraceFormat {
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name {
+            "text" => Some(TraceFormat::Text),
+            "json" => Some(TraceFormat::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// Limits the trace to the instructions of interest
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TraceFilter {
+    /// Inclusive range of program counter values
+    pub addresses: Option<(Address, Address)>,
+    /// Mnemonics such as "DRW" or "CALL", everything is traced when empty
+    pub mnemonics: Vec<String>,
+}
+
+impl TraceFilter {
+    /// Parses an address range in hexadecimal, e.g. "200-2FF"
+    pub fn parse_range(range: &str) -> Result<(Address, Address), String> {
+        let invalid = || format!("Invalid address range '{}', expected e.g. 200-2FF", range);
+        let mut bounds = range.splitn(2, '-');
+        let mut bound = || {
+            bounds
+                .next()
+                .and_then(|bound| Address::from_str_radix(bound.trim(), 16).ok())
+                .ok_or_else(invalid)
+        };
+        let start = bound()?;
+        let end = bound()?;
+        if start > end {
+            return Err(invalid());
+        }
+        Ok((start, end))
+    }
+
+    /// Parses a comma separated list of mnemonics, e.g. "drw,call"
+    pub fn parse_mnemonics(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(|mnemonic| mnemonic.trim().to_uppercase())
+            .filter(|mnemonic| !mnemonic.is_empty())
+            .collect()
+    }
+
+    fn matches(&self, pc: Address, instruction: &Instruction) -> bool {
+        let in_range = match self.addresses {
+            Some((start, end)) => pc >= start && pc <= end,
+            None => true,
+        };
+        let mnemonic = instruction.mnemonic();
+        in_range
+            && (self.mnemonics.is_empty() || self.mnemonics.iter().any(|wanted| wanted == mnemonic))
+    }
+}
+
+/// A register that an instruction changed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Register(u8, u8, u8),
+    Index(u32, u32),
+}
+
+/// Everything recorded about one executed instruction
+pub struct TraceEntry<'a> {
+    pub cycle: u32,
+    pub pc: Address,
+    pub opcode: u16,
+    pub instruction: &'a Instruction,
+    pub changes: &'a [Change],
+}
+
+/// Writes a trace of the executed instructions. Writing stops at the first
+/// error, which is reported by `finish`.
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    filter: TraceFilter,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + 'static>(writer: W, format: TraceFormat, filter: TraceFilter) -> Tracer {
+        Tracer {
+            writer: Box::new(writer),
+            format,
+            filter,
+            error: None,
+        }
+    }
+
+    pub fn create(path: &Path, format: TraceFormat, filter: TraceFilter) -> io::Result<Tracer> {
+        let file = File::create(path)?;
+        Ok(Tracer::new(BufWriter::new(file), format, filter))
+    }
+
+    /// Whether the instruction at this address would be written, so that the
+    /// CPU can skip collecting register changes for the others
+    pub fn wants(&self, pc: Address, instruction: &Instruction) -> bool {
+        self.error.is_none() && self.filter.matches(pc, instruction)
+    }
+
+    pub fn record(&mut self, entry: &TraceEntry) {
+        if !self.wants(entry.pc, entry.instruction) {
+            return;
+        }
+        let line = match self.format {
+            TraceFormat::Text => format_text(entry),
+            TraceFormat::JsonLines => format_json(entry),
+        };
+        if let Err(e) = writeln!(self.writer, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.writer.flush()
+    }
+}
+
+fn format_text(entry: &TraceEntry) -> String {
+    let changes: Vec<_> = entry
+        .changes
+        .iter()
+        .map(|change| match *change {
+            Change::Register(register, old, new) => {
+                format!("V{:X}:{:02X}->{:02X}", register, old, new)
+            }
+            Change::Index(old, new) => format!("I:{:03X}->{:03X}", old, new),
+        })
+        .collect();
+    let line = format!(
+        "{:>10} {:03X} {:04X} {:<20} {}",
+        entry.cycle,
+        entry.pc,
+        entry.opcode,
+        entry.instruction.to_string(),
+        changes.join(" ")
+    );
+    line.trim_end().to_string()
+}
+
+fn format_json(entry: &TraceEntry) -> String {
+    let changes: Vec<_> = entry
+        .changes
+        .iter()
+        .map(|change| match *change {
+            Change::Register(register, old, new) => {
+                format!("\"V{:X}\":[{},{}]", register, old, new)
+            }
+            Change::Index(old, new) => format!("\"I\":[{},{}]", old, new),
+        })
+        .collect();
+    // The instruction text only contains letters, digits and punctuation that
+    // need no escaping
+    format!(
+        "{{\"cycle\":{},\"pc\":{},\"opcode\":\"{:04X}\",\"instruction\":\"{}\",\"changes\":{{{}}}}}",
+        entry.cycle,
+        entry.pc,
+        entry.opcode,
+        entry.instruction,
+        changes.join(",")
+    )
+}
+
+/// The first line at which two traces differ
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    /// Line number starting at 1
+    pub line: usize,
+    /// The lines of each trace, None when it ended before the other
+    pub left: Option<String>,
+    pub right: Option<String>,
+    /// The lines leading up to the divergence, which both traces share
+    pub context: Vec<String>,
+}
+
+/// Lines of common history kept for a divergence
+const DIVERGENCE_CONTEXT: usize = 5;
+
+/// Compares two traces written in the same format line by line
+pub fn diff_traces<A: BufRead, B: BufRead>(left: A, right: B) -> io::Result<Option<Divergence>> {
+    let mut left = left.lines();
+    let mut right = right.lines();
+    let mut context = Vec::new();
+    let mut line = 0;
+
+    loop {
+        line += 1;
+        let left_line = left.next().map_or(Ok(None), |line| line.map(Some))?;
+        let right_line = right.next().map_or(Ok(None), |line| line.map(Some))?;
+        if left_line.is_none() && right_line.is_none() {
+            return Ok(None);
+        }
+        if left_line != right_line {
+            return Ok(Some(Divergence {
+                line,
+                left: left_line,
+                right: right_line,
+                context,
+            }));
+        }
+
+        context.extend(left_line);
+        if context.len() > DIVERGENCE_CONTEXT {
+            context.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn entry_line(format: TraceFormat) -> String {
+        let instruction = Instruction::parse(0x7A05);
+        let changes = [Change::Register(0xA, 0x02, 0x07)];
+        let entry = TraceEntry {
+            cycle: 3,
+            pc: 0x204,
+            opcode: 0x7A05,
+            instruction: &instruction,
+            changes: &changes,
+        };
+        match format {
+            TraceFormat::Text => format_text(&entry),
+            TraceFormat::JsonLines => format_json(&entry),
+        }
+    }
+
+    #[test]
+    fn entries_are_formatted() {
+        assert_eq!(
+            "         3 204 7A05 ADD VA, 5            VA:02->07",
+            entry_line(TraceFormat::Text)
+        );
+        assert_eq!(
+            "{\"cycle\":3,\"pc\":516,\"opcode\":\"7A05\",\"instruction\":\"ADD VA, 5\",\"changes\":{\"VA\":[2,7]}}",
+            entry_line(TraceFormat::JsonLines)
+        );
+    }
+
+    #[test]
+    fn filter_limits_addresses_and_mnemonics() {
+        let filter = TraceFilter {
+            addresses: Some(TraceFilter::parse_range("200-2ff").unwrap()),
+            mnemonics: TraceFilter::parse_mnemonics("drw, call"),
+        };
+        assert!(filter.matches(0x200, &Instruction::parse(0xD125)));
+        assert!(!filter.matches(0x300, &Instruction::parse(0xD125)));
+        assert!(!filter.matches(0x200, &Instruction::parse(0x7A05)));
+        assert!(TraceFilter::parse_range("300-200").is_err());
+    }
+
+    #[test]
+    fn first_divergence_is_found() {
+        let left = Cursor::new("a\nb\nc\nd\n");
+        let right = Cursor::new("a\nb\nx\n");
+        let divergence = diff_traces(left, right).unwrap().unwrap();
+        assert_eq!(3, divergence.line);
+        assert_eq!(Some(String::from("c")), divergence.left);
+        assert_eq!(Some(String::from("x")), divergence.right);
+        assert_eq!(vec!["a", "b"], divergence.context);
+
+        let same = diff_traces(Cursor::new("a\n"), Cursor::new("a\n")).unwrap();
+        assert_eq!(None, same);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Notices when a file is modified by polling its modification time
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// A modification time that differs from the last one seen. It is only
+    /// reported once it stays the same for a poll, so that files are not read
+    /// while an assembler is still writing them.
+    pending: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> FileWatcher {
+        FileWatcher {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            pending: None,
+        }
+    }
+
+    /// Returns true once when the file changed since the last time it did
+    pub fn poll(&mut self) -> bool {
+        let current = modified(&self.path);
+        if current.is_none() || current == self.modified {
+            self.pending = None;
+            return false;
+        }
+
+        if self.pending == current {
+            self.modified = current;
+            self.pending = None;
+            true
+        } else {
+            self.pending = current;
+            false
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::thread;
+    use std::time::Duration;
+    use testing::temp_path;
+
+    #[test]
+    fn change_is_reported_once_the_file_settles() {
+        let path = temp_path("watcher.ch8");
+        File::create(&path).unwrap().write_all(&[0x12, 0x00]).unwrap();
+        let mut watcher = FileWatcher::new(&path);
+        assert!(!watcher.poll());
+
+        thread::sleep(Duration::from_millis(20));
+        File::create(&path).unwrap().write_all(&[0x12, 0x02]).unwrap();
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,16 @@
+use std::process::Command;
+
+/// Runs the ROMs in tests/roms.yml through the chip8-test harness, so that
+/// changes to the emulator that alter what they draw are noticed
+#[test]
+fn bundled_rom_manifest_passes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-test"))
+        .arg("tests/roms.yml")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
@@ -0,0 +1,462 @@
+//! Runs small programs through `Cpu::cycle` under every quirk profile and
+//! checks the registers, memory and screen they leave behind, following the
+//! expectations of the well-known CHIP-8 test ROMs (opcode, flags, quirks and
+//! keypad tests). Every program ends in a jump to itself.
+
+extern crate chip8;
+
+use chip8::cpu::{Cpu, PROGRAM_START};
+use chip8::display::WIDTH;
+use chip8::quirks::Quirks;
+
+const CLOCK_SPEED: u32 = 360;
+const MAX_CYCLES: u32 = 10_000;
+
+struct Profile {
+    name: &'static str,
+    quirks: Quirks,
+}
+
+/// The interpreters ROMs are commonly written for
+fn profiles() -> Vec<Profile> {
+    vec![
+        Profile {
+            name: "default",
+            quirks: Quirks::default(),
+        },
+        Profile {
+            name: "cosmac-vip",
+            quirks: Quirks {
+                shift_uses_vy: true,
+                load_store_increments_index: true,
+                logic_resets_vf: true,
+                clip_sprites: true,
+            },
+        },
+        Profile {
+            name: "wrapping",
+            quirks: Quirks {
+                clip_sprites: false,
+                ..Quirks::default()
+            },
+        },
+    ]
+}
+
+fn load(program: &[u16], profile: &Profile) -> Cpu {
+    let rom = program
+        .iter()
+        .flat_map(|opcode| vec![(opcode >> 8) as u8, *opcode as u8])
+        .collect();
+    let mut cpu = Cpu::new(rom, CLOCK_SPEED, false);
+    cpu.set_quirks(profile.quirks);
+    cpu
+}
+
+/// Cycles until the program reaches a jump to itself
+fn run_to_halt(cpu: &mut Cpu, profile: &Profile) {
+    for _ in 0..MAX_CYCLES {
+        let pc = cpu.pc();
+        let opcode = {
+            let memory = cpu.state().memory;
+            (memory[pc as usize] as u16) << 8 | memory[pc as usize + 1] as u16
+        };
+        if opcode == 0x1000 | pc {
+            return;
+        }
+        assert!(!cpu.faulted, "{}: faulted at 0x{:03X}", profile.name, pc);
+        cpu.cycle();
+    }
+    panic!(
+        "{}: did not halt, stuck at 0x{:03X}",
+        profile.name,
+        cpu.pc()
+    );
+}
+
+fn run(program: &[u16], profile: &Profile) -> Cpu {
+    let mut cpu = load(program, profile);
+    run_to_halt(&mut cpu, profile);
+    cpu
+}
+
+/// The address of the `index`th instruction of a program
+fn address(index: u16) -> u16 {
+    PROGRAM_START as u16 + index * 2
+}
+
+#[test]
+fn arithmetic_sets_carry_and_borrow() {
+    // (opcode, VX, VY, result, VF)
+    let cases = [
+        (0x8124, 0x10, 0x20, 0x30, 0),
+        (0x8124, 0xF0, 0x20, 0x10, 1),
+        (0x8124, 0xFF, 0x01, 0x00, 1),
+        (0x8125, 0x30, 0x10, 0x20, 1),
+        (0x8125, 0x10, 0x10, 0x00, 1),
+        (0x8125, 0x10, 0x30, 0xE0, 0),
+        (0x8127, 0x10, 0x30, 0x20, 1),
+        (0x8127, 0x30, 0x30, 0x00, 1),
+        (0x8127, 0x30, 0x10, 0xE0, 0),
+    ];
+    for profile in profiles() {
+        for &(opcode, x, y, result, flag) in cases.iter() {
+            // LD V1, x; LD V2, y; <op> V1, V2; LD V3, VF
+            let cpu = run(
+                &[0x6100 | x, 0x6200 | y, opcode, 0x83F0, 0x1000 | address(4)],
+                &profile,
+            );
+            let registers = cpu.state().registers;
+            let case = format!("{}: {:04X} with {:02X}, {:02X}", profile.name, opcode, x, y);
+            assert_eq!(result as u8, registers[0x1], "{}", case);
+            assert_eq!(flag, registers[0x3], "{}", case);
+            assert_eq!(y as u8, registers[0x2], "{}", case);
+        }
+    }
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_destination() {
+    // (opcode, VF, VY, VF afterwards)
+    let cases = [
+        (0x8F14, 0xFF, 0x01, 1),
+        (0x8F14, 0x01, 0x01, 0),
+        (0x8F15, 0x01, 0x02, 0),
+        (0x8F15, 0x02, 0x01, 1),
+        (0x8F17, 0x02, 0x01, 0),
+        (0x8F17, 0x01, 0x02, 1),
+        (0x8F16, 0x02, 0x02, 0),
+        (0x8F1E, 0x80, 0x80, 1),
+    ];
+    for profile in profiles() {
+        for &(opcode, vf, y, flag) in cases.iter() {
+            // LD VF, vf; LD V1, y; <op> VF, V1
+            let cpu = run(
+                &[0x6F00 | vf, 0x6100 | y, opcode, 0x1000 | address(3)],
+                &profile,
+            );
+            let case = format!(
+                "{}: {:04X} with {:02X}, {:02X}",
+                profile.name, opcode, vf, y
+            );
+            assert_eq!(flag, cpu.state().registers[0xF], "{}", case);
+        }
+    }
+}
+
+#[test]
+fn shifts_set_vf_to_the_bit_shifted_out() {
+    // (opcode, VX, VY, (result, VF) shifting VX, (result, VF) shifting VY)
+    let cases = [
+        (0x8126, 0x05, 0x80, (0x02, 1), (0x40, 0)),
+        (0x8126, 0x80, 0x05, (0x40, 0), (0x02, 1)),
+        (0x812E, 0x81, 0x41, (0x02, 1), (0x82, 0)),
+        (0x812E, 0x41, 0x81, (0x82, 0), (0x02, 1)),
+    ];
+    for profile in profiles() {
+        for &(opcode, x, y, from_x, from_y) in cases.iter() {
+            let (result, flag) = if profile.quirks.shift_uses_vy {
+                from_y
+            } else {
+                from_x
+            };
+            let cpu = run(
+                &[0x6100 | x, 0x6200 | y, opcode, 0x83F0, 0x1000 | address(4)],
+                &profile,
+            );
+            let registers = cpu.state().registers;
+            let case = format!("{}: {:04X} with {:02X}, {:02X}", profile.name, opcode, x, y);
+            assert_eq!(result as u8, registers[0x1], "{}", case);
+            assert_eq!(flag, registers[0x3], "{}", case);
+        }
+    }
+}
+
+#[test]
+fn logic_and_loads() {
+    // (opcode, result)
+    let cases = [
+        (0x8120, 0x0A),
+        (0x8121, 0x0E),
+        (0x8122, 0x08),
+        (0x8123, 0x06),
+    ];
+    for profile in profiles() {
+        for &(opcode, result) in cases.iter() {
+            // LD VF, 1; LD V1, 0x0C; LD V2, 0x0A; <op> V1, V2
+            let cpu = run(
+                &[0x6F01, 0x610C, 0x620A, opcode, 0x1000 | address(4)],
+                &profile,
+            );
+            let registers = cpu.state().registers;
+            let case = format!("{}: {:04X}", profile.name, opcode);
+            assert_eq!(result, registers[0x1], "{}", case);
+            let flag = if opcode != 0x8120 && profile.quirks.logic_resets_vf {
+                0
+            } else {
+                1
+            };
+            assert_eq!(flag, registers[0xF], "{}", case);
+        }
+
+        // LD V4, 0xFE; ADD V4, 3; RND V5, 0; RND V6, 0x0F
+        let cpu = run(
+            &[0x64FE, 0x7403, 0xC500, 0xC60F, 0x1000 | address(4)],
+            &profile,
+        );
+        let registers = cpu.state().registers;
+        assert_eq!(0x01, registers[0x4], "{}: 7XNN wraps", profile.name);
+        assert_eq!(0x00, registers[0xF], "{}: 7XNN leaves VF", profile.name);
+        assert_eq!(0x00, registers[0x5], "{}: CXNN masks", profile.name);
+        assert!(registers[0x6] <= 0x0F, "{}: CXNN masks", profile.name);
+    }
+}
+
+#[test]
+fn skips_jumps_and_subroutines() {
+    let program = [
+        0x6005, // 200: LD V0, 5
+        0x6105, // 202: LD V1, 5
+        0x6206, // 204: LD V2, 6
+        0x3005, // 206: SE V0, 5
+        0x7A01, // 208: ADD VA, 1
+        0x4006, // 20A: SNE V0, 6
+        0x7A01, // 20C: ADD VA, 1
+        0x5010, // 20E: SE V0, V1
+        0x7A01, // 210: ADD VA, 1
+        0x9020, // 212: SNE V0, V2
+        0x7A01, // 214: ADD VA, 1
+        0x3006, // 216: SE V0, 6
+        0x7B01, // 218: ADD VB, 1
+        0x4005, // 21A: SNE V0, 5
+        0x7B01, // 21C: ADD VB, 1
+        0x5020, // 21E: SE V0, V2
+        0x7B01, // 220: ADD VB, 1
+        0x9010, // 222: SNE V0, V1
+        0x7B01, // 224: ADD VB, 1
+        0x0300, // 226: SYS 0x300
+        0x6000, // 228: LD V0, 0
+        0x2236, // 22A: CALL 0x236
+        0x7001, // 22C: ADD V0, 1
+        0xB230, // 22E: JP V0, 0x230
+        0x1234, // 230: JP 0x234
+        0x1232, // 232: JP 0x232, reached through JP V0
+        0x1234, // 234: JP 0x234
+        0x223A, // 236: CALL 0x23A
+        0x00EE, // 238: RET
+        0x7001, // 23A: ADD V0, 1
+        0x00EE, // 23C: RET
+    ];
+    for profile in profiles() {
+        let cpu = run(&program, &profile);
+        let state = cpu.state();
+        assert_eq!(0x00, state.registers[0xA], "{}: skips taken", profile.name);
+        assert_eq!(
+            0x04, state.registers[0xB],
+            "{}: skips not taken",
+            profile.name
+        );
+        assert_eq!(0x02, state.registers[0x0], "{}: calls return", profile.name);
+        assert_eq!(0, state.sp, "{}: stack is empty", profile.name);
+        assert_eq!(0x232, state.pc, "{}: BNNN adds V0", profile.name);
+    }
+}
+
+#[test]
+fn timers_count_down_at_60_hz() {
+    let program = [
+        0x600A, // 200: LD V0, 10
+        0x6103, // 202: LD V1, 3
+        0xF015, // 204: LD DT, V0
+        0xF118, // 206: LD ST, V1
+        0xF207, // 208: LD V2, DT
+        0xF307, // 20A: LD V3, DT
+        0x3300, // 20C: SE V3, 0
+        0x120A, // 20E: JP 0x20A
+        0x1210, // 210: JP 0x210
+    ];
+    for profile in profiles() {
+        let mut cpu = load(&program, &profile);
+        for _ in 0..5 {
+            cpu.cycle();
+        }
+        assert_eq!(10, cpu.state().registers[0x2], "{}", profile.name);
+        assert_eq!(3, cpu.state().sound_timer, "{}", profile.name);
+
+        run_to_halt(&mut cpu, &profile);
+        let state = cpu.state();
+        assert_eq!(0, state.delay_timer, "{}", profile.name);
+        assert_eq!(0, state.sound_timer, "{}", profile.name);
+        assert_eq!(10, cpu.frame(), "{}", profile.name);
+    }
+}
+
+#[test]
+fn keypad() {
+    let program = [
+        0x6305, // 200: LD V3, 5
+        0x6406, // 202: LD V4, 6
+        0xE39E, // 204: SKP V3
+        0x7B01, // 206: ADD VB, 1
+        0xE3A1, // 208: SKNP V3
+        0x7A01, // 20A: ADD VA, 1
+        0xE49E, // 20C: SKP V4
+        0x7A01, // 20E: ADD VA, 1
+        0xE4A1, // 210: SKNP V4
+        0x7B01, // 212: ADD VB, 1
+        0xF00A, // 214: LD V0, K
+        0x1216, // 216: JP 0x216
+    ];
+    for profile in profiles() {
+        let mut cpu = load(&program, &profile);
+        cpu.set_key(0x5, true);
+        for _ in 0..8 {
+            cpu.cycle();
+        }
+        cpu.set_key(0x5, false);
+        for _ in 0..10 {
+            cpu.cycle();
+        }
+        assert_eq!(0x214, cpu.pc(), "{}: FX0A waits", profile.name);
+
+        cpu.set_key(0xE, true);
+        run_to_halt(&mut cpu, &profile);
+        let registers = cpu.state().registers;
+        assert_eq!(0x00, registers[0xB], "{}: skips taken", profile.name);
+        assert_eq!(0x02, registers[0xA], "{}: skips not taken", profile.name);
+        assert_eq!(
+            0x0E, registers[0x0],
+            "{}: FX0A stores the key",
+            profile.name
+        );
+    }
+}
+
+#[test]
+fn memory_and_index() {
+    let program = [
+        0x6001, // 200: LD V0, 1
+        0x6102, // 202: LD V1, 2
+        0x6203, // 204: LD V2, 3
+        0xA300, // 206: LD I, 0x300
+        0xF255, // 208: LD [I], V2
+        0x6000, // 20A: LD V0, 0
+        0x6100, // 20C: LD V1, 0
+        0x6200, // 20E: LD V2, 0
+        0xA300, // 210: LD I, 0x300
+        0xF165, // 212: LD V1, [I]
+        0x64FE, // 214: LD V4, 254
+        0xA310, // 216: LD I, 0x310
+        0xF433, // 218: LD B, V4
+        0x6502, // 21A: LD V5, 2
+        0xF51E, // 21C: ADD I, V5
+        0x121E, // 21E: JP 0x21E
+    ];
+    for profile in profiles() {
+        let mut cpu = load(&program, &profile);
+        for _ in 0..5 {
+            cpu.cycle();
+        }
+        let increments = profile.quirks.load_store_increments_index;
+        let expected_index = if increments { 0x303 } else { 0x300 };
+        assert_eq!(expected_index, cpu.state().index, "{}: FX55", profile.name);
+        for _ in 0..5 {
+            cpu.cycle();
+        }
+        let expected_index = if increments { 0x302 } else { 0x300 };
+        assert_eq!(expected_index, cpu.state().index, "{}: FX65", profile.name);
+
+        run_to_halt(&mut cpu, &profile);
+        let state = cpu.state();
+        assert_eq!([1, 2, 3, 0], state.memory[0x300..0x304], "{}", profile.name);
+        assert_eq!([1, 2, 0], state.registers[0x0..0x3], "{}", profile.name);
+        assert_eq!([2, 5, 4], state.memory[0x310..0x313], "{}", profile.name);
+        assert_eq!(0x312, state.index, "{}: FX1E", profile.name);
+    }
+}
+
+/// Eight pixels starting at `x` packed into a byte, the leftmost in the
+/// highest bit
+fn pixels(cpu: &mut Cpu, x: usize, y: usize) -> u8 {
+    let screen = cpu.get_screen();
+    (0..8).fold(0, |byte, i| byte << 1 | screen[y][(x + i) % WIDTH] as u8)
+}
+
+fn lit_pixels(cpu: &mut Cpu) -> usize {
+    let screen = cpu.get_screen();
+    screen
+        .iter()
+        .map(|row| row.iter().filter(|&&pixel| pixel).count())
+        .sum()
+}
+
+#[test]
+fn draw_collision_and_font() {
+    let program = [
+        0x00E0, // 200: CLS
+        0x6007, // 202: LD V0, 7
+        0xF029, // 204: LD F, V0
+        0x6108, // 206: LD V1, 8
+        0x6204, // 208: LD V2, 4
+        0xD125, // 20A: DRW V1, V2, 5
+        0x8AF0, // 20C: LD VA, VF
+        0xD125, // 20E: DRW V1, V2, 5
+        0x8BF0, // 210: LD VB, VF
+        0x6000, // 212: LD V0, 0
+        0xF029, // 214: LD F, V0
+        0xD125, // 216: DRW V1, V2, 5
+        0x1218, // 218: JP 0x218
+    ];
+    for profile in profiles() {
+        let mut cpu = run(&program, &profile);
+        let registers = *cpu.state().registers;
+        assert_eq!(0, registers[0xA], "{}: no collision", profile.name);
+        assert_eq!(1, registers[0xB], "{}: collision", profile.name);
+        assert_eq!(0, cpu.state().index, "{}: FX29", profile.name);
+
+        let rows: Vec<u8> = (4..9).map(|y| pixels(&mut cpu, 8, y)).collect();
+        assert_eq!(vec![0xF0, 0x90, 0x90, 0x90, 0xF0], rows, "{}", profile.name);
+        assert_eq!(14, lit_pixels(&mut cpu), "{}", profile.name);
+    }
+}
+
+#[test]
+fn sprites_clip_or_wrap_at_the_edges() {
+    let program = [
+        0x603E, // 200: LD V0, 62
+        0x611E, // 202: LD V1, 30
+        0xA000, // 204: LD I, 0x000
+        0xD015, // 206: DRW V0, V1, 5
+        0x6046, // 208: LD V0, 70
+        0x6122, // 20A: LD V1, 34
+        0xD015, // 20C: DRW V0, V1, 5
+        0x120E, // 20E: JP 0x20E
+    ];
+    for profile in profiles() {
+        let mut cpu = run(&program, &profile);
+        let clipped = profile.quirks.clip_sprites;
+
+        // The digit 0 at (62, 30) crosses both edges
+        let expected = if clipped {
+            [0xC0, 0x80, 0x00, 0x00, 0x00]
+        } else {
+            [0xF0, 0x90, 0x90, 0x90, 0xF0]
+        };
+        let rows = [
+            pixels(&mut cpu, 62, 30),
+            pixels(&mut cpu, 62, 31),
+            pixels(&mut cpu, 62, 0),
+            pixels(&mut cpu, 62, 1),
+            pixels(&mut cpu, 62, 2),
+        ];
+        assert_eq!(expected, rows, "{}", profile.name);
+
+        // (70, 34) is off the screen, so the sprite starts at (6, 2) whether
+        // or not it is clipped
+        let rows: Vec<u8> = (2..7).map(|y| pixels(&mut cpu, 6, y)).collect();
+        assert_eq!(vec![0xF0, 0x90, 0x90, 0x90, 0xF0], rows, "{}", profile.name);
+
+        let lit = lit_pixels(&mut cpu);
+        assert_eq!(if clipped { 17 } else { 28 }, lit, "{}", profile.name);
+    }
+}
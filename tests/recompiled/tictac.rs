@@ -0,0 +1,1237 @@
+// Recompiled from tictac.rom by `chip8 --recompile`, do not edit
+
+use chip8::cpu::Cpu;
+use chip8::instruction::Instruction::*;
+
+/// Runs up to `budget` instructions and returns how many were run. Code that
+/// was not found ahead of time or was modified since is left to the
+/// interpreter.
+pub fn run(cpu: &mut Cpu, budget: u32) -> u32 {
+    let mut executed = 0;
+    while executed < budget && !cpu.faulted {
+        let remaining = budget - executed;
+        let ran = match cpu.pc() {
+            0x200 => block_200(cpu, remaining),
+            0x218 => block_218(cpu, remaining),
+            0x21C => block_21c(cpu, remaining),
+            0x23E => block_23e(cpu, remaining),
+            0x244 => block_244(cpu, remaining),
+            0x246 => block_246(cpu, remaining),
+            0x24C => block_24c(cpu, remaining),
+            0x24E => block_24e(cpu, remaining),
+            0x254 => block_254(cpu, remaining),
+            0x25A => block_25a(cpu, remaining),
+            0x25C => block_25c(cpu, remaining),
+            0x260 => block_260(cpu, remaining),
+            0x266 => block_266(cpu, remaining),
+            0x268 => block_268(cpu, remaining),
+            0x26A => block_26a(cpu, remaining),
+            0x26C => block_26c(cpu, remaining),
+            0x276 => block_276(cpu, remaining),
+            0x278 => block_278(cpu, remaining),
+            0x27A => block_27a(cpu, remaining),
+            0x27C => block_27c(cpu, remaining),
+            0x282 => block_282(cpu, remaining),
+            0x286 => block_286(cpu, remaining),
+            0x288 => block_288(cpu, remaining),
+            0x28A => block_28a(cpu, remaining),
+            0x2A2 => block_2a2(cpu, remaining),
+            0x2A4 => block_2a4(cpu, remaining),
+            0x2A8 => block_2a8(cpu, remaining),
+            0x2AA => block_2aa(cpu, remaining),
+            0x2AC => block_2ac(cpu, remaining),
+            0x2B4 => block_2b4(cpu, remaining),
+            0x2B8 => block_2b8(cpu, remaining),
+            0x2BA => block_2ba(cpu, remaining),
+            0x2C0 => block_2c0(cpu, remaining),
+            0x2C2 => block_2c2(cpu, remaining),
+            0x2C4 => block_2c4(cpu, remaining),
+            0x2C6 => block_2c6(cpu, remaining),
+            0x2C8 => block_2c8(cpu, remaining),
+            0x2D8 => block_2d8(cpu, remaining),
+            0x2DC => block_2dc(cpu, remaining),
+            0x2E0 => block_2e0(cpu, remaining),
+            0x2E6 => block_2e6(cpu, remaining),
+            0x2EA => block_2ea(cpu, remaining),
+            0x2EE => block_2ee(cpu, remaining),
+            0x2F4 => block_2f4(cpu, remaining),
+            0x2F8 => block_2f8(cpu, remaining),
+            0x2FC => block_2fc(cpu, remaining),
+            0x302 => block_302(cpu, remaining),
+            0x306 => block_306(cpu, remaining),
+            0x30A => block_30a(cpu, remaining),
+            0x310 => block_310(cpu, remaining),
+            0x314 => block_314(cpu, remaining),
+            0x318 => block_318(cpu, remaining),
+            0x31E => block_31e(cpu, remaining),
+            0x322 => block_322(cpu, remaining),
+            0x326 => block_326(cpu, remaining),
+            0x32C => block_32c(cpu, remaining),
+            0x330 => block_330(cpu, remaining),
+            0x334 => block_334(cpu, remaining),
+            0x33A => block_33a(cpu, remaining),
+            0x33E => block_33e(cpu, remaining),
+            0x342 => block_342(cpu, remaining),
+            0x344 => block_344(cpu, remaining),
+            0x34A => block_34a(cpu, remaining),
+            0x34C => block_34c(cpu, remaining),
+            0x34E => block_34e(cpu, remaining),
+            0x350 => block_350(cpu, remaining),
+            0x352 => block_352(cpu, remaining),
+            0x354 => block_354(cpu, remaining),
+            0x356 => block_356(cpu, remaining),
+            0x35A => block_35a(cpu, remaining),
+            0x35C => block_35c(cpu, remaining),
+            0x35E => block_35e(cpu, remaining),
+            0x360 => block_360(cpu, remaining),
+            0x364 => block_364(cpu, remaining),
+            0x366 => block_366(cpu, remaining),
+            0x378 => block_378(cpu, remaining),
+            0x388 => block_388(cpu, remaining),
+            0x38E => block_38e(cpu, remaining),
+            0x392 => block_392(cpu, remaining),
+            0x394 => block_394(cpu, remaining),
+            _ => 0,
+        };
+        if ran > 0 {
+            executed += ran;
+        } else {
+            cpu.cycle();
+            executed += 1;
+        }
+    }
+    executed
+}
+
+fn block_200(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x200, 0x202) {
+        return 0;
+    }
+    // JP 0x218
+    cpu.poke_pc(0x218);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_218(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x218, 0x21C) {
+        return 0;
+    }
+    // LD VB, 0
+    cpu.poke_register(0xB, 0x00);
+    // LD VC, 0
+    cpu.poke_register(0xC, 0x00);
+    cpu.poke_pc(0x21C);
+    cpu.retire(2, 12);
+    2
+}
+
+fn block_21c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 17 || cpu.code_modified(0x21C, 0x23E) {
+        return 0;
+    }
+    // LD V0, VB
+    cpu.poke_register(0x0, cpu.registers()[0xB]);
+    // LD V1, VC
+    cpu.poke_register(0x1, cpu.registers()[0xC]);
+    // LD I, 0x3E6
+    cpu.poke_index(0x3E6);
+    cpu.poke_pc(0x222);
+    cpu.retire(3, 100);
+    cpu.execute(0xF155, StoreIndex(1));
+    if cpu.pc() != 0x224 {
+        return 4;
+    }
+    // LD I, 0x3C4
+    cpu.poke_index(0x3C4);
+    cpu.poke_pc(0x226);
+    cpu.retire(1, 12);
+    cpu.execute(0xFF65, ReadIndex(15));
+    if cpu.pc() != 0x228 {
+        return 6;
+    }
+    // LD I, 0x3B4
+    cpu.poke_index(0x3B4);
+    cpu.poke_pc(0x22A);
+    cpu.retire(1, 12);
+    cpu.execute(0xFF55, StoreIndex(15));
+    if cpu.pc() != 0x22C {
+        return 8;
+    }
+    // LD I, 0x3E6
+    cpu.poke_index(0x3E6);
+    cpu.poke_pc(0x22E);
+    cpu.retire(1, 12);
+    cpu.execute(0xF165, ReadIndex(1));
+    if cpu.pc() != 0x230 {
+        return 10;
+    }
+    // LD VB, V0
+    cpu.poke_register(0xB, cpu.registers()[0x0]);
+    // LD VC, V1
+    cpu.poke_register(0xC, cpu.registers()[0x1]);
+    cpu.poke_pc(0x234);
+    cpu.retire(2, 88);
+    cpu.execute(0x00E0, Clear);
+    if cpu.pc() != 0x236 {
+        return 13;
+    }
+    // LD VE, 1
+    cpu.poke_register(0xE, 0x01);
+    // LD V0, 19
+    cpu.poke_register(0x0, 0x13);
+    // LD V1, 3
+    cpu.poke_register(0x1, 0x03);
+    // LD I, 0x39A
+    cpu.poke_index(0x39A);
+    cpu.poke_pc(0x23E);
+    cpu.retire(4, 30);
+    17
+}
+
+fn block_23e(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x23E, 0x244) {
+        return 0;
+    }
+    cpu.execute(0xD011, Draw(0, 1, 1));
+    if cpu.pc() != 0x240 {
+        return 1;
+    }
+    // ADD V0, 8
+    cpu.poke_register(0x0, cpu.registers()[0x0].wrapping_add(0x08));
+    // SE V0, 43
+    cpu.poke_pc(if cpu.registers()[0x0] == 0x2B { 0x246 } else { 0x244 });
+    cpu.retire(2, 22);
+    3
+}
+
+fn block_244(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x244, 0x246) {
+        return 0;
+    }
+    // JP 0x23E
+    cpu.poke_pc(0x23E);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_246(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x246, 0x24C) {
+        return 0;
+    }
+    // LD V0, 19
+    cpu.poke_register(0x0, 0x13);
+    // ADD V1, 8
+    cpu.poke_register(0x1, cpu.registers()[0x1].wrapping_add(0x08));
+    // SE V1, 35
+    cpu.poke_pc(if cpu.registers()[0x1] == 0x23 { 0x24E } else { 0x24C });
+    cpu.retire(3, 28);
+    3
+}
+
+fn block_24c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x24C, 0x24E) {
+        return 0;
+    }
+    // JP 0x23E
+    cpu.poke_pc(0x23E);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_24e(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x24E, 0x254) {
+        return 0;
+    }
+    // LD V0, 19
+    cpu.poke_register(0x0, 0x13);
+    // LD V1, 3
+    cpu.poke_register(0x1, 0x03);
+    // LD I, 0x39B
+    cpu.poke_index(0x39B);
+    cpu.poke_pc(0x254);
+    cpu.retire(3, 24);
+    3
+}
+
+fn block_254(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x254, 0x25A) {
+        return 0;
+    }
+    cpu.execute(0xD01F, Draw(0, 1, 15));
+    if cpu.pc() != 0x256 {
+        return 1;
+    }
+    // ADD V0, 8
+    cpu.poke_register(0x0, cpu.registers()[0x0].wrapping_add(0x08));
+    // SE V0, 51
+    cpu.poke_pc(if cpu.registers()[0x0] == 0x33 { 0x25C } else { 0x25A });
+    cpu.retire(2, 22);
+    3
+}
+
+fn block_25a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x25A, 0x25C) {
+        return 0;
+    }
+    // JP 0x254
+    cpu.poke_pc(0x254);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_25c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x25C, 0x260) {
+        return 0;
+    }
+    // LD V0, 19
+    cpu.poke_register(0x0, 0x13);
+    // ADD V1, 15
+    cpu.poke_register(0x1, cpu.registers()[0x1].wrapping_add(0x0F));
+    cpu.poke_pc(0x260);
+    cpu.retire(2, 16);
+    2
+}
+
+fn block_260(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x260, 0x266) {
+        return 0;
+    }
+    cpu.execute(0xD01A, Draw(0, 1, 10));
+    if cpu.pc() != 0x262 {
+        return 1;
+    }
+    // ADD V0, 8
+    cpu.poke_register(0x0, cpu.registers()[0x0].wrapping_add(0x08));
+    // SE V0, 51
+    cpu.poke_pc(if cpu.registers()[0x0] == 0x33 { 0x268 } else { 0x266 });
+    cpu.retire(2, 22);
+    3
+}
+
+fn block_266(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x266, 0x268) {
+        return 0;
+    }
+    // JP 0x260
+    cpu.poke_pc(0x260);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_268(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x268, 0x26A) {
+        return 0;
+    }
+    cpu.execute(0x2366, Call(870));
+    1
+}
+
+fn block_26a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x26A, 0x26C) {
+        return 0;
+    }
+    cpu.execute(0xF00A, WaitKey(0));
+    1
+}
+
+fn block_26c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 5 || cpu.code_modified(0x26C, 0x276) {
+        return 0;
+    }
+    // LD V1, V0
+    cpu.poke_register(0x1, cpu.registers()[0x0]);
+    // LD I, 0x3B4
+    cpu.poke_index(0x3B4);
+    // ADD I, V0
+    cpu.poke_index(cpu.index() + cpu.registers()[0x0] as u32);
+    cpu.poke_pc(0x272);
+    cpu.retire(3, 75);
+    cpu.execute(0xF065, ReadIndex(0));
+    if cpu.pc() != 0x274 {
+        return 4;
+    }
+    // SNE V0, 0
+    cpu.poke_pc(if cpu.registers()[0x0] != 0x00 { 0x278 } else { 0x276 });
+    cpu.retire(1, 12);
+    5
+}
+
+fn block_276(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x276, 0x278) {
+        return 0;
+    }
+    // JP 0x28A
+    cpu.poke_pc(0x28A);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_278(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x278, 0x27A) {
+        return 0;
+    }
+    cpu.execute(0x227C, Call(636));
+    1
+}
+
+fn block_27a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x27A, 0x27C) {
+        return 0;
+    }
+    // JP 0x26A
+    cpu.poke_pc(0x26A);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_27c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x27C, 0x282) {
+        return 0;
+    }
+    // LD V0, 16
+    cpu.poke_register(0x0, 0x10);
+    cpu.poke_pc(0x27E);
+    cpu.retire(1, 6);
+    cpu.execute(0xF018, SetSound(0));
+    if cpu.pc() != 0x280 {
+        return 2;
+    }
+    cpu.execute(0xF015, SetDelay(0));
+    3
+}
+
+fn block_282(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x282, 0x286) {
+        return 0;
+    }
+    cpu.execute(0xF007, LoadDelay(0));
+    if cpu.pc() != 0x284 {
+        return 1;
+    }
+    // SE V0, 0
+    cpu.poke_pc(if cpu.registers()[0x0] == 0x00 { 0x288 } else { 0x286 });
+    cpu.retire(1, 12);
+    2
+}
+
+fn block_286(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x286, 0x288) {
+        return 0;
+    }
+    // JP 0x282
+    cpu.poke_pc(0x282);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_288(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x288, 0x28A) {
+        return 0;
+    }
+    cpu.execute(0x00EE, Return);
+    1
+}
+
+fn block_28a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 12 || cpu.code_modified(0x28A, 0x2A2) {
+        return 0;
+    }
+    // LD V0, 2
+    cpu.poke_register(0x0, 0x02);
+    // XOR VE, V0
+    cpu.poke_register(0xE, cpu.registers()[0xE] ^ cpu.registers()[0x0]);
+    if cpu.quirks().logic_resets_vf {
+        cpu.poke_register(0xF, 0);
+    }
+    // LD V0, VE
+    cpu.poke_register(0x0, cpu.registers()[0xE]);
+    cpu.poke_pc(0x290);
+    cpu.retire(3, 94);
+    cpu.execute(0xF055, StoreIndex(0));
+    if cpu.pc() != 0x292 {
+        return 4;
+    }
+    // LD I, 0x3D4
+    cpu.poke_index(0x3D4);
+    // LD V0, V1
+    cpu.poke_register(0x0, cpu.registers()[0x1]);
+    // ADD V0, 255
+    cpu.poke_register(0x0, cpu.registers()[0x0].wrapping_add(0xFF));
+    // ADD V0, V0
+    let (sum, carry) = cpu.registers()[0x0].overflowing_add(cpu.registers()[0x0]);
+    cpu.poke_register(0x0, sum);
+    cpu.poke_register(0xF, carry as u8);
+    // ADD I, V0
+    cpu.poke_index(cpu.index() + cpu.registers()[0x0] as u32);
+    cpu.poke_pc(0x29C);
+    cpu.retire(5, 129);
+    cpu.execute(0xF165, ReadIndex(1));
+    if cpu.pc() != 0x29E {
+        return 10;
+    }
+    // LD I, 0x3AA
+    cpu.poke_index(0x3AA);
+    // SE VE, 3
+    cpu.poke_pc(if cpu.registers()[0xE] == 0x03 { 0x2A4 } else { 0x2A2 });
+    cpu.retire(2, 24);
+    12
+}
+
+fn block_2a2(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2A2, 0x2A4) {
+        return 0;
+    }
+    // LD I, 0x3AF
+    cpu.poke_index(0x3AF);
+    cpu.poke_pc(0x2A4);
+    cpu.retire(1, 12);
+    1
+}
+
+fn block_2a4(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2A4, 0x2A8) {
+        return 0;
+    }
+    cpu.execute(0xD015, Draw(0, 1, 5));
+    if cpu.pc() != 0x2A6 {
+        return 1;
+    }
+    cpu.execute(0x22C8, Call(712));
+    2
+}
+
+fn block_2a8(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2A8, 0x2AA) {
+        return 0;
+    }
+    // SE VA, 0
+    cpu.poke_pc(if cpu.registers()[0xA] == 0x00 { 0x2AC } else { 0x2AA });
+    cpu.retire(1, 12);
+    1
+}
+
+fn block_2aa(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2AA, 0x2AC) {
+        return 0;
+    }
+    // JP 0x21C
+    cpu.poke_pc(0x21C);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_2ac(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 4 || cpu.code_modified(0x2AC, 0x2B4) {
+        return 0;
+    }
+    // LD I, 0x3B4
+    cpu.poke_index(0x3B4);
+    // LD V1, 0
+    cpu.poke_register(0x1, 0x00);
+    // LD V2, 0
+    cpu.poke_register(0x2, 0x00);
+    // LD V3, 1
+    cpu.poke_register(0x3, 0x01);
+    cpu.poke_pc(0x2B4);
+    cpu.retire(4, 30);
+    4
+}
+
+fn block_2b4(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2B4, 0x2B8) {
+        return 0;
+    }
+    cpu.execute(0xF065, ReadIndex(0));
+    if cpu.pc() != 0x2B6 {
+        return 1;
+    }
+    // SE V0, 0
+    cpu.poke_pc(if cpu.registers()[0x0] == 0x00 { 0x2BA } else { 0x2B8 });
+    cpu.retire(1, 12);
+    2
+}
+
+fn block_2b8(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2B8, 0x2BA) {
+        return 0;
+    }
+    // ADD V1, 1
+    cpu.poke_register(0x1, cpu.registers()[0x1].wrapping_add(0x01));
+    cpu.poke_pc(0x2BA);
+    cpu.retire(1, 10);
+    1
+}
+
+fn block_2ba(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x2BA, 0x2C0) {
+        return 0;
+    }
+    // ADD I, V3
+    cpu.poke_index(cpu.index() + cpu.registers()[0x3] as u32);
+    // ADD V2, 1
+    cpu.poke_register(0x2, cpu.registers()[0x2].wrapping_add(0x01));
+    // SE V2, 16
+    cpu.poke_pc(if cpu.registers()[0x2] == 0x10 { 0x2C2 } else { 0x2C0 });
+    cpu.retire(3, 41);
+    3
+}
+
+fn block_2c0(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2C0, 0x2C2) {
+        return 0;
+    }
+    // JP 0x2B4
+    cpu.poke_pc(0x2B4);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_2c2(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2C2, 0x2C4) {
+        return 0;
+    }
+    // SE V1, 16
+    cpu.poke_pc(if cpu.registers()[0x1] == 0x10 { 0x2C6 } else { 0x2C4 });
+    cpu.retire(1, 12);
+    1
+}
+
+fn block_2c4(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2C4, 0x2C6) {
+        return 0;
+    }
+    // JP 0x26A
+    cpu.poke_pc(0x26A);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_2c6(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x2C6, 0x2C8) {
+        return 0;
+    }
+    // JP 0x21C
+    cpu.poke_pc(0x21C);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_2c8(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 8 || cpu.code_modified(0x2C8, 0x2D8) {
+        return 0;
+    }
+    // LD VA, 0
+    cpu.poke_register(0xA, 0x00);
+    // LD I, 0x3B4
+    cpu.poke_index(0x3B4);
+    // LD V0, 1
+    cpu.poke_register(0x0, 0x01);
+    // ADD I, V0
+    cpu.poke_index(cpu.index() + cpu.registers()[0x0] as u32);
+    cpu.poke_pc(0x2D0);
+    cpu.retire(4, 43);
+    cpu.execute(0xF865, ReadIndex(8));
+    if cpu.pc() != 0x2D2 {
+        return 5;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V0
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x0]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2D6);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    8
+}
+
+fn block_2d8(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2D8, 0x2DC) {
+        return 0;
+    }
+    // ADD V9, V1
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x1]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2DA);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_2dc(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2DC, 0x2E0) {
+        return 0;
+    }
+    // ADD V9, V2
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x2]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2DE);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_2e0(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x2E0, 0x2E6) {
+        return 0;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V3
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x3]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2E4);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    3
+}
+
+fn block_2e6(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2E6, 0x2EA) {
+        return 0;
+    }
+    // ADD V9, V4
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x4]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2E8);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_2ea(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2EA, 0x2EE) {
+        return 0;
+    }
+    // ADD V9, V5
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x5]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2EC);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_2ee(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x2EE, 0x2F4) {
+        return 0;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V6
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x6]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2F2);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    3
+}
+
+fn block_2f4(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2F4, 0x2F8) {
+        return 0;
+    }
+    // ADD V9, V7
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x7]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2F6);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_2f8(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x2F8, 0x2FC) {
+        return 0;
+    }
+    // ADD V9, V8
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x8]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x2FA);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_2fc(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x2FC, 0x302) {
+        return 0;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V6
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x6]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x300);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    3
+}
+
+fn block_302(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x302, 0x306) {
+        return 0;
+    }
+    // ADD V9, V3
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x3]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x304);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_306(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x306, 0x30A) {
+        return 0;
+    }
+    // ADD V9, V0
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x0]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x308);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_30a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x30A, 0x310) {
+        return 0;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V7
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x7]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x30E);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    3
+}
+
+fn block_310(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x310, 0x314) {
+        return 0;
+    }
+    // ADD V9, V4
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x4]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x312);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_314(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x314, 0x318) {
+        return 0;
+    }
+    // ADD V9, V1
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x1]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x316);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_318(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x318, 0x31E) {
+        return 0;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V8
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x8]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x31C);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    3
+}
+
+fn block_31e(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x31E, 0x322) {
+        return 0;
+    }
+    // ADD V9, V5
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x5]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x320);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_322(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x322, 0x326) {
+        return 0;
+    }
+    // ADD V9, V2
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x2]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x324);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_326(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x326, 0x32C) {
+        return 0;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V8
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x8]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x32A);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    3
+}
+
+fn block_32c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x32C, 0x330) {
+        return 0;
+    }
+    // ADD V9, V4
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x4]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x32E);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_330(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x330, 0x334) {
+        return 0;
+    }
+    // ADD V9, V0
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x0]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x332);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_334(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x334, 0x33A) {
+        return 0;
+    }
+    // LD V9, 0
+    cpu.poke_register(0x9, 0x00);
+    // ADD V9, V6
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x6]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x338);
+    cpu.retire(2, 50);
+    cpu.execute(0x2344, Call(836));
+    3
+}
+
+fn block_33a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x33A, 0x33E) {
+        return 0;
+    }
+    // ADD V9, V4
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x4]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x33C);
+    cpu.retire(1, 44);
+    cpu.execute(0x2344, Call(836));
+    2
+}
+
+fn block_33e(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x33E, 0x342) {
+        return 0;
+    }
+    // ADD V9, V2
+    let (sum, carry) = cpu.registers()[0x9].overflowing_add(cpu.registers()[0x2]);
+    cpu.poke_register(0x9, sum);
+    cpu.poke_register(0xF, carry as u8);
+    cpu.poke_pc(0x340);
+    cpu.retire(1, 44);
+    cpu.execute(0x234A, Call(842));
+    2
+}
+
+fn block_342(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x342, 0x344) {
+        return 0;
+    }
+    cpu.execute(0x00EE, Return);
+    1
+}
+
+fn block_344(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x344, 0x34A) {
+        return 0;
+    }
+    // SHL V9, V0
+    let value = if cpu.quirks().shift_uses_vy { cpu.registers()[0x0] } else { cpu.registers()[0x9] };
+    cpu.poke_register(0x9, value << 1);
+    cpu.poke_register(0xF, value >> 7);
+    // SHL V9, V0
+    let value = if cpu.quirks().shift_uses_vy { cpu.registers()[0x0] } else { cpu.registers()[0x9] };
+    cpu.poke_register(0x9, value << 1);
+    cpu.poke_register(0xF, value >> 7);
+    cpu.poke_pc(0x348);
+    cpu.retire(2, 88);
+    cpu.execute(0x00EE, Return);
+    3
+}
+
+fn block_34a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x34A, 0x34C) {
+        return 0;
+    }
+    // SNE V9, 21
+    cpu.poke_pc(if cpu.registers()[0x9] != 0x15 { 0x34E } else { 0x34C });
+    cpu.retire(1, 12);
+    1
+}
+
+fn block_34c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x34C, 0x34E) {
+        return 0;
+    }
+    // JP 0x354
+    cpu.poke_pc(0x354);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_34e(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x34E, 0x350) {
+        return 0;
+    }
+    // SNE V9, 63
+    cpu.poke_pc(if cpu.registers()[0x9] != 0x3F { 0x352 } else { 0x350 });
+    cpu.retire(1, 12);
+    1
+}
+
+fn block_350(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x350, 0x352) {
+        return 0;
+    }
+    // JP 0x35A
+    cpu.poke_pc(0x35A);
+    cpu.retire(1, 23);
+    1
+}
+
+fn block_352(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x352, 0x354) {
+        return 0;
+    }
+    cpu.execute(0x00EE, Return);
+    1
+}
+
+fn block_354(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x354, 0x356) {
+        return 0;
+    }
+    cpu.execute(0x2366, Call(870));
+    1
+}
+
+fn block_356(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x356, 0x35A) {
+        return 0;
+    }
+    // ADD VB, 1
+    cpu.poke_register(0xB, cpu.registers()[0xB].wrapping_add(0x01));
+    // JP 0x35E
+    cpu.poke_pc(0x35E);
+    cpu.retire(2, 33);
+    2
+}
+
+fn block_35a(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x35A, 0x35C) {
+        return 0;
+    }
+    cpu.execute(0x2366, Call(870));
+    1
+}
+
+fn block_35c(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x35C, 0x35E) {
+        return 0;
+    }
+    // ADD VC, 1
+    cpu.poke_register(0xC, cpu.registers()[0xC].wrapping_add(0x01));
+    cpu.poke_pc(0x35E);
+    cpu.retire(1, 10);
+    1
+}
+
+fn block_35e(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x35E, 0x360) {
+        return 0;
+    }
+    cpu.execute(0x2366, Call(870));
+    1
+}
+
+fn block_360(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x360, 0x364) {
+        return 0;
+    }
+    // LD VA, 1
+    cpu.poke_register(0xA, 0x01);
+    cpu.poke_pc(0x362);
+    cpu.retire(1, 6);
+    cpu.execute(0xF00A, WaitKey(0));
+    2
+}
+
+fn block_364(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x364, 0x366) {
+        return 0;
+    }
+    cpu.execute(0x00EE, Return);
+    1
+}
+
+fn block_366(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 9 || cpu.code_modified(0x366, 0x378) {
+        return 0;
+    }
+    // LD V3, 5
+    cpu.poke_register(0x3, 0x05);
+    // LD V4, 10
+    cpu.poke_register(0x4, 0x0A);
+    // LD I, 0x3AF
+    cpu.poke_index(0x3AF);
+    cpu.poke_pc(0x36C);
+    cpu.retire(3, 24);
+    cpu.execute(0xD345, Draw(3, 4, 5));
+    if cpu.pc() != 0x36E {
+        return 4;
+    }
+    // LD V3, 2
+    cpu.poke_register(0x3, 0x02);
+    // ADD V4, 6
+    cpu.poke_register(0x4, cpu.registers()[0x4].wrapping_add(0x06));
+    // LD I, 0x3E6
+    cpu.poke_index(0x3E6);
+    cpu.poke_pc(0x374);
+    cpu.retire(3, 28);
+    cpu.execute(0xFB33, LoadBCD(11));
+    if cpu.pc() != 0x376 {
+        return 8;
+    }
+    cpu.execute(0x2388, Call(904));
+    9
+}
+
+fn block_378(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 8 || cpu.code_modified(0x378, 0x388) {
+        return 0;
+    }
+    // LD V3, 50
+    cpu.poke_register(0x3, 0x32);
+    // LD V4, 10
+    cpu.poke_register(0x4, 0x0A);
+    // LD I, 0x3AA
+    cpu.poke_index(0x3AA);
+    cpu.poke_pc(0x37E);
+    cpu.retire(3, 24);
+    cpu.execute(0xD345, Draw(3, 4, 5));
+    if cpu.pc() != 0x380 {
+        return 4;
+    }
+    // LD V3, 47
+    cpu.poke_register(0x3, 0x2F);
+    // ADD V4, 6
+    cpu.poke_register(0x4, cpu.registers()[0x4].wrapping_add(0x06));
+    // LD I, 0x3E6
+    cpu.poke_index(0x3E6);
+    cpu.poke_pc(0x386);
+    cpu.retire(3, 28);
+    cpu.execute(0xFC33, LoadBCD(12));
+    8
+}
+
+fn block_388(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x388, 0x38E) {
+        return 0;
+    }
+    cpu.execute(0xF265, ReadIndex(2));
+    if cpu.pc() != 0x38A {
+        return 1;
+    }
+    cpu.execute(0xF029, LoadDigit(0));
+    if cpu.pc() != 0x38C {
+        return 2;
+    }
+    cpu.execute(0x2394, Call(916));
+    3
+}
+
+fn block_38e(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 2 || cpu.code_modified(0x38E, 0x392) {
+        return 0;
+    }
+    cpu.execute(0xF129, LoadDigit(1));
+    if cpu.pc() != 0x390 {
+        return 1;
+    }
+    cpu.execute(0x2394, Call(916));
+    2
+}
+
+fn block_392(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 1 || cpu.code_modified(0x392, 0x394) {
+        return 0;
+    }
+    cpu.execute(0xF229, LoadDigit(2));
+    1
+}
+
+fn block_394(cpu: &mut Cpu, remaining: u32) -> u32 {
+    if remaining < 3 || cpu.code_modified(0x394, 0x39A) {
+        return 0;
+    }
+    cpu.execute(0xD345, Draw(3, 4, 5));
+    if cpu.pc() != 0x396 {
+        return 1;
+    }
+    // ADD V3, 5
+    cpu.poke_register(0x3, cpu.registers()[0x3].wrapping_add(0x05));
+    cpu.poke_pc(0x398);
+    cpu.retire(1, 10);
+    cpu.execute(0x00EE, Return);
+    3
+}
@@ -0,0 +1,39 @@
+extern crate chip8;
+
+#[path = "recompiled/tictac.rs"]
+mod tictac;
+
+use chip8::cpu::Cpu;
+use std::fs;
+
+const ROM: &str = "roms/tictac.rom";
+const INSTRUCTIONS_PER_FRAME: u32 = 6;
+
+#[test]
+fn recompiled_rom_draws_the_same_frames_as_the_interpreter() {
+    let rom = fs::read(ROM).unwrap();
+    let mut interpreted = Cpu::new(rom.clone(), 360, false);
+    let mut recompiled = Cpu::new(rom, 360, false);
+
+    for frame in 0..120 {
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            interpreted.cycle();
+        }
+        tictac::run(&mut recompiled, INSTRUCTIONS_PER_FRAME);
+
+        assert_eq!(interpreted.pc(), recompiled.pc(), "frame {}", frame);
+        assert_eq!(
+            interpreted.state().registers,
+            recompiled.state().registers,
+            "frame {}",
+            frame
+        );
+        assert!(
+            interpreted.get_screen()[..] == recompiled.get_screen()[..],
+            "frame {}",
+            frame
+        );
+    }
+    let screen = recompiled.get_screen();
+    assert!(screen.iter().any(|row| row.iter().any(|&pixel| pixel)));
+}